@@ -1,205 +1,1551 @@
-use crate::utils::{DocContent, ImageContent};
+use crate::encryption::decrypt_package;
+use crate::fields::{field_value, FieldStack, FieldValue};
+use crate::numbering::{lower_roman, ListTracker};
+use crate::package::{
+    document_part_path, open_docx_file, read_body_markup, read_core_properties, read_style_bases,
+    AnchoredImage, BodyMarkup, Equation, TableMarkup,
+};
+use crate::styles::{paragraph_style_id, StyleSheet};
+use crate::utils::{
+    emus_to_mm, is_rtl_char, Alignment, Border, BorderStyle, CellBorders, CellContent, Comment,
+    DocContent, DocxDocument, FontFamily, FontMap, ImageContent, ImageSize, Indentation,
+    LineSpacing, ListItem, Note, Paragraph, RgbColor, RowContent, Section, SourceFile, Strike,
+    TableBorders, TableContent, TextRun, VerticalAlign,
+};
 
-use anyhow::{Context, Result};
+use crate::error::{ConversionError, Result};
+use crate::pdf_writer::{PdfOptions, TrackChangesMode};
 use docx_rust::{
     document::{
-        BodyContent, ParagraphContent, RunContent, Table, TableCellContent, TableRowContent,
+        BodyContent, Break, BreakType, CharType, DelText, FieldChar, ParagraphContent, Run,
+        RunContent, Sym, Table, TableCellContent, TableRowContent, Text, SDT,
+    },
+    formatting::{
+        BorderStyle as DocxBorderStyle, CharacterProperty, JustificationVal, LineRule,
+        ParagraphProperty, SectionProperty, Spacing, VertAlignType,
     },
-    DocxFile,
 };
-use log::{debug, info};
+use log::{debug, info, warn};
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     fs::File,
-    io::{BufReader, Read},
+    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    path::Path,
 };
+use zip::{result::ZipError, ZipArchive};
+
+// Tallest image kept in the text flow, in millimeters.
+const INLINE_IMAGE_MAX_HEIGHT: f32 = 10.0;
+// Text of a page number field until it is filled in on each page.
+const PAGE_FIELD_PLACEHOLDER: &str = "1";
+
+trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
 
+type DocxArchive<'a> = ZipArchive<&'a mut dyn ReadSeek>;
 
-pub fn read_docx(docx_path: &str) -> Result<Vec<DocContent>> {
+/// Reads the content of a DOCX without converting it. The options that affect
+/// reading, such as `strict`, apply.
+pub fn read_docx(docx_path: &str, options: &PdfOptions) -> Result<DocxDocument> {
     debug!("Opening DOCX file: {}", docx_path);
-    let doc = DocxFile::from_file(docx_path)
-        .map_err(|e| anyhow::anyhow!("Failed to open DOCX file: {}: {:?}", docx_path, e))?;
+    let file = File::open(docx_path).map_err(|e| ConversionError::io(docx_path, e))?;
+    let mut document = read_docx_from(BufReader::new(file), options)?;
+    if let (Some(source), Some(name)) = (&mut document.source, Path::new(docx_path).file_name()) {
+        source.name = name.to_string_lossy().into_owned();
+    }
+    Ok(document)
+}
+
+/// Same as [`read_docx`], from any seekable source.
+// The same reader serves both the document parts and the media extraction.
+pub fn read_docx_from<R: Read + Seek>(mut reader: R, options: &PdfOptions) -> Result<DocxDocument> {
+    // The file as given is attached, encrypted or not.
+    let source = if options.embed_source {
+        Some(read_source(&mut reader)?)
+    } else {
+        None
+    };
+    if let Some(package) = decrypt_package(&mut reader, options.password.as_deref())? {
+        let document = read_docx_from(Cursor::new(package), options)?;
+        return Ok(DocxDocument { source, ..document });
+    }
+    let doc = open_docx_file(&mut reader)?;
 
     debug!("Parsing DOCX file");
     let docx = doc
         .parse()
-        .map_err(|e| anyhow::anyhow!("Failed to parse DOCX file: {:?}", e))?;
+        .map_err(|e| ConversionError::DocxParse(format!("{:?}", e)))?;
+
+    debug!("Opening DOCX archive for media extraction");
+    let mut zip: DocxArchive = ZipArchive::new(&mut reader as &mut dyn ReadSeek)?;
+    let metadata = read_core_properties(&mut zip)?;
+    let BodyMarkup {
+        first_section,
+        section_breaks,
+        text_boxes,
+        anchored_images,
+        equations,
+        tables: table_markup,
+        comments,
+    } = read_body_markup(&mut zip)?;
+    let styles = StyleSheet::new(&docx.styles, read_style_bases(&mut zip)?, &options.font_map);
 
     debug!("Processing DOCX content");
     let mut content_order = Vec::new();
+    let mut context = ReadContext {
+        docx: &docx,
+        zip,
+        lists: ListTracker::new(docx.numbering.as_ref()),
+        styles: &styles,
+        text_boxes,
+        anchored_images,
+        equations,
+        section_breaks,
+        table_markup,
+        tables_read: 0,
+        comments,
+        notes: Notes::default(),
+        fields: FieldStack::default(),
+        track_changes: options.track_changes,
+        strict: options.strict,
+    };
 
     process_body_content(
         &docx.document.body.content,
-        &docx,
-        docx_path,
+        &mut context,
         &mut content_order,
     )?;
+    let notes = context.notes;
+
+    let section = section_property(&docx);
+    let header = section
+        .and_then(|section| section.header.as_ref())
+        .and_then(|reference| {
+            let target = part_target(&docx, &reference.id)?;
+            docx.headers.get(target.as_str())
+        })
+        .map(|header| header_footer_content(&header.content, &styles, true))
+        .unwrap_or_default();
+    let footer = section
+        .and_then(|section| section.footer.as_ref())
+        .and_then(|reference| {
+            let target = part_target(&docx, &reference.id)?;
+            docx.footers.get(target.as_str())
+        })
+        .map(|footer| header_footer_content(&footer.content, &styles, true))
+        .unwrap_or_default();
 
     debug!(
         "DOCX processing complete. Found {} content items",
         content_order.len()
     );
-    Ok(content_order)
+    Ok(DocxDocument {
+        content: content_order,
+        header,
+        footer,
+        footnotes: notes.footnotes,
+        endnotes: notes.endnotes,
+        metadata,
+        columns: first_section.columns,
+        page_size: first_section.page_size,
+        source,
+    })
+}
+
+// Named after the file when read from a path.
+fn read_source<R: Read + Seek>(reader: &mut R) -> Result<SourceFile> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .and_then(|_| reader.seek(SeekFrom::Start(0)))
+        .map_err(ZipError::Io)?;
+    Ok(SourceFile {
+        name: "document.docx".to_string(),
+        bytes,
+    })
+}
+
+// The section properties of the last (or only) section live at the end of
+// the body.
+fn section_property<'a>(docx: &'a docx_rust::Docx) -> Option<&'a SectionProperty<'a>> {
+    docx.document
+        .body
+        .content
+        .iter()
+        .rev()
+        .find_map(|content| match content {
+            BodyContent::SectionProperty(section) => Some(section),
+            _ => None,
+        })
+}
+
+// Header and footer parts are keyed by their file name relative to word/.
+fn part_target(docx: &docx_rust::Docx, id: &str) -> Option<String> {
+    let target = docx.document_rels.as_ref()?.get_target(id);
+    if target.is_none() {
+        debug!("Header/footer relationship not found: {}", id);
+    }
+    target.map(|target| {
+        let path = document_part_path(target);
+        path.strip_prefix("word/").unwrap_or(&path).to_string()
+    })
+}
+
+// Only the text of headers and footers is kept: their images are resolved
+// through the header's own relationships, which are not read yet. With
+// `page_fields`, PAGE and NUMPAGES fields become runs filled in on each page.
+fn header_footer_content(
+    content: &[BodyContent],
+    styles: &StyleSheet,
+    page_fields: bool,
+) -> Vec<DocContent> {
+    let mut fields = FieldStack::new(page_fields);
+    content
+        .iter()
+        .filter_map(|content| match content {
+            BodyContent::Paragraph(paragraph) => Some(paragraph),
+            _ => None,
+        })
+        .map(|paragraph| {
+            let mut runs = Vec::new();
+            for content in &paragraph.content {
+                match content {
+                    ParagraphContent::Run(run) => {
+                        push_part_run(run, paragraph, styles, &mut fields, &mut runs);
+                    }
+                    ParagraphContent::SimpleField(field) => {
+                        let Some(value) = field_value(&field.instr, page_fields) else {
+                            for run in &field.runs {
+                                push_part_run(run, paragraph, styles, &mut fields, &mut runs);
+                            }
+                            continue;
+                        };
+                        let properties = field
+                            .runs
+                            .first()
+                            .map(|run| styles.run_properties(Some(paragraph), run))
+                            .unwrap_or_default();
+                        runs.push(field_run(styles, &properties, value));
+                    }
+                    _ => {}
+                }
+            }
+            DocContent::Paragraph(Paragraph {
+                runs: runs
+                    .into_iter()
+                    .filter(|run| !run.text.is_empty())
+                    .flat_map(split_small_caps)
+                    .collect(),
+                alignment: paragraph_alignment(&styles.paragraph_properties(paragraph)),
+                ..Default::default()
+            })
+        })
+        .filter(|item| !item.runs().is_empty())
+        .collect()
+}
+
+// State shared while reading the body of a document.
+struct ReadContext<'a, 'z> {
+    docx: &'a docx_rust::Docx<'a>,
+    zip: DocxArchive<'z>,
+    lists: ListTracker<'a>,
+    styles: &'a StyleSheet<'a>,
+    // Text of the text boxes anchored in each body paragraph, by paragraph
+    // index.
+    text_boxes: HashMap<usize, Vec<Vec<String>>>,
+    // Floating pictures anchored in each body paragraph, by paragraph index.
+    anchored_images: HashMap<usize, Vec<AnchoredImage>>,
+    // Equations of each body paragraph, by paragraph index.
+    equations: HashMap<usize, Vec<Equation>>,
+    // The section after each body paragraph that ends one, by paragraph
+    // index.
+    section_breaks: HashMap<usize, Section>,
+    // Markup of each body table, in the order table_content reads them, and
+    // how many tables it has read.
+    table_markup: Vec<TableMarkup>,
+    tables_read: usize,
+    // Review comments by the index of the body paragraph or table they are
+    // anchored in.
+    comments: HashMap<usize, Vec<Comment>>,
+    notes: Notes,
+    // Complex fields open in the body, which can span paragraphs, as tables
+    // of contents do.
+    fields: FieldStack,
+    track_changes: TrackChangesMode,
+    // Broken references fail the conversion instead of being skipped.
+    strict: bool,
 }
 
 fn process_body_content(
     body_content: &Vec<BodyContent>,
-    docx: &docx_rust::Docx,
-    docx_path: &str,
+    context: &mut ReadContext,
     content_order: &mut Vec<DocContent>,
 ) -> Result<()> {
+    let mut paragraph_index = 0;
+    let mut body_index = 0;
     for content in body_content {
+        let first_item = content_order.len();
         match content {
             BodyContent::Paragraph(paragraph) => {
-                process_paragraph(paragraph, docx, docx_path, content_order)?;
+                let equations = context.equations.remove(&paragraph_index);
+                process_paragraph(paragraph, equations, context, content_order)?;
+                attach_comments(context, body_index, &mut content_order[first_item..]);
+                body_index += 1;
+                // Floating pictures and text boxes follow the paragraph they
+                // are anchored in.
+                let anchored_images = context.anchored_images.remove(&paragraph_index);
+                for anchored in anchored_images.into_iter().flatten() {
+                    let image = relationship_image(&anchored.embed, anchored.size, context)?;
+                    if let Some(image) = image {
+                        content_order.push(DocContent::Image {
+                            image: ImageContent {
+                                position: anchored.position,
+                                ..image
+                            },
+                            comments: Vec::new(),
+                        });
+                    }
+                }
+                let text_boxes = context.text_boxes.remove(&paragraph_index);
+                for paragraphs in text_boxes.into_iter().flatten() {
+                    content_order.push(text_box_content(paragraphs));
+                }
+                if let Some(section) = context.section_breaks.remove(&paragraph_index) {
+                    content_order.push(DocContent::SectionBreak(section));
+                }
+                paragraph_index += 1;
             }
             BodyContent::Table(table) => {
-                process_table(table, content_order)?;
+                process_table(table, context, content_order)?;
+                attach_comments(context, body_index, &mut content_order[first_item..]);
+                body_index += 1;
             }
+            BodyContent::Sdt(sdt) => process_content_control(sdt, context, content_order)?,
             _ => {}
         }
     }
     Ok(())
 }
 
-fn process_table(table: &Table, content_order: &mut Vec<DocContent>) -> Result<()> {
-    let mut table_content = String::from("TABLE_START\n");
-
-    for row in &table.rows {
-        table_content.push('|');
-        for cell in &row.cells {
-            match cell {
-                TableRowContent::TableCell(table_cell) => {
-                    let mut cell_content = String::new();
-                    for content in &table_cell.content {
-                        match content {
-                            TableCellContent::Paragraph(paragraph) => {
-                                let mut paragraph_text = String::new();
-                                process_paragraph_content(paragraph, &mut paragraph_text)?;
-                                cell_content.push_str(&paragraph_text);
-                            }
+// Content controls (w:sdt), such as form fields, wrap paragraphs and tables of
+// their own. What is read from document.xml apart (equations, text boxes,
+// floating pictures and comments) is keyed by the paragraphs and tables right
+// in the body, so it is not looked up for these.
+fn process_content_control(
+    sdt: &SDT,
+    context: &mut ReadContext,
+    content_order: &mut Vec<DocContent>,
+) -> Result<()> {
+    let Some(content) = &sdt.content else {
+        return Ok(());
+    };
+    for content in &content.content {
+        match content {
+            BodyContent::Paragraph(paragraph) => {
+                process_paragraph(paragraph, None, context, content_order)?;
+            }
+            BodyContent::Table(table) => process_table(table, context, content_order)?,
+            BodyContent::Sdt(sdt) => process_content_control(sdt, context, content_order)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Comments go on the first item with content read from their paragraph or
+// table, rather than on a break marker.
+fn attach_comments(context: &mut ReadContext, body_index: usize, items: &mut [DocContent]) {
+    let Some(comments) = context.comments.remove(&body_index) else {
+        return;
+    };
+    let item_comments = items.iter_mut().find_map(|item| match item {
+        DocContent::Paragraph(paragraph) if !paragraph.runs.is_empty() => {
+            Some(&mut paragraph.comments)
+        }
+        DocContent::Table { comments, .. } | DocContent::Image { comments, .. } => Some(comments),
+        _ => None,
+    });
+    match item_comments {
+        Some(item_comments) => item_comments.extend(comments),
+        None => debug!("Dropping {} comments of an empty paragraph", comments.len()),
+    }
+}
+
+// The paragraphs of a text box are kept as lines of a single run.
+fn text_box_content(paragraphs: Vec<String>) -> DocContent {
+    DocContent::TextBox(vec![TextRun {
+        text: paragraphs.join("\n"),
+        ..Default::default()
+    }])
+}
+
+fn process_table(
+    table: &Table,
+    context: &mut ReadContext,
+    content_order: &mut Vec<DocContent>,
+) -> Result<()> {
+    let table = table_content(table, context)?;
+    if table.rows.is_empty() {
+        debug!("Skipping table without rows");
+        return Ok(());
+    }
+    content_order.push(DocContent::Table {
+        table,
+        comments: Vec::new(),
+    });
+
+    Ok(())
+}
+
+fn table_content(table: &Table, context: &mut ReadContext) -> Result<TableContent> {
+    let mut markup = context
+        .table_markup
+        .get_mut(context.tables_read)
+        .map(std::mem::take)
+        .unwrap_or_default();
+    context.tables_read += 1;
+    // The markup is matched to the rows docx-rust read by position, which
+    // would shift the header rows, spans and shading of every row after one
+    // read by only one of them.
+    if markup.cells.len() != table.rows.len() {
+        warn!(
+            "Table {} has {} rows, but {} in its markup; its header rows, spans and shading are ignored",
+            context.tables_read,
+            table.rows.len(),
+            markup.cells.len()
+        );
+        markup = TableMarkup::default();
+    }
+    let mut content = TableContent {
+        borders: table_borders(table),
+        column_widths: table
+            .grids
+            .columns
+            .iter()
+            .map(|column| column.width.map_or(0.0, twips_to_mm))
+            .collect(),
+        ..Default::default()
+    };
+
+    for (row_index, row) in table.rows.iter().enumerate() {
+        let mut row_content = RowContent {
+            header: row_index < markup.header_rows,
+            ..Default::default()
+        };
+        // Cells wrapped in a content control (w:sdt) are not read by
+        // docx-rust: only their text is known, from the markup.
+        let mut cells_markup = markup
+            .cells
+            .get_mut(row_index)
+            .map(std::mem::take)
+            .unwrap_or_default()
+            .into_iter();
+        let mut table_cells = row.cells.iter().filter_map(|cell| match cell {
+            TableRowContent::TableCell(table_cell) => Some(table_cell),
+            _ => None,
+        });
+        loop {
+            let cell_markup = cells_markup.next().unwrap_or_default();
+            let mut cell_content = CellContent {
+                column_span: cell_markup.span,
+                shading: cell_markup.shading,
+                vertical_align: cell_markup.vertical_align,
+                ..Default::default()
+            };
+            if let Some(text) = cell_markup.content_control_text {
+                if !text.is_empty() {
+                    cell_content.paragraphs.push(vec![TextRun {
+                        text,
+                        ..Default::default()
+                    }]);
+                }
+                row_content.cells.push(cell_content);
+                continue;
+            }
+            let Some(table_cell) = table_cells.next() else {
+                break;
+            };
+            cell_content.borders = cell_borders(table_cell);
+            let first_paragraph = table_cell.content.iter().find_map(|content| match content {
+                TableCellContent::Paragraph(paragraph) => Some(paragraph),
+                _ => None,
+            });
+            if let Some(paragraph) = first_paragraph {
+                let properties = context.styles.paragraph_properties(paragraph);
+                cell_content.alignment = paragraph_alignment(&properties);
+            }
+            for content in &table_cell.content {
+                match content {
+                    TableCellContent::Paragraph(paragraph) => {
+                        let mut runs = Vec::new();
+                        process_paragraph_content(
+                            paragraph,
+                            context,
+                            &mut runs,
+                            &mut cell_content.images,
+                        )?;
+                        if !runs.is_empty() {
+                            let runs = runs.into_iter().flat_map(split_small_caps).collect();
+                            cell_content.paragraphs.push(runs);
+                        }
+                    }
+                    TableCellContent::Table(nested) => {
+                        debug!("Processing nested table");
+                        let nested = table_content(nested, context)?;
+                        if !nested.rows.is_empty() {
+                            cell_content.tables.push(nested);
                         }
                     }
-                    table_content.push_str(&cell_content);
-                    table_content.push('|');
                 }
-                _ => {}
             }
+            row_content.cells.push(cell_content);
         }
-        table_content.push('\n');
+        content.rows.push(row_content);
+    }
+
+    Ok(content)
+}
+
+// Tables without an explicit w:tblBorders usually get their borders from a
+// table style, so they keep the full single-line grid.
+fn table_borders(table: &Table) -> TableBorders {
+    let Some(borders) = &table.property.borders else {
+        return TableBorders::default();
+    };
+
+    let side = |border: Option<Border>| border.unwrap_or(Border::NONE);
+    TableBorders {
+        top: side(borders.top.as_ref().map(|b| border(&b.style, b.size))),
+        bottom: side(borders.bottom.as_ref().map(|b| border(&b.style, b.size))),
+        left: side(borders.left.as_ref().map(|b| border(&b.style, b.size))),
+        right: side(borders.right.as_ref().map(|b| border(&b.style, b.size))),
+        inside_horizontal: side(
+            borders
+                .inside_horizontal
+                .as_ref()
+                .map(|b| border(&b.style, b.size)),
+        ),
+        inside_vertical: side(
+            borders
+                .inside_vertical
+                .as_ref()
+                .map(|b| border(&b.style, b.size)),
+        ),
     }
+}
 
-    table_content.push_str("TABLE_END\n");
+fn cell_borders(cell: &docx_rust::document::TableCell) -> CellBorders {
+    let Some(borders) = &cell.property.borders else {
+        return CellBorders::default();
+    };
 
-    content_order.push(DocContent {
-        text: table_content,
-        image: None,
-    });
+    CellBorders {
+        top: borders.top.as_ref().map(|b| border(&b.style, b.size)),
+        bottom: borders.bottom.as_ref().map(|b| border(&b.style, b.size)),
+        left: borders.left.as_ref().map(|b| border(&b.style, b.size)),
+        right: borders.right.as_ref().map(|b| border(&b.style, b.size)),
+    }
+}
 
-    Ok(())
+// w:sz on borders is expressed in eighths of a point.
+fn border(style: &DocxBorderStyle, size: Option<usize>) -> Border {
+    match style {
+        DocxBorderStyle::None | DocxBorderStyle::Nil => Border::NONE,
+        _ => Border {
+            style: BorderStyle::Single,
+            width: size.map_or(Border::default().width, |size| size as f32 / 8.0),
+        },
+    }
 }
 
+// Reads the runs of a paragraph of a table cell. Notes referenced from a cell
+// only leave their mark in its text; the notes themselves are rendered after
+// the body.
 fn process_paragraph_content(
     paragraph: &docx_rust::document::Paragraph,
-    paragraph_text: &mut String,
+    context: &mut ReadContext,
+    paragraph_runs: &mut Vec<TextRun>,
+    images: &mut Vec<ImageContent>,
 ) -> Result<()> {
+    let styles = context.styles;
     for para_content in &paragraph.content {
-        if let ParagraphContent::Run(run) = para_content {
+        let (runs, revision) = match para_content {
+            ParagraphContent::Run(run) => (std::slice::from_ref(run), None),
+            ParagraphContent::Link(hyperlink) => (hyperlink.content.as_slice(), None),
+            _ => {
+                let Some((revision, runs)) = revision_runs(para_content) else {
+                    continue;
+                };
+                if !keeps_revision(context.track_changes, revision) {
+                    continue;
+                }
+                let shown = context.track_changes == TrackChangesMode::Show;
+                (runs, shown.then_some(revision))
+            }
+        };
+        let runs_start = paragraph_runs.len();
+        for run in runs {
+            let properties = styles.run_properties(Some(paragraph), run);
+            let symbol_font = run_symbol_font(&properties);
+            let mut run_text = String::new();
             for run_content in &run.content {
                 match run_content {
-                    RunContent::Text(text) => {
-                        paragraph_text.push_str(&text.text);
+                    RunContent::Text(Text { text, .. })
+                    | RunContent::DelText(DelText { text, .. }) => {
+                        run_text.push_str(&symbol_text(symbol_font, text));
                     }
+                    RunContent::Sym(sym) => {
+                        run_text.extend(sym_char(sym));
+                    }
+                    // Page and column breaks only end the line in a cell.
                     RunContent::Break(_) => {
-                        paragraph_text.push(' ');
+                        run_text.push('\n');
+                    }
+                    RunContent::Tab(_) => {
+                        run_text.push('\t');
+                    }
+                    RunContent::FootnoteReference(reference) => {
+                        let footnote = context.notes.footnote(context.docx, styles, reference.id);
+                        if let Some((_, mark)) = footnote {
+                            if !run_text.is_empty() {
+                                let text = std::mem::take(&mut run_text);
+                                paragraph_runs.push(styled_run(styles, &properties, text, None));
+                            }
+                            paragraph_runs.push(note_mark(styles, &properties, mark));
+                        }
+                    }
+                    RunContent::EndnoteReference(reference) => {
+                        if let Some(mark) =
+                            context.notes.endnote(context.docx, styles, reference.id)
+                        {
+                            if !run_text.is_empty() {
+                                let text = std::mem::take(&mut run_text);
+                                paragraph_runs.push(styled_run(styles, &properties, text, None));
+                            }
+                            paragraph_runs.push(note_mark(styles, &properties, mark));
+                        }
+                    }
+                    RunContent::Drawing(drawing) => {
+                        if let Some(image) = extract_image_from_drawing(drawing, context)? {
+                            images.push(image);
+                        }
                     }
                     _ => {}
                 }
             }
+            if !run_text.is_empty() {
+                paragraph_runs.push(styled_run(styles, &properties, run_text, None));
+            }
+        }
+        for run in &mut paragraph_runs[runs_start..] {
+            mark_revision(run, revision);
         }
     }
     Ok(())
 }
 
+// Equations are set as plain text among the runs, where they stand in the
+// paragraph.
 fn process_paragraph(
     paragraph: &docx_rust::document::Paragraph,
-    docx: &docx_rust::Docx,
-    docx_path: &str,
+    equations: Option<Vec<Equation>>,
+    context: &mut ReadContext,
     content_order: &mut Vec<DocContent>,
 ) -> Result<()> {
-    let mut paragraph_text = String::new();
+    let styles = context.styles;
+    let properties = styles.paragraph_properties(paragraph);
+    let list = properties
+        .iter()
+        .find_map(|property| property.numbering.as_ref())
+        .filter(|numbering| numbering.id.value != 0)
+        .map(|numbering| {
+            context
+                .lists
+                .next_item(numbering.id.value, numbering.level.value)
+        });
+
+    let mut pending = PendingParagraph {
+        paragraph,
+        heading_level: heading_level(paragraph, styles),
+        caption: is_caption(paragraph, styles),
+        properties,
+        list,
+        runs: Vec::new(),
+        revision: None,
+    };
+    let mut equations = equations.unwrap_or_default().into_iter().peekable();
+    let mut runs_read = 0;
+    let mut add_equations = |runs_read: usize, pending: &mut PendingParagraph| {
+        while let Some(equation) = equations.next_if(|equation| equation.runs_before <= runs_read) {
+            pending.runs.push(TextRun {
+                text: equation.text,
+                ..Default::default()
+            });
+        }
+    };
     for para_content in &paragraph.content {
-        if let ParagraphContent::Run(run) = para_content {
-            for run_content in &run.content {
-                match run_content {
-                    RunContent::Text(text) => {
-                        paragraph_text.push_str(&text.text);
+        match para_content {
+            ParagraphContent::Run(run) => {
+                add_equations(runs_read, &mut pending);
+                process_run(run, None, context, &mut pending, content_order)?;
+                runs_read += 1;
+            }
+            ParagraphContent::Link(hyperlink) => {
+                let link = hyperlink_target(hyperlink, context.docx);
+                for run in &hyperlink.content {
+                    add_equations(runs_read, &mut pending);
+                    process_run(run, link.as_deref(), context, &mut pending, content_order)?;
+                    runs_read += 1;
+                }
+            }
+            // Page numbers are only filled in in headers and footers, so the
+            // body keeps their cached result.
+            ParagraphContent::SimpleField(field) => match field_value(&field.instr, false) {
+                Some(value) => {
+                    let properties = field
+                        .runs
+                        .first()
+                        .map(|run| styles.run_properties(Some(paragraph), run))
+                        .unwrap_or_default();
+                    pending.runs.push(field_run(styles, &properties, value));
+                }
+                None => {
+                    for run in &field.runs {
+                        process_run(run, None, context, &mut pending, content_order)?;
                     }
-                    RunContent::Break(_) => {
-                        paragraph_text.push('\n');
+                }
+            },
+            _ => {
+                let Some((revision, runs)) = revision_runs(para_content) else {
+                    continue;
+                };
+                let kept = keeps_revision(context.track_changes, revision);
+                pending.revision =
+                    (context.track_changes == TrackChangesMode::Show).then_some(revision);
+                for run in runs {
+                    add_equations(runs_read, &mut pending);
+                    if kept {
+                        process_run(run, None, context, &mut pending, content_order)?;
                     }
-                    RunContent::Drawing(drawing) => {
-                        if let Some(image_bytes) =
-                            extract_image_from_drawing(drawing, docx, docx_path)?
-                        {
-                            content_order.push(DocContent {
-                                text: String::new(),
-                                image: Some(ImageContent { bytes: image_bytes }),
-                            });
-                        }
+                    runs_read += 1;
+                }
+                pending.revision = None;
+            }
+        }
+    }
+    add_equations(usize::MAX, &mut pending);
+    pending.flush(content_order);
+    Ok(())
+}
+
+// A tracked change (w:ins or w:del).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Revision {
+    Insertion,
+    Deletion,
+}
+
+// The runs of a tracked insertion or deletion.
+fn revision_runs<'c, 'a>(content: &'c ParagraphContent<'a>) -> Option<(Revision, &'c [Run<'a>])> {
+    match content {
+        ParagraphContent::Insertion(insertion) => Some((Revision::Insertion, &insertion.runs)),
+        ParagraphContent::Deletion(deletion) => Some((Revision::Deletion, &deletion.runs)),
+        _ => None,
+    }
+}
+
+// Insertions shown marked are underlined and deletions struck through, as
+// Word does.
+fn mark_revision(run: &mut TextRun, revision: Option<Revision>) {
+    match revision {
+        Some(Revision::Insertion) => run.underline = true,
+        Some(Revision::Deletion) => run.strike = Strike::Single,
+        None => {}
+    }
+}
+
+fn keeps_revision(mode: TrackChangesMode, revision: Revision) -> bool {
+    match mode {
+        TrackChangesMode::Accept => revision == Revision::Insertion,
+        TrackChangesMode::Reject => revision == Revision::Deletion,
+        TrackChangesMode::Show => true,
+    }
+}
+
+// Text of a paragraph that has not been emitted yet. It is flushed before
+// each drawing so that images keep their place between the surrounding text.
+struct PendingParagraph<'a> {
+    paragraph: &'a docx_rust::document::Paragraph<'a>,
+    heading_level: Option<u8>,
+    caption: bool,
+    properties: Vec<&'a ParagraphProperty<'a>>,
+    list: Option<ListItem>,
+    runs: Vec<TextRun>,
+    // Set while reading the runs of a tracked change that is shown marked.
+    revision: Option<Revision>,
+}
+
+impl PendingParagraph<'_> {
+    fn push(&mut self, mut run: TextRun) {
+        mark_revision(&mut run, self.revision);
+        self.runs.push(run);
+    }
+
+    fn flush(&mut self, content_order: &mut Vec<DocContent>) {
+        if self.runs.is_empty() {
+            return;
+        }
+        content_order.push(DocContent::Paragraph(Paragraph {
+            runs: std::mem::take(&mut self.runs)
+                .into_iter()
+                .flat_map(split_small_caps)
+                .collect(),
+            // Only the first part of a split paragraph gets the list marker.
+            list: self.list.take(),
+            heading_level: self.heading_level,
+            caption: self.caption,
+            alignment: paragraph_alignment(&self.properties),
+            indentation: paragraph_indentation(&self.properties),
+            line_spacing: paragraph_line_spacing(&self.properties),
+            space_before: paragraph_spacing(&self.properties, |spacing| spacing.before),
+            space_after: paragraph_spacing(&self.properties, |spacing| spacing.after),
+            rtl: paragraph_rtl(self.paragraph, &self.properties),
+            keep_lines: paragraph_flag(&self.properties, |property| {
+                property.keep_lines.as_ref().map(|keep| keep.value)
+            }),
+            keep_next: paragraph_flag(&self.properties, |property| {
+                property.keep_next.as_ref().map(|keep| keep.value)
+            }),
+            comments: Vec::new(),
+        }));
+    }
+}
+
+fn process_run(
+    run: &docx_rust::document::Run,
+    link: Option<&str>,
+    context: &mut ReadContext,
+    pending: &mut PendingParagraph,
+    content_order: &mut Vec<DocContent>,
+) -> Result<()> {
+    let styles = context.styles;
+    let properties = styles.run_properties(Some(pending.paragraph), run);
+    let symbol_font = run_symbol_font(&properties);
+    let mut run_text = String::new();
+    for run_content in &run.content {
+        match run_content {
+            RunContent::Text(_) | RunContent::DelText(_) if context.fields.hides_text() => {}
+            RunContent::Text(Text { text, .. }) | RunContent::DelText(DelText { text, .. }) => {
+                run_text.push_str(&symbol_text(symbol_font, text));
+            }
+            RunContent::FieldChar(field_char) => {
+                if let Some(FieldValue::Text(value)) =
+                    read_field_char(&mut context.fields, field_char)
+                {
+                    run_text.push_str(&value);
+                }
+            }
+            RunContent::InstrText(instruction) => context.fields.instruction(&instruction.text),
+            RunContent::Sym(sym) => {
+                run_text.extend(sym_char(sym));
+            }
+            RunContent::Break(brk) if is_page_or_column_break(brk) => {
+                if !run_text.is_empty() {
+                    pending.push(styled_run(
+                        styles,
+                        &properties,
+                        std::mem::take(&mut run_text),
+                        link,
+                    ));
+                }
+                pending.flush(content_order);
+                content_order.push(match brk.ty {
+                    Some(BreakType::Column) => DocContent::ColumnBreak,
+                    _ => DocContent::PageBreak,
+                });
+            }
+            RunContent::Break(_) => {
+                run_text.push('\n');
+            }
+            RunContent::Tab(_) => {
+                run_text.push('\t');
+            }
+            RunContent::FootnoteReference(reference) => {
+                let footnote = context.notes.footnote(context.docx, styles, reference.id);
+                if let Some((index, mark)) = footnote {
+                    if !run_text.is_empty() {
+                        pending.push(styled_run(
+                            styles,
+                            &properties,
+                            std::mem::take(&mut run_text),
+                            link,
+                        ));
                     }
-                    _ => {}
+                    pending.push(TextRun {
+                        footnote: Some(index),
+                        ..note_mark(styles, &properties, mark)
+                    });
                 }
             }
+            RunContent::EndnoteReference(reference) => {
+                if let Some(mark) = context.notes.endnote(context.docx, styles, reference.id) {
+                    if !run_text.is_empty() {
+                        pending.push(styled_run(
+                            styles,
+                            &properties,
+                            std::mem::take(&mut run_text),
+                            link,
+                        ));
+                    }
+                    pending.push(note_mark(styles, &properties, mark));
+                }
+            }
+            RunContent::Drawing(drawing) => {
+                if let Some(image) = extract_image_from_drawing(drawing, context)? {
+                    if !run_text.is_empty() {
+                        pending.push(styled_run(
+                            styles,
+                            &properties,
+                            std::mem::take(&mut run_text),
+                            link,
+                        ));
+                    }
+                    if is_inline_image(&image) {
+                        pending.push(TextRun {
+                            image: Some(image),
+                            ..styled_run(styles, &properties, String::new(), link)
+                        });
+                        continue;
+                    }
+                    pending.flush(content_order);
+                    content_order.push(DocContent::Image {
+                        image,
+                        comments: Vec::new(),
+                    });
+                }
+            }
+            _ => {}
         }
     }
-    if !paragraph_text.is_empty() {
-        content_order.push(DocContent {
-            text: paragraph_text,
-            image: None,
-        });
+    if !run_text.is_empty() {
+        pending.push(styled_run(styles, &properties, run_text, link));
     }
     Ok(())
 }
 
-fn extract_image_from_drawing(
-    drawing: &docx_rust::document::Drawing,
-    docx: &docx_rust::Docx,
-    docx_path: &str,
-) -> Result<Option<Vec<u8>>> {
-    if let Some(inline) = &drawing.inline {
-        if let Some(graphic) = &inline.graphic {
-            let rl_id = graphic.data.pic.fill.blip.embed.to_string();
-            if let Some(relationships) = &docx.document_rels {
-                if let Some(target) = relationships.get_target(&rl_id) {
-                    return Ok(Some(extract_image_bytes(docx_path, &target)?));
-                }
+// Footnotes and endnotes collected while reading the body, numbered in the
+// order they are first referenced.
+#[derive(Default)]
+struct Notes {
+    footnotes: Vec<Note>,
+    endnotes: Vec<Note>,
+    footnote_ids: HashMap<isize, usize>,
+    endnote_ids: HashMap<isize, usize>,
+}
+
+impl Notes {
+    // Returns the index of the footnote and its reference mark.
+    fn footnote(
+        &mut self,
+        docx: &docx_rust::Docx,
+        styles: &StyleSheet,
+        id: Option<isize>,
+    ) -> Option<(usize, String)> {
+        let id = id?;
+        let index = match self.footnote_ids.get(&id) {
+            Some(&index) => index,
+            None => {
+                let note = docx
+                    .footnotes
+                    .as_ref()?
+                    .content
+                    .iter()
+                    .find(|note| note.id == Some(id))?;
+                let mark = (self.footnotes.len() + 1).to_string();
+                self.footnotes
+                    .push(note_content(&mark, &note.content, styles));
+                self.footnote_ids.insert(id, self.footnotes.len() - 1);
+                self.footnotes.len() - 1
+            }
+        };
+        Some((index, (index + 1).to_string()))
+    }
+
+    fn endnote(
+        &mut self,
+        docx: &docx_rust::Docx,
+        styles: &StyleSheet,
+        id: Option<isize>,
+    ) -> Option<String> {
+        let id = id?;
+        let index = match self.endnote_ids.get(&id) {
+            Some(&index) => index,
+            None => {
+                let note = docx
+                    .endnotes
+                    .as_ref()?
+                    .content
+                    .iter()
+                    .find(|note| note.id == Some(id))?;
+                let mark = lower_roman(self.endnotes.len() + 1);
+                self.endnotes
+                    .push(note_content(&mark, &note.content, styles));
+                self.endnote_ids.insert(id, self.endnotes.len() - 1);
+                self.endnotes.len() - 1
             }
+        };
+        Some(lower_roman(index + 1))
+    }
+}
+
+// The text of a note, its paragraphs separated by line breaks, after its mark.
+fn note_content(mark: &str, content: &[BodyContent], styles: &StyleSheet) -> Note {
+    let mut runs = vec![TextRun {
+        text: mark.to_string(),
+        vertical_align: VerticalAlign::Superscript,
+        ..Default::default()
+    }];
+    for (index, item) in header_footer_content(content, styles, false)
+        .into_iter()
+        .enumerate()
+    {
+        if index > 0 {
+            runs.push(TextRun {
+                text: "\n".to_string(),
+                ..Default::default()
+            });
         }
+        runs.extend_from_slice(item.runs());
     }
-    Ok(None)
+    Note { runs }
 }
 
-fn extract_image_bytes(docx_path: &str, target: &str) -> Result<Vec<u8>> {
-    let file = File::open(docx_path)
-        .with_context(|| format!("Failed to open DOCX file: {}", docx_path))?;
-    let mut zip = zip::ZipArchive::new(BufReader::new(file))
-        .with_context(|| "Failed to create ZIP archive")?;
+fn note_mark(styles: &StyleSheet, properties: &[&CharacterProperty], mark: String) -> TextRun {
+    TextRun {
+        vertical_align: VerticalAlign::Superscript,
+        ..styled_run(styles, properties, mark, None)
+    }
+}
+
+fn is_page_or_column_break(brk: &Break) -> bool {
+    matches!(brk.ty, Some(BreakType::Page | BreakType::Column))
+}
+
+// Reads a run of a header, footer or note. The value of a field that replaces
+// its cached result is added to the text, or is a run of its own for page
+// numbers.
+fn push_part_run(
+    run: &docx_rust::document::Run,
+    paragraph: &docx_rust::document::Paragraph,
+    styles: &StyleSheet,
+    fields: &mut FieldStack,
+    runs: &mut Vec<TextRun>,
+) {
+    let properties = styles.run_properties(Some(paragraph), run);
+    let symbol_font = run_symbol_font(&properties);
+    let mut text = String::new();
+    for run_content in &run.content {
+        match run_content {
+            RunContent::Text(_) if fields.hides_text() => {}
+            RunContent::Text(run_text) => text.push_str(&symbol_text(symbol_font, &run_text.text)),
+            RunContent::Sym(sym) => text.extend(sym_char(sym)),
+            // Headers, footers and notes are not split by page or column breaks.
+            RunContent::Break(_) => text.push('\n'),
+            RunContent::Tab(_) => text.push('\t'),
+            RunContent::FieldChar(field_char) => match read_field_char(fields, field_char) {
+                Some(FieldValue::Text(value)) => text.push_str(&value),
+                Some(value) => {
+                    let text = std::mem::take(&mut text);
+                    runs.push(styled_run(styles, &properties, text, None));
+                    runs.push(field_run(styles, &properties, value));
+                }
+                None => {}
+            },
+            RunContent::InstrText(instruction) => fields.instruction(&instruction.text),
+            _ => {}
+        }
+    }
+    runs.push(styled_run(styles, &properties, text, None));
+}
+
+// Returns the value shown by a field that a begin, separate or end character
+// opens or ends the result of, when it replaces the cached result.
+fn read_field_char(fields: &mut FieldStack, field_char: &FieldChar) -> Option<FieldValue> {
+    match field_char.ty {
+        Some(CharType::Begin) => {
+            fields.begin();
+            None
+        }
+        Some(CharType::Separate) => fields.separate(),
+        Some(CharType::End) => fields.end(),
+        None => None,
+    }
+}
+
+// Page numbers are set apart with a placeholder as their text, for the header
+// or footer to be laid out before they are known.
+fn field_run(styles: &StyleSheet, properties: &[&CharacterProperty], value: FieldValue) -> TextRun {
+    match value {
+        FieldValue::Text(text) => styled_run(styles, properties, text, None),
+        FieldValue::Page(field) => TextRun {
+            field: Some(field),
+            ..styled_run(styles, properties, PAGE_FIELD_PLACEHOLDER.to_string(), None)
+        },
+    }
+}
+
+// All caps text is uppercased right away, while small caps runs are only
+// flagged: split_small_caps sets them apart once the paragraph is read.
+fn styled_run(
+    styles: &StyleSheet,
+    properties: &[&CharacterProperty],
+    text: String,
+    link: Option<&str>,
+) -> TextRun {
+    let caps = run_caps(properties);
+    TextRun {
+        text: if caps { text.to_uppercase() } else { text },
+        link: link.map(str::to_string),
+        font_size: run_font_size(properties),
+        font_family: run_font_family(properties, styles.fonts),
+        bold: run_bold(properties),
+        color: run_color(properties),
+        highlight: run_highlight(properties),
+        vertical_align: run_vertical_align(properties),
+        strike: run_strike(properties),
+        underline: false,
+        small_caps: !caps && run_small_caps(properties),
+        footnote: None,
+        image: None,
+        field: None,
+    }
+}
+
+// w:sz is expressed in half-points.
+fn run_font_size(properties: &[&CharacterProperty]) -> Option<f32> {
+    let size = properties
+        .iter()
+        .find_map(|property| property.size.as_ref())?;
+    Some(size.value as f32 / 2.0)
+}
+
+// Only the built-in PDF fonts are available, so common fonts are mapped to
+// the closest of them by name. The caller's mappings come first.
+fn run_font_family(properties: &[&CharacterProperty], font_map: &FontMap) -> Option<FontFamily> {
+    let fonts = properties
+        .iter()
+        .find_map(|property| property.fonts.as_ref())?;
+    let name = fonts.ascii.as_ref().or(fonts.h_ansi.as_ref())?;
+    if let Some(family) = font_map.family(name) {
+        return Some(family);
+    }
+    match name.trim().to_ascii_lowercase().as_str() {
+        "arial" | "calibri" | "calibri light" | "helvetica" | "segoe ui" | "verdana" | "tahoma"
+        | "aptos" | "liberation sans" => Some(FontFamily::Helvetica),
+        "times new roman" | "times" | "georgia" | "cambria" | "garamond" | "book antiqua"
+        | "liberation serif" => Some(FontFamily::Times),
+        "consolas" | "courier new" | "courier" | "lucida console" | "cascadia code"
+        | "cascadia mono" | "liberation mono" => Some(FontFamily::Courier),
+        _ => None,
+    }
+}
+
+// Symbol fonts place their glyphs on letter codes, or on the same codes moved
+// to the private use area (U+F000 and up). The built-in fonts have neither,
+// so the checkbox glyphs are mapped to their Unicode characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SymbolFont {
+    Wingdings,
+    Wingdings2,
+    Symbol,
+}
+
+fn symbol_font(name: &str) -> Option<SymbolFont> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "wingdings" => Some(SymbolFont::Wingdings),
+        "wingdings 2" => Some(SymbolFont::Wingdings2),
+        "symbol" => Some(SymbolFont::Symbol),
+        _ => None,
+    }
+}
+
+fn run_symbol_font(properties: &[&CharacterProperty]) -> Option<SymbolFont> {
+    let fonts = properties
+        .iter()
+        .find_map(|property| property.fonts.as_ref())?;
+    symbol_font(fonts.ascii.as_ref().or(fonts.h_ansi.as_ref())?)
+}
+
+fn symbol_char_to_unicode(font: SymbolFont, ch: char) -> Option<char> {
+    let code = match ch as u32 {
+        code @ 0xF000..=0xF0FF => code - 0xF000,
+        code @ 0..=0xFF => code,
+        _ => return None,
+    };
+    match (font, code) {
+        (SymbolFont::Wingdings, 0x6F | 0x71 | 0xA8) => Some('☐'),
+        (SymbolFont::Wingdings, 0xFE) => Some('☑'),
+        (SymbolFont::Wingdings, 0x78 | 0xFD) => Some('☒'),
+        (SymbolFont::Wingdings2, 0x2A | 0xA3) => Some('☐'),
+        (SymbolFont::Wingdings2, 0x52) => Some('☑'),
+        (SymbolFont::Wingdings2, 0x53 | 0x54) => Some('☒'),
+        _ => None,
+    }
+}
+
+// Other characters of a symbol font are kept as they are.
+fn symbol_text(font: Option<SymbolFont>, text: &str) -> Cow<'_, str> {
+    match font {
+        Some(font) => text
+            .chars()
+            .map(|ch| symbol_char_to_unicode(font, ch).unwrap_or(ch))
+            .collect::<String>()
+            .into(),
+        None => text.into(),
+    }
+}
+
+// A w:sym names its font and character code (in hexadecimal) itself. Only
+// the checkboxes are kept: the other symbols have no glyph to draw.
+fn sym_char(sym: &Sym) -> Option<char> {
+    let font = symbol_font(sym.font.as_ref()?)?;
+    let code = u32::from_str_radix(sym.char.as_ref()?.trim(), 16).ok()?;
+    symbol_char_to_unicode(font, char::from_u32(code)?)
+}
+
+// A bare <w:b/> is on; w:val="false" turns off the bold of a style.
+fn run_bold(properties: &[&CharacterProperty]) -> bool {
+    properties
+        .iter()
+        .find_map(|property| property.bold.as_ref())
+        .is_some_and(|bold| bold.value.unwrap_or(true))
+}
 
-    let image_path = if target.starts_with("word/") {
-        target.to_string()
+fn run_color(properties: &[&CharacterProperty]) -> Option<RgbColor> {
+    let color = properties
+        .iter()
+        .find_map(|property| property.color.as_ref())?;
+    RgbColor::from_hex(&color.value)
+}
+
+fn run_highlight(properties: &[&CharacterProperty]) -> Option<RgbColor> {
+    let highlight = properties
+        .iter()
+        .find_map(|property| property.highlight.as_ref())?;
+    RgbColor::from_highlight(&highlight.value)
+}
+
+fn run_vertical_align(properties: &[&CharacterProperty]) -> VerticalAlign {
+    let vertical_align = properties
+        .iter()
+        .find_map(|property| property.vertical_align.as_ref());
+    match vertical_align.map(|vertical_align| &vertical_align.value) {
+        Some(VertAlignType::Superscript) => VerticalAlign::Superscript,
+        Some(VertAlignType::Subscript) => VerticalAlign::Subscript,
+        _ => VerticalAlign::Baseline,
+    }
+}
+
+// Like bold, a bare <w:caps/> or <w:smallCaps/> is on.
+fn run_caps(properties: &[&CharacterProperty]) -> bool {
+    properties
+        .iter()
+        .find_map(|property| property.caps.as_ref())
+        .is_some_and(|caps| caps.value.unwrap_or(true))
+}
+
+fn run_small_caps(properties: &[&CharacterProperty]) -> bool {
+    properties
+        .iter()
+        .find_map(|property| property.small_caps.as_ref())
+        .is_some_and(|small_caps| small_caps.value.unwrap_or(true))
+}
+
+// Splits a small caps run into its lowercase letters, uppercased and flagged
+// to be drawn smaller, and the rest of its text, drawn as it is.
+fn split_small_caps(run: TextRun) -> Vec<TextRun> {
+    if !run.small_caps || run.image.is_some() || run.text.is_empty() {
+        return vec![TextRun {
+            small_caps: false,
+            ..run
+        }];
+    }
+    let mut parts: Vec<TextRun> = Vec::new();
+    for ch in run.text.chars() {
+        let lowercase = ch.is_lowercase();
+        match parts.last_mut() {
+            Some(part) if part.small_caps == lowercase => part.text.extend(ch.to_uppercase()),
+            _ => parts.push(TextRun {
+                text: ch.to_uppercase().collect(),
+                small_caps: lowercase,
+                ..run.clone()
+            }),
+        }
+    }
+    parts
+}
+
+// A bare <w:strike/> is on; w:val="false" turns it off.
+fn run_strike(properties: &[&CharacterProperty]) -> Strike {
+    if properties
+        .iter()
+        .find_map(|property| property.dstrike.as_ref())
+        .is_some_and(|dstrike| dstrike.value.unwrap_or(true))
+    {
+        Strike::Double
+    } else if properties
+        .iter()
+        .find_map(|property| property.strike.as_ref())
+        .is_some_and(|strike| strike.value.unwrap_or(true))
+    {
+        Strike::Single
     } else {
-        format!("word/{}", target)
+        Strike::None
+    }
+}
+
+fn hyperlink_target(
+    hyperlink: &docx_rust::document::Hyperlink,
+    docx: &docx_rust::Docx,
+) -> Option<String> {
+    let id = hyperlink.id.as_ref()?;
+    let target = docx.document_rels.as_ref()?.get_target(id);
+    if target.is_none() {
+        debug!("Hyperlink relationship not found: {}", id);
+    }
+    target.map(str::to_string)
+}
+
+// A paragraph is a heading when its style, or a style it is based on, is one
+// of the built-in heading styles.
+fn heading_level(paragraph: &docx_rust::document::Paragraph, styles: &StyleSheet) -> Option<u8> {
+    let style_id = paragraph_style_id(paragraph)?;
+    std::iter::once(style_id)
+        .chain(
+            styles
+                .chain(Some(style_id))
+                .into_iter()
+                .map(|style| style.style_id.as_ref()),
+        )
+        .find_map(heading_style_level)
+}
+
+fn heading_style_level(style_id: &str) -> Option<u8> {
+    let level = style_id
+        .to_ascii_lowercase()
+        .strip_prefix("heading")?
+        .trim()
+        .parse::<u8>()
+        .ok()?;
+    (1..=6).contains(&level).then_some(level)
+}
+
+// The built-in Caption style keeps the name "caption" when Word localizes its
+// id, such as "Legenda".
+fn is_caption(paragraph: &docx_rust::document::Paragraph, styles: &StyleSheet) -> bool {
+    let Some(style_id) = paragraph_style_id(paragraph) else {
+        return false;
+    };
+    style_id.eq_ignore_ascii_case("caption")
+        || styles.chain(Some(style_id)).into_iter().any(|style| {
+            style.style_id.eq_ignore_ascii_case("caption")
+                || style
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| name.value.eq_ignore_ascii_case("caption"))
+        })
+}
+
+// w:bidi marks a right-to-left paragraph. Without it, a paragraph whose first
+// letter is Hebrew or Arabic is taken as right-to-left as well.
+fn paragraph_rtl(
+    paragraph: &docx_rust::document::Paragraph,
+    properties: &[&ParagraphProperty],
+) -> bool {
+    let bidi = properties
+        .iter()
+        .find_map(|property| property.bidi.as_ref());
+    if let Some(bidi) = bidi {
+        return bidi.value.unwrap_or(true);
+    }
+
+    paragraph
+        .content
+        .iter()
+        .filter_map(|content| match content {
+            ParagraphContent::Run(run) => Some(run),
+            _ => None,
+        })
+        .flat_map(|run| &run.content)
+        .filter_map(|content| match content {
+            RunContent::Text(text) => Some(text.text.as_ref()),
+            _ => None,
+        })
+        .flat_map(str::chars)
+        .find(|ch| ch.is_alphabetic())
+        .is_some_and(is_rtl_char)
+}
+
+// Like bold, a bare flag such as <w:keepNext/> is on.
+fn paragraph_flag(
+    properties: &[&ParagraphProperty],
+    flag: impl Fn(&ParagraphProperty) -> Option<Option<bool>>,
+) -> bool {
+    properties
+        .iter()
+        .find_map(|property| flag(property))
+        .is_some_and(|value| value.unwrap_or(true))
+}
+
+fn paragraph_alignment(properties: &[&ParagraphProperty]) -> Alignment {
+    let Some(justification) = properties
+        .iter()
+        .find_map(|property| property.justification.as_ref())
+    else {
+        return Alignment::Left;
     };
 
+    match justification.value {
+        JustificationVal::Center => Alignment::Center,
+        JustificationVal::Right | JustificationVal::End => Alignment::Right,
+        JustificationVal::Both | JustificationVal::Distribute => Alignment::Justify,
+        _ => Alignment::Left,
+    }
+}
+
+fn paragraph_indentation(properties: &[&ParagraphProperty]) -> Indentation {
+    let Some(indent) = properties
+        .iter()
+        .find_map(|property| property.indent.as_ref())
+    else {
+        return Indentation::default();
+    };
+
+    let first_line = match (indent.hanging, indent.first_line) {
+        (Some(hanging), _) => -twips_to_mm(hanging),
+        (None, Some(first_line)) => twips_to_mm(first_line),
+        (None, None) => 0.0,
+    };
+    Indentation {
+        left: indent.left.map_or(0.0, twips_to_mm),
+        right: indent.right.map_or(0.0, twips_to_mm),
+        first_line,
+    }
+}
+
+// w:line is in 240ths of a line for the auto rule, and in twips for the
+// exact and at least rules.
+fn paragraph_line_spacing(properties: &[&ParagraphProperty]) -> LineSpacing {
+    let spacings = || {
+        properties
+            .iter()
+            .filter_map(|property| property.spacing.as_ref())
+    };
+    let Some(line) = spacings()
+        .find_map(|spacing| spacing.line)
+        .filter(|&line| line > 0)
+    else {
+        return LineSpacing::default();
+    };
+    match spacings().find_map(|spacing| spacing.line_rule.as_ref()) {
+        Some(LineRule::Exact) => LineSpacing::Exact(twips_to_mm(line)),
+        Some(LineRule::AtLeast) => LineSpacing::AtLeast(twips_to_mm(line)),
+        Some(LineRule::Auto) | None => LineSpacing::Multiple(line as f32 / 240.0),
+    }
+}
+
+// Space before or after the paragraph, from the nearest properties that set it.
+fn paragraph_spacing(
+    properties: &[&ParagraphProperty],
+    value: impl Fn(&Spacing) -> Option<isize>,
+) -> Option<f32> {
+    properties
+        .iter()
+        .filter_map(|property| property.spacing.as_ref())
+        .find_map(value)
+        .map(|twips| twips_to_mm(twips.max(0)))
+}
+
+// A twip is a twentieth of a point.
+fn twips_to_mm(twips: isize) -> f32 {
+    twips as f32 / 1440.0 * 25.4
+}
+
+fn extract_image_from_drawing(
+    drawing: &docx_rust::document::Drawing,
+    context: &mut ReadContext,
+) -> Result<Option<ImageContent>> {
+    let Some(inline) = &drawing.inline else {
+        return Ok(None);
+    };
+    let Some(graphic) = &inline.graphic else {
+        return Ok(None);
+    };
+
+    let size = inline.extent.as_ref().and_then(|extent| {
+        let size = ImageSize {
+            width: emus_to_mm(extent.cx as f32),
+            height: emus_to_mm(extent.cy as f32),
+        };
+        (size.width > 0.0 && size.height > 0.0).then_some(size)
+    });
+    relationship_image(&graphic.data.pic.fill.blip.embed, size, context)
+}
+
+// The picture a relationship of the document points to.
+fn relationship_image(
+    rl_id: &str,
+    size: Option<ImageSize>,
+    context: &mut ReadContext,
+) -> Result<Option<ImageContent>> {
+    let target = context
+        .docx
+        .document_rels
+        .as_ref()
+        .and_then(|relationships| relationships.get_target(rl_id));
+    let Some(target) = target else {
+        if context.strict {
+            return Err(ConversionError::MissingRelationship {
+                id: rl_id.to_string(),
+            });
+        }
+        warn!("Image relationship not found, skipping image: {}", rl_id);
+        return Ok(None);
+    };
+
+    Ok(Some(ImageContent {
+        bytes: extract_image_bytes(&mut context.zip, target)?,
+        size,
+        position: None,
+    }))
+}
+
+// Images no taller than a couple of text lines, such as inline equations or
+// signatures, stay within the text instead of getting a line of their own.
+fn is_inline_image(image: &ImageContent) -> bool {
+    image
+        .size
+        .is_some_and(|size| size.height <= INLINE_IMAGE_MAX_HEIGHT)
+}
+
+fn extract_image_bytes(zip: &mut DocxArchive<'_>, target: &str) -> Result<Vec<u8>> {
+    let image_path = document_part_path(target);
+
     info!("Trying to open image file: {}", image_path);
 
-    let mut image_file = zip
-        .by_name(&image_path)
-        .with_context(|| format!("Image not found in path: {}", image_path))?;
+    let mut image_file = match zip.by_name(&image_path) {
+        Ok(image_file) => image_file,
+        Err(ZipError::FileNotFound) => {
+            return Err(ConversionError::MissingPart { path: image_path });
+        }
+        Err(e) => return Err(e.into()),
+    };
 
     let mut buffer = Vec::new();
-    Read::read_to_end(&mut image_file, &mut buffer).with_context(|| "Failed to read image file")?;
+    Read::read_to_end(&mut image_file, &mut buffer)
+        .map_err(|e| ConversionError::io(&image_path, e))?;
 
     info!("Image file read successfully. Size: {} bytes", buffer.len());
     Ok(buffer)
-}
\ No newline at end of file
+}
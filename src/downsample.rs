@@ -0,0 +1,82 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, RgbImage, RgbaImage};
+use log::debug;
+use printpdf::{ColorBits, ColorSpace, ImageXObject, Px, SMask};
+
+// Shrinks an image wider or taller than `max_dimension` pixels to fit in that
+// size, keeping its aspect ratio. Only 8-bit RGB and grayscale images are
+// resampled, which covers what the decoders produce for photos.
+pub fn downsample(image: ImageXObject, max_dimension: u32) -> ImageXObject {
+    let (width, height) = (image.width.0 as u32, image.height.0 as u32);
+    if width.max(height) <= max_dimension {
+        return image;
+    }
+    let Some(decoded) = to_dynamic_image(&image) else {
+        return image;
+    };
+    debug!(
+        "Downsampling {}x{} image to at most {} pixels",
+        width, height, max_dimension
+    );
+    let resized = decoded.resize(max_dimension, max_dimension, FilterType::Triangle);
+    to_image_xobject(resized)
+}
+
+fn to_dynamic_image(image: &ImageXObject) -> Option<DynamicImage> {
+    if !matches!(image.bits_per_component, ColorBits::Bit8) || image.image_filter.is_some() {
+        return None;
+    }
+    let (width, height) = (image.width.0 as u32, image.height.0 as u32);
+    let data = image.image_data.clone();
+    match (image.color_space, &image.smask) {
+        (ColorSpace::Rgb, None) => RgbImage::from_raw(width, height, data).map(DynamicImage::from),
+        // printpdf keeps the alpha channel in a soft mask, one value per pixel.
+        (ColorSpace::Rgb, Some(mask)) => {
+            let pixels = data
+                .chunks_exact(3)
+                .zip(&mask.matte)
+                .flat_map(|(rgb, &alpha)| [rgb[0], rgb[1], rgb[2], alpha as u8])
+                .collect();
+            RgbaImage::from_raw(width, height, pixels).map(DynamicImage::from)
+        }
+        (ColorSpace::Greyscale, None) => {
+            GrayImage::from_raw(width, height, data).map(DynamicImage::from)
+        }
+        _ => None,
+    }
+}
+
+fn to_image_xobject(image: DynamicImage) -> ImageXObject {
+    let (width, height) = (image.width(), image.height());
+    let (color_space, image_data, smask) = match image {
+        DynamicImage::ImageLuma8(gray) => (ColorSpace::Greyscale, gray.into_raw(), None),
+        DynamicImage::ImageRgba8(rgba) => {
+            let mut rgb = Vec::with_capacity(rgba.len() / 4 * 3);
+            let mut alpha = Vec::with_capacity(rgba.len() / 4);
+            for pixel in rgba.pixels() {
+                rgb.extend_from_slice(&pixel.0[..3]);
+                alpha.push(i64::from(pixel.0[3]));
+            }
+            let mask = SMask {
+                width: i64::from(width),
+                height: i64::from(height),
+                interpolate: false,
+                bits_per_component: 8,
+                matte: alpha,
+            };
+            (ColorSpace::Rgb, rgb, Some(mask))
+        }
+        image => (ColorSpace::Rgb, image.into_rgb8().into_raw(), None),
+    };
+    ImageXObject {
+        width: Px(width as usize),
+        height: Px(height as usize),
+        color_space,
+        bits_per_component: ColorBits::Bit8,
+        interpolate: true,
+        image_data,
+        image_filter: None,
+        smask,
+        clipping_bbox: None,
+    }
+}
@@ -0,0 +1,373 @@
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, KeyInit};
+use aes::{Aes128, Aes192, Aes256};
+use cfb::CompoundFile;
+use log::debug;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::io::{Read, Seek, SeekFrom};
+use zip::result::ZipError;
+
+use crate::error::{ConversionError, Result};
+use crate::package::{attribute_value, is_ole_file, next_tag, tag_name};
+
+const AES_BLOCK_SIZE: usize = 16;
+// The package is encrypted in segments, each with its own initialization
+// vector.
+const SEGMENT_SIZE: usize = 4096;
+// Office writes a spin count of 100000. Much larger ones, one hash each, only
+// serve to keep a crafted file decrypting for hours.
+const MAX_SPIN_COUNT: u32 = 10_000_000;
+
+// Block keys mixed into the password hash to derive the key of each value of
+// the key encryptor (ECMA-376 agile encryption).
+const VERIFIER_INPUT_BLOCK: [u8; 8] = [0xfe, 0xa7, 0xd2, 0x76, 0x3b, 0x4b, 0x9e, 0x79];
+const VERIFIER_HASH_BLOCK: [u8; 8] = [0xd7, 0xaa, 0x0f, 0x6d, 0x30, 0x61, 0x34, 0x4e];
+const KEY_VALUE_BLOCK: [u8; 8] = [0x14, 0x6e, 0x0b, 0xe7, 0xab, 0xac, 0xd0, 0xd6];
+
+// Password protected DOCX files are OLE compound files holding the encrypted
+// ZIP package, instead of the package itself. Returns the decrypted package,
+// or None when the file is not encrypted.
+pub fn decrypt_package<R: Read + Seek>(
+    reader: &mut R,
+    password: Option<&str>,
+) -> Result<Option<Vec<u8>>> {
+    if !is_ole_file(reader)? {
+        return Ok(None);
+    }
+    let streams = {
+        let mut file = CompoundFile::open(&mut *reader).map_err(compound_file_error)?;
+        if file.exists("EncryptionInfo") && file.exists("EncryptedPackage") {
+            Some((
+                read_stream(&mut file, "EncryptionInfo")?,
+                read_stream(&mut file, "EncryptedPackage")?,
+            ))
+        } else {
+            None
+        }
+    };
+    let Some((info, package)) = streams else {
+        // A legacy .doc file, reported as such when the package is opened.
+        reader.seek(SeekFrom::Start(0)).map_err(ZipError::Io)?;
+        return Ok(None);
+    };
+    let Some(password) = password else {
+        return Err(ConversionError::PasswordRequired);
+    };
+
+    debug!("Decrypting password protected package");
+    let encryption = AgileEncryption::parse(&info)?;
+    let key = encryption.package_key(password)?;
+    encryption.decrypt(&key, &package).map(Some)
+}
+
+// The parameters of the EncryptionInfo stream needed to decrypt the package.
+struct AgileEncryption {
+    // Salt, key size and block size of the package itself (keyData).
+    package_hash: HashAlgorithm,
+    package_salt: Vec<u8>,
+    package_key_bytes: usize,
+    package_block_size: usize,
+    // Password key encryptor (p:encryptedKey).
+    hash: HashAlgorithm,
+    key_bytes: usize,
+    key_block_size: usize,
+    salt: Vec<u8>,
+    spin_count: u32,
+    verifier_hash_input: Vec<u8>,
+    verifier_hash_value: Vec<u8>,
+    key_value: Vec<u8>,
+}
+
+impl AgileEncryption {
+    // The stream starts with its version, 4.4 for agile encryption, and
+    // reserved flags, followed by an XML descriptor.
+    fn parse(info: &[u8]) -> Result<Self> {
+        let version = info.get(..4).map(|version| (version[0], version[2]));
+        if version != Some((4, 4)) {
+            return Err(ConversionError::UnsupportedEncryption(
+                "only agile encryption (Office 2010 and later) is supported".to_string(),
+            ));
+        }
+        let xml = String::from_utf8_lossy(info.get(8..).unwrap_or_default());
+
+        let mut key_data = None;
+        let mut encrypted_key = None;
+        let mut position = 0;
+        while let Some((tag, end)) = next_tag(&xml, position) {
+            position = end;
+            match tag_name(tag).rsplit(':').next() {
+                Some("keyData") => key_data = Some(tag),
+                Some("encryptedKey") => encrypted_key = Some(tag),
+                _ => {}
+            }
+        }
+        let (Some(key_data), Some(encrypted_key)) = (key_data, encrypted_key) else {
+            return Err(ConversionError::UnsupportedEncryption(
+                "no password key encryptor".to_string(),
+            ));
+        };
+        let package_hash = encryptor_hash(key_data)?;
+        let hash = encryptor_hash(encrypted_key)?;
+        let spin_count = number_attribute(encrypted_key, "spinCount")?;
+        if spin_count > MAX_SPIN_COUNT {
+            return Err(ConversionError::UnsupportedEncryption(format!(
+                "spin count of {}",
+                spin_count
+            )));
+        }
+
+        Ok(AgileEncryption {
+            package_hash,
+            package_salt: base64_attribute(key_data, "saltValue")?,
+            package_key_bytes: number_attribute::<usize>(key_data, "keyBits")? / 8,
+            package_block_size: number_attribute(key_data, "blockSize")?,
+            hash,
+            key_bytes: number_attribute::<usize>(encrypted_key, "keyBits")? / 8,
+            key_block_size: number_attribute(encrypted_key, "blockSize")?,
+            salt: base64_attribute(encrypted_key, "saltValue")?,
+            spin_count,
+            verifier_hash_input: base64_attribute(encrypted_key, "encryptedVerifierHashInput")?,
+            verifier_hash_value: base64_attribute(encrypted_key, "encryptedVerifierHashValue")?,
+            key_value: base64_attribute(encrypted_key, "encryptedKeyValue")?,
+        })
+    }
+
+    // Derives the keys of the key encryptor from the password, checks the
+    // password against the verifier and decrypts the key of the package.
+    fn package_key(&self, password: &str) -> Result<Vec<u8>> {
+        let password: Vec<u8> = password.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let mut hash = self.hash.digest(&[&self.salt, &password]);
+        for iteration in 0..self.spin_count {
+            hash = self.hash.digest(&[&iteration.to_le_bytes(), &hash]);
+        }
+        let iv = sized(&self.salt, self.key_block_size);
+        let decrypt_value = |block_key: &[u8], value: &[u8]| {
+            let key = self.hash.digest(&[&hash, block_key]);
+            aes_cbc_decrypt(&sized(&key, self.key_bytes), &iv, value)
+        };
+
+        let verifier_input = decrypt_value(&VERIFIER_INPUT_BLOCK, &self.verifier_hash_input)?;
+        let verifier_hash = decrypt_value(&VERIFIER_HASH_BLOCK, &self.verifier_hash_value)?;
+        let expected = verifier_input
+            .get(..self.salt.len())
+            .map(|input| self.hash.digest(&[input]));
+        if !expected.is_some_and(|expected| verifier_hash.starts_with(&expected)) {
+            return Err(ConversionError::WrongPassword);
+        }
+
+        let key = decrypt_value(&KEY_VALUE_BLOCK, &self.key_value)?;
+        Ok(key[..self.package_key_bytes.min(key.len())].to_vec())
+    }
+
+    // The EncryptedPackage stream holds the size of the package, then the
+    // package itself, padded to whole cipher blocks.
+    fn decrypt(&self, key: &[u8], stream: &[u8]) -> Result<Vec<u8>> {
+        let Some((size, encrypted)) = stream.split_first_chunk::<8>() else {
+            return Err(ConversionError::DocxParse(
+                "Encrypted package is truncated".to_string(),
+            ));
+        };
+        let size = u64::from_le_bytes(*size) as usize;
+        let mut package = Vec::with_capacity(encrypted.len());
+        for (index, segment) in encrypted.chunks(SEGMENT_SIZE).enumerate() {
+            let iv = self
+                .package_hash
+                .digest(&[&self.package_salt, &(index as u32).to_le_bytes()]);
+            let iv = sized(&iv, self.package_block_size);
+            package.extend(aes_cbc_decrypt(key, &iv, segment)?);
+        }
+        package.truncate(size);
+        Ok(package)
+    }
+}
+
+// Only AES in CBC mode is supported, with any of the hash algorithms Office
+// writes.
+fn encryptor_hash(tag: &str) -> Result<HashAlgorithm> {
+    let cipher = attribute_value(tag, "cipherAlgorithm").unwrap_or_default();
+    let chaining = attribute_value(tag, "cipherChaining").unwrap_or_default();
+    let hash = attribute_value(tag, "hashAlgorithm").unwrap_or_default();
+    match HashAlgorithm::from_name(hash) {
+        Some(algorithm) if (cipher, chaining) == ("AES", "ChainingModeCBC") => Ok(algorithm),
+        _ => Err(ConversionError::UnsupportedEncryption(format!(
+            "{} {} with {}",
+            cipher, chaining, hash
+        ))),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "SHA1" | "SHA-1" => Some(HashAlgorithm::Sha1),
+            "SHA256" | "SHA-256" => Some(HashAlgorithm::Sha256),
+            "SHA384" | "SHA-384" => Some(HashAlgorithm::Sha384),
+            "SHA512" | "SHA-512" => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    fn digest(self, parts: &[&[u8]]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha1 => digest::<Sha1>(parts),
+            HashAlgorithm::Sha256 => digest::<Sha256>(parts),
+            HashAlgorithm::Sha384 => digest::<Sha384>(parts),
+            HashAlgorithm::Sha512 => digest::<Sha512>(parts),
+        }
+    }
+}
+
+fn digest<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = D::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().to_vec()
+}
+
+fn read_stream<R: Read + Seek>(file: &mut CompoundFile<R>, name: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    file.open_stream(name)
+        .and_then(|mut stream| stream.read_to_end(&mut bytes))
+        .map_err(compound_file_error)?;
+    Ok(bytes)
+}
+
+fn compound_file_error(error: std::io::Error) -> ConversionError {
+    ConversionError::DocxParse(format!("Failed to read encrypted package: {}", error))
+}
+
+fn base64_attribute(tag: &str, name: &str) -> Result<Vec<u8>> {
+    use base64::Engine;
+
+    attribute_value(tag, name)
+        .and_then(|value| base64::engine::general_purpose::STANDARD.decode(value).ok())
+        .ok_or_else(|| invalid_attribute(name))
+}
+
+fn number_attribute<T: std::str::FromStr>(tag: &str, name: &str) -> Result<T> {
+    attribute_value(tag, name)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| invalid_attribute(name))
+}
+
+fn invalid_attribute(name: &str) -> ConversionError {
+    ConversionError::DocxParse(format!("Invalid {} in the encryption info", name))
+}
+
+// Hashes and salts are cut or padded with 0x36 bytes to the size of a key or
+// block.
+fn sized(bytes: &[u8], size: usize) -> Vec<u8> {
+    let mut sized = bytes[..size.min(bytes.len())].to_vec();
+    sized.resize(size, 0x36);
+    sized
+}
+
+fn aes_cbc_decrypt(key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let decrypted = match key.len() {
+        16 => Aes128::new_from_slice(key).map(|cipher| cbc_decrypt(&cipher, iv, data)),
+        24 => Aes192::new_from_slice(key).map(|cipher| cbc_decrypt(&cipher, iv, data)),
+        _ => Aes256::new_from_slice(key).map(|cipher| cbc_decrypt(&cipher, iv, data)),
+    };
+    let bits = key.len() * 8;
+    decrypted.map_err(|_| ConversionError::UnsupportedEncryption(format!("{}-bit key", bits)))
+}
+
+// Bytes past the last whole block are dropped.
+fn cbc_decrypt(cipher: &impl BlockDecrypt, iv: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut previous = sized(iv, AES_BLOCK_SIZE);
+    let mut decrypted = Vec::with_capacity(data.len());
+    for block in data.chunks_exact(AES_BLOCK_SIZE) {
+        let mut plain = GenericArray::clone_from_slice(block);
+        cipher.decrypt_block(&mut plain);
+        decrypted.extend(plain.iter().zip(&previous).map(|(byte, mask)| byte ^ mask));
+        previous = block.to_vec();
+    }
+    decrypted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Written following MS-OFFCRYPTO with another implementation, for the
+    // password "secret" and a package key of 0x64, 0x65, ...
+    const SHA1_KEY_DATA: &str = r#"<keyData saltSize="16" blockSize="16" keyBits="128" hashSize="20" cipherAlgorithm="AES" cipherChaining="ChainingModeCBC" hashAlgorithm="SHA1" saltValue="EBESExQVFhcYGRobHB0eHw=="/>"#;
+    const SHA1_ENCRYPTED_KEY: &str = r#"<p:encryptedKey spinCount="1000" saltSize="16" blockSize="16" keyBits="128" hashSize="20" cipherAlgorithm="AES" cipherChaining="ChainingModeCBC" hashAlgorithm="SHA1" saltValue="AAECAwQFBgcICQoLDA0ODw==" encryptedVerifierHashInput="RD8+XomQPB8UVf3L8sJNTw==" encryptedVerifierHashValue="tr5uquyo35tV4xbwdtaC+PvNGjw9fscVCK0rDz3tyUw=" encryptedKeyValue="zQVNFseVSEkOGcMb49x2WA=="/>"#;
+    const SHA1_PACKAGE: &str =
+        "1500000000000000dda07c3621c6a778ac8b0a2a95baf9740d9f660c4732f4aed3e6fd1bcd54a5f8";
+    const SHA512_KEY_DATA: &str = r#"<keyData saltSize="16" blockSize="16" keyBits="256" hashSize="64" cipherAlgorithm="AES" cipherChaining="ChainingModeCBC" hashAlgorithm="SHA512" saltValue="EBESExQVFhcYGRobHB0eHw=="/>"#;
+    const SHA512_ENCRYPTED_KEY: &str = r#"<p:encryptedKey spinCount="1000" saltSize="16" blockSize="16" keyBits="256" hashSize="64" cipherAlgorithm="AES" cipherChaining="ChainingModeCBC" hashAlgorithm="SHA512" saltValue="AAECAwQFBgcICQoLDA0ODw==" encryptedVerifierHashInput="BxZsvH3VCirwN5JT1Womow==" encryptedVerifierHashValue="0+K+22q4VLZbRotfRS66ABmcyzoYnuZMCxEIhbr2u5ksdkzDu7lRmJ5S/hY2FCJxffeKAWtRmrWy6BPIEkWO7Q==" encryptedKeyValue="fJXOc5yhiLvdanzIttELClapNf/LIByWVEsmr+oOFUQ="/>"#;
+    const SHA512_PACKAGE: &str =
+        "1500000000000000bed3a95ed94ce9ee42b5a414d4650aa5eb8fe4f4ff1b26e18e0b513c812bf00f";
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn encryption_info(key_data: &str, encrypted_key: &str) -> Vec<u8> {
+        let mut info = vec![4, 0, 4, 0, 0x40, 0, 0, 0];
+        info.extend(format!("<encryption>{}{}</encryption>", key_data, encrypted_key).bytes());
+        info
+    }
+
+    fn decrypt(key_data: &str, encrypted_key: &str, package: &str) -> Result<Vec<u8>> {
+        let encryption = AgileEncryption::parse(&encryption_info(key_data, encrypted_key))?;
+        let key = encryption.package_key("secret")?;
+        assert_eq!(key, (0x64..0x64 + key.len() as u8).collect::<Vec<_>>());
+        encryption.decrypt(&key, &hex(package))
+    }
+
+    #[test]
+    fn aes_cbc_matches_the_nist_vectors() {
+        // SP 800-38A F.2.2 and F.2.6, first block.
+        let iv = hex("000102030405060708090a0b0c0d0e0f");
+        let plain = hex("6bc1bee22e409f96e93d7e117393172a");
+        let key = hex("2b7e151628aed2a6abf7158809cf4f3c");
+        let encrypted = hex("7649abac8119b246cee98e9b12e9197d");
+        assert_eq!(aes_cbc_decrypt(&key, &iv, &encrypted).unwrap(), plain);
+        let key = hex("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4");
+        let encrypted = hex("f58c4c04d6e5f1ba779eabfb5f7bfbd6");
+        assert_eq!(aes_cbc_decrypt(&key, &iv, &encrypted).unwrap(), plain);
+    }
+
+    #[test]
+    fn packages_are_decrypted_with_sha1_and_sha512() {
+        let sha1 = decrypt(SHA1_KEY_DATA, SHA1_ENCRYPTED_KEY, SHA1_PACKAGE).unwrap();
+        assert_eq!(sha1, b"PK\x03\x04 agile encryption");
+        let sha512 = decrypt(SHA512_KEY_DATA, SHA512_ENCRYPTED_KEY, SHA512_PACKAGE).unwrap();
+        assert_eq!(sha512, b"PK\x03\x04 agile encryption");
+    }
+
+    #[test]
+    fn a_wrong_password_fails_the_verifier() {
+        let info = encryption_info(SHA1_KEY_DATA, SHA1_ENCRYPTED_KEY);
+        let encryption = AgileEncryption::parse(&info).unwrap();
+        assert!(matches!(
+            encryption.package_key("Secret"),
+            Err(ConversionError::WrongPassword)
+        ));
+    }
+
+    #[test]
+    fn huge_spin_counts_are_rejected() {
+        let encrypted_key =
+            SHA1_ENCRYPTED_KEY.replace("spinCount=\"1000\"", "spinCount=\"4000000000\"");
+        assert!(matches!(
+            AgileEncryption::parse(&encryption_info(SHA1_KEY_DATA, &encrypted_key)),
+            Err(ConversionError::UnsupportedEncryption(_))
+        ));
+    }
+}
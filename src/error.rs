@@ -0,0 +1,118 @@
+use printpdf::image_crate::ImageError;
+use std::{fmt, io};
+use zip::result::ZipError;
+
+pub type Result<T> = std::result::Result<T, ConversionError>;
+
+#[derive(Debug)]
+pub enum ConversionError {
+    Io { path: String, source: io::Error },
+    DocxParse(String),
+    Archive(ZipError),
+    MissingPart { path: String },
+    MissingRelationship { id: String },
+    UnsupportedImage { format: String },
+    ImageDecode { format: String, source: ImageError },
+    Pdf(printpdf::Error),
+    PdfRewrite(printpdf::lopdf::Error),
+    InvalidOption(String),
+    // Typography, header or footer leaving no room on the page for the body.
+    InvalidPageConfig(String),
+    // A legacy Word .doc file, which must be saved as .docx first.
+    LegacyDocUnsupported,
+    // The document goes past one of the conversion limits, such as its
+    // number of pages.
+    LimitExceeded { limit: &'static str, max: usize },
+    // A password protected document, given no password or the wrong one.
+    PasswordRequired,
+    WrongPassword,
+    UnsupportedEncryption(String),
+}
+
+impl ConversionError {
+    pub(crate) fn io(path: &str, source: io::Error) -> Self {
+        ConversionError::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConversionError::Io { path, source } => write!(f, "{}: {}", path, source),
+            ConversionError::DocxParse(message) => {
+                write!(f, "Failed to parse DOCX file: {}", message)
+            }
+            ConversionError::Archive(source) => write!(f, "Failed to read ZIP archive: {}", source),
+            ConversionError::MissingPart { path } => write!(f, "Part not found in path: {}", path),
+            ConversionError::MissingRelationship { id } => {
+                write!(f, "Relationship not found: {}", id)
+            }
+            ConversionError::UnsupportedImage { format } => {
+                write!(f, "Formato de imagem não suportado: {}", format)
+            }
+            ConversionError::ImageDecode { format, source } => write!(
+                f,
+                "Falha ao converter a imagem {} para o formato PDF: {}",
+                format, source
+            ),
+            ConversionError::Pdf(source) => write!(f, "Failed to write PDF: {}", source),
+            ConversionError::PdfRewrite(source) => write!(f, "Failed to rewrite PDF: {}", source),
+            ConversionError::InvalidOption(message) => write!(f, "{}", message),
+            ConversionError::InvalidPageConfig(message) => {
+                write!(f, "Invalid page layout: {}", message)
+            }
+            ConversionError::LegacyDocUnsupported => write!(
+                f,
+                "Legacy Word .doc files are not supported; open the file in Word or \
+                 LibreOffice, save it as .docx and convert that"
+            ),
+            ConversionError::LimitExceeded { limit, max } => {
+                write!(f, "Document exceeds the limit of {} {}", max, limit)
+            }
+            ConversionError::PasswordRequired => {
+                write!(
+                    f,
+                    "The document is password protected; a password is needed"
+                )
+            }
+            ConversionError::WrongPassword => write!(f, "Wrong password for the document"),
+            ConversionError::UnsupportedEncryption(message) => {
+                write!(f, "Unsupported document encryption: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConversionError::Io { source, .. } => Some(source),
+            ConversionError::Archive(source) => Some(source),
+            ConversionError::ImageDecode { source, .. } => Some(source),
+            ConversionError::Pdf(source) => Some(source),
+            ConversionError::PdfRewrite(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<ZipError> for ConversionError {
+    fn from(error: ZipError) -> Self {
+        ConversionError::Archive(error)
+    }
+}
+
+impl From<printpdf::Error> for ConversionError {
+    fn from(error: printpdf::Error) -> Self {
+        ConversionError::Pdf(error)
+    }
+}
+
+impl From<printpdf::lopdf::Error> for ConversionError {
+    fn from(error: printpdf::lopdf::Error) -> Self {
+        ConversionError::PdfRewrite(error)
+    }
+}
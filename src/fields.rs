@@ -0,0 +1,298 @@
+use crate::utils::PageField;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Word's short date and time in US English, the language of the month and
+// day names, for DATE and TIME fields with no \@ switch.
+const DEFAULT_DATE_FORMAT: &str = "M/d/yyyy";
+const DEFAULT_TIME_FORMAT: &str = "h:mm AM/PM";
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+// What a field shows instead of the result cached in the document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Text(String),
+    // Filled in on each page the field is drawn on.
+    Page(PageField),
+}
+
+// Complex fields (w:fldChar) open at the current run, the innermost last. A
+// field is made of runs holding its instruction (w:instrText), then after a
+// separator the runs of its cached result, up to its end.
+#[derive(Debug, Default)]
+pub struct FieldStack {
+    fields: Vec<OpenField>,
+    // Whether PAGE and NUMPAGES fields are filled in, as in headers and
+    // footers, instead of keeping their cached result.
+    page_fields: bool,
+}
+
+#[derive(Debug)]
+struct OpenField {
+    instruction: String,
+    separated: bool,
+    value: Option<FieldValue>,
+}
+
+impl FieldStack {
+    pub fn new(page_fields: bool) -> Self {
+        FieldStack {
+            fields: Vec::new(),
+            page_fields,
+        }
+    }
+
+    pub fn begin(&mut self) {
+        self.fields.push(OpenField {
+            instruction: String::new(),
+            separated: false,
+            value: None,
+        });
+    }
+
+    pub fn instruction(&mut self, text: &str) {
+        if let Some(field) = self.fields.last_mut().filter(|field| !field.separated) {
+            field.instruction.push_str(text);
+        }
+    }
+
+    // Returns what the field shows in place of the cached result that
+    // follows, if anything.
+    pub fn separate(&mut self) -> Option<FieldValue> {
+        let page_fields = self.page_fields;
+        let field = self.fields.last_mut()?;
+        field.separated = true;
+        field.value = field_value(&field.instruction, page_fields);
+        field.value.clone()
+    }
+
+    // A field with no separator has no cached result: returns what it shows,
+    // if anything.
+    pub fn end(&mut self) -> Option<FieldValue> {
+        let field = self.fields.pop()?;
+        if field.separated {
+            None
+        } else {
+            field_value(&field.instruction, self.page_fields)
+        }
+    }
+
+    // Whether the text being read is part of an instruction, or of a cached
+    // result that is replaced.
+    pub fn hides_text(&self) -> bool {
+        self.fields
+            .iter()
+            .any(|field| !field.separated || field.value.is_some())
+    }
+}
+
+// DATE and TIME fields show the current date and time (UTC), and PAGE and
+// NUMPAGES the page number and count when `page_fields` is set. Other fields,
+// such as TOC or REF, keep their cached result.
+pub fn field_value(instruction: &str, page_fields: bool) -> Option<FieldValue> {
+    let name = instruction.split_whitespace().next()?.to_uppercase();
+    match name.as_str() {
+        "PAGE" if page_fields => Some(FieldValue::Page(PageField::Number)),
+        "NUMPAGES" if page_fields => Some(FieldValue::Page(PageField::Count)),
+        "DATE" | "TIME" => {
+            let default = if name == "DATE" {
+                DEFAULT_DATE_FORMAT
+            } else {
+                DEFAULT_TIME_FORMAT
+            };
+            let picture = date_picture(instruction).unwrap_or(default);
+            let seconds = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs() as i64);
+            Some(FieldValue::Text(format_date(seconds, picture)))
+        }
+        _ => None,
+    }
+}
+
+// The date-time picture of a field, given with its \@ switch, such as
+// `DATE \@ "d MMMM yyyy"`.
+fn date_picture(instruction: &str) -> Option<&str> {
+    let switch = instruction.split_once("\\@")?.1.trim_start();
+    match switch.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next(),
+        None => switch.split_whitespace().next(),
+    }
+}
+
+// Formats a time, in seconds since the Unix epoch, with a Word date-time
+// picture: d, dd, ddd and dddd for the day, M to MMMM for the month, yy and
+// yyyy for the year, H, h, m, s and AM/PM (or am/pm) for the time. Text in
+// single quotes and other characters are kept as they are.
+fn format_date(seconds: i64, picture: &str) -> String {
+    let days = seconds.div_euclid(86400);
+    let time = seconds.rem_euclid(86400);
+    let (year, month, day) = civil_date(days);
+    let weekday = WEEKDAYS[(days + 4).rem_euclid(7) as usize];
+    let month_name = MONTHS[month as usize - 1];
+    let (hour, minute, second) = (time / 3600, time / 60 % 60, time % 60);
+
+    let mut text = String::new();
+    let mut chars = picture.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\'' {
+            text.extend(chars.by_ref().take_while(|&ch| ch != '\''));
+            continue;
+        }
+        if matches!(ch, 'A' | 'a') {
+            let rest: String = chars.clone().take(4).collect();
+            if rest.eq_ignore_ascii_case("M/PM") {
+                chars.nth(3);
+                let marker = if hour < 12 { "AM" } else { "PM" };
+                if ch == 'a' {
+                    text.push_str(&marker.to_lowercase());
+                } else {
+                    text.push_str(marker);
+                }
+                continue;
+            }
+        }
+        let mut count = 1;
+        while chars.next_if_eq(&ch).is_some() {
+            count += 1;
+        }
+        let two_digits = |value: i64| format!("{:02}", value);
+        let part = match (ch, count) {
+            ('d', 1) => day.to_string(),
+            ('d', 2) => two_digits(day),
+            ('d', 3) => weekday[..3].to_string(),
+            ('d', _) => weekday.to_string(),
+            ('M', 1) => month.to_string(),
+            ('M', 2) => two_digits(month),
+            ('M', 3) => month_name[..3].to_string(),
+            ('M', _) => month_name.to_string(),
+            ('y', 1 | 2) => two_digits(year % 100),
+            ('y', _) => year.to_string(),
+            ('H', 1) => hour.to_string(),
+            ('H', _) => two_digits(hour),
+            ('h', 1) => ((hour + 11) % 12 + 1).to_string(),
+            ('h', _) => two_digits((hour + 11) % 12 + 1),
+            ('m', 1) => minute.to_string(),
+            ('m', _) => two_digits(minute),
+            ('s', 1) => second.to_string(),
+            ('s', _) => two_digits(second),
+            _ => ch.to_string().repeat(count),
+        };
+        text.push_str(&part);
+    }
+    text
+}
+
+// Year, month and day of a number of days since 1970-01-01, in the proleptic
+// Gregorian calendar.
+fn civil_date(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, so that leap days come last.
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-02-29 13:05:09 UTC, a Thursday.
+    const LEAP_DAY: i64 = 1_709_211_909;
+    // 1969-07-20 20:17:00 UTC, a Sunday.
+    const MOON_LANDING: i64 = -14_182_980;
+
+    #[test]
+    fn civil_date_handles_leap_days_and_dates_before_1970() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(LEAP_DAY.div_euclid(86400)), (2024, 2, 29));
+        assert_eq!(civil_date(11_017), (2000, 3, 1));
+        assert_eq!(civil_date(MOON_LANDING.div_euclid(86400)), (1969, 7, 20));
+    }
+
+    #[test]
+    fn format_date_follows_the_picture() {
+        assert_eq!(format_date(LEAP_DAY, "d MMMM yyyy"), "29 February 2024");
+        assert_eq!(format_date(LEAP_DAY, "dddd, MMM d"), "Thursday, Feb 29");
+        assert_eq!(format_date(LEAP_DAY, DEFAULT_DATE_FORMAT), "2/29/2024");
+        assert_eq!(format_date(LEAP_DAY, DEFAULT_TIME_FORMAT), "1:05 PM");
+        assert_eq!(format_date(LEAP_DAY, "HH:mm:ss 'UTC'"), "13:05:09 UTC");
+        assert_eq!(
+            format_date(MOON_LANDING, "ddd dd/MM/yy h:mm am/pm"),
+            "Sun 20/07/69 8:17 pm"
+        );
+    }
+
+    #[test]
+    fn date_picture_reads_quoted_and_bare_switches() {
+        assert_eq!(
+            date_picture(r#"DATE \@ "d MMMM yyyy""#),
+            Some("d MMMM yyyy")
+        );
+        assert_eq!(
+            date_picture(r#"TIME \@ HH:mm \* MERGEFORMAT"#),
+            Some("HH:mm")
+        );
+        assert_eq!(date_picture("DATE"), None);
+    }
+
+    #[test]
+    fn field_stack_hides_instructions_and_replaced_results() {
+        let mut fields = FieldStack::new(true);
+        fields.begin();
+        fields.instruction(" PAGE ");
+        assert!(fields.hides_text());
+        assert_eq!(fields.separate(), Some(FieldValue::Page(PageField::Number)));
+        assert!(fields.hides_text());
+        assert_eq!(fields.end(), None);
+        assert!(!fields.hides_text());
+    }
+
+    #[test]
+    fn field_stack_keeps_cached_results_of_other_fields() {
+        let mut fields = FieldStack::new(false);
+        fields.begin();
+        fields.instruction("TOC \\o \"1-3\"");
+        // A nested field in the cached result, with no separator.
+        assert_eq!(fields.separate(), None);
+        assert!(!fields.hides_text());
+        fields.begin();
+        fields.instruction("NUMPAGES");
+        assert!(fields.hides_text());
+        assert_eq!(fields.end(), None);
+        assert!(!fields.hides_text());
+        assert_eq!(fields.end(), None);
+    }
+}
@@ -0,0 +1,22 @@
+use hyphenation::{Hyphenator, Language, Load, Standard};
+use log::warn;
+use std::sync::OnceLock;
+
+// The US English patterns embedded in the binary, loaded on first use.
+fn dictionary() -> Option<&'static Standard> {
+    static DICTIONARY: OnceLock<Option<Standard>> = OnceLock::new();
+    DICTIONARY
+        .get_or_init(|| match Standard::from_embedded(Language::EnglishUS) {
+            Ok(dictionary) => Some(dictionary),
+            Err(e) => {
+                warn!("Failed to load hyphenation patterns: {}", e);
+                None
+            }
+        })
+        .as_ref()
+}
+
+// Byte offsets in `word` where it may be broken with a hyphen.
+pub fn hyphenation_points(word: &str) -> Vec<usize> {
+    dictionary().map_or_else(Vec::new, |dictionary| dictionary.hyphenate(word).breaks)
+}
@@ -0,0 +1,161 @@
+use crate::pdf_writer::image_format_name;
+use crate::utils::{DocContent, DocxDocument, ImageContent, TableContent, TextRun};
+
+use image::ImageReader;
+use std::fmt::Write;
+use std::io::Cursor;
+
+// Describes the content read from a DOCX as JSON, in document order.
+pub fn document_json(document: &DocxDocument) -> String {
+    let mut json = String::from("{\n");
+    write_items(&mut json, "content", &document.content);
+    json.push_str(",\n");
+    write_items(&mut json, "header", &document.header);
+    json.push_str(",\n");
+    write_items(&mut json, "footer", &document.footer);
+    json.push_str("\n}\n");
+    json
+}
+
+fn write_items(json: &mut String, name: &str, items: &[DocContent]) {
+    let _ = write!(json, "  \"{}\": [", name);
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        json.push_str("\n    ");
+        json.push_str(&item_json(item));
+    }
+    if !items.is_empty() {
+        json.push_str("\n  ");
+    }
+    json.push(']');
+}
+
+fn item_json(item: &DocContent) -> String {
+    let text = |runs: &[TextRun]| runs.iter().map(|run| run.text.as_str()).collect::<String>();
+    let paragraph = match item {
+        DocContent::PageBreak => return "{\"type\": \"page_break\"}".to_string(),
+        DocContent::ColumnBreak => return "{\"type\": \"column_break\"}".to_string(),
+        DocContent::SectionBreak(section) => {
+            return format!(
+                "{{\"type\": \"section_break\", \"width_mm\": {:.1}, \"height_mm\": {:.1}, \"columns\": {}}}",
+                section.page_size.width, section.page_size.height, section.columns.count
+            );
+        }
+        DocContent::Table { table, .. } => return table_json(table),
+        DocContent::Image { image, .. } => return image_json(image),
+        DocContent::TextBox(runs) => {
+            return format!(
+                "{{\"type\": \"text_box\", \"text\": {}}}",
+                string_json(&text(runs))
+            );
+        }
+        DocContent::Paragraph(paragraph) => paragraph,
+    };
+
+    let text = text(&paragraph.runs);
+    let mut json = format!(
+        "{{\"type\": \"paragraph\", \"runs\": {}, \"text\": {}, \"alignment\": \"{:?}\"",
+        paragraph.runs.len(),
+        string_json(&text),
+        paragraph.alignment
+    );
+    if let Some(level) = paragraph.heading_level {
+        let _ = write!(json, ", \"heading_level\": {}", level);
+    }
+    let inline_images: Vec<String> = paragraph
+        .runs
+        .iter()
+        .filter_map(|run| run.image.as_ref())
+        .map(image_json)
+        .collect();
+    if !inline_images.is_empty() {
+        let _ = write!(json, ", \"images\": [{}]", inline_images.join(", "));
+    }
+    if !paragraph.comments.is_empty() {
+        let _ = write!(json, ", \"comments\": {}", paragraph.comments.len());
+    }
+    if let Some(list) = &paragraph.list {
+        let _ = write!(
+            json,
+            ", \"list\": {{\"level\": {}, \"marker\": {}}}",
+            list.level,
+            string_json(&list.marker)
+        );
+    }
+    json.push('}');
+    json
+}
+
+fn table_json(table: &TableContent) -> String {
+    let images = table
+        .rows
+        .iter()
+        .flat_map(|row| &row.cells)
+        .flat_map(|cell| &cell.images)
+        .map(image_json)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let tables = table
+        .rows
+        .iter()
+        .flat_map(|row| &row.cells)
+        .flat_map(|cell| &cell.tables)
+        .map(table_json)
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "{{\"type\": \"table\", \"rows\": {}, \"columns\": {}, \"images\": [{}], \"tables\": [{}]}}",
+        table.rows.len(),
+        table.column_count(),
+        images,
+        tables
+    )
+}
+
+fn image_json(image: &ImageContent) -> String {
+    let mut json = format!(
+        "{{\"type\": \"image\", \"format\": \"{}\", \"bytes\": {}",
+        image_format_name(&image.bytes),
+        image.bytes.len()
+    );
+    let dimensions = ImageReader::new(Cursor::new(&image.bytes))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok());
+    if let Some((width, height)) = dimensions {
+        let _ = write!(json, ", \"width_px\": {}, \"height_px\": {}", width, height);
+    }
+    if let Some(size) = image.size {
+        let _ = write!(
+            json,
+            ", \"width_mm\": {:.1}, \"height_mm\": {:.1}",
+            size.width, size.height
+        );
+    }
+    if let Some((x, y)) = image.position {
+        let _ = write!(json, ", \"x_mm\": {:.1}, \"y_mm\": {:.1}", x, y);
+    }
+    json.push('}');
+    json
+}
+
+fn string_json(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            ch if ch.is_control() => {
+                let _ = write!(json, "\\u{:04x}", ch as u32);
+            }
+            ch => json.push(ch),
+        }
+    }
+    json.push('"');
+    json
+}
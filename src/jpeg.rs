@@ -0,0 +1,58 @@
+use image::codecs::jpeg::JpegDecoder;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder};
+use log::debug;
+use printpdf::{ColorBits, ColorSpace, Image, ImageXObject, Px};
+use std::io::Cursor;
+
+// printpdf's JPEG path ignores the EXIF orientation of phone pictures and
+// inverts the colors of Adobe CMYK JPEGs. Such images are decoded to RGB here
+// with the orientation applied. Returns None for JPEGs that need no
+// correction, or that cannot be decoded, which are left to printpdf.
+pub fn corrected_jpeg(bytes: &[u8]) -> Option<Image> {
+    let mut decoder = JpegDecoder::new(Cursor::new(bytes)).ok()?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let is_cmyk = frame_components(bytes) == Some(4);
+    if orientation == Orientation::NoTransforms && !is_cmyk {
+        return None;
+    }
+
+    debug!(
+        "Correcting JPEG image (orientation {:?}, CMYK: {})",
+        orientation, is_cmyk
+    );
+    let mut decoded = DynamicImage::from_decoder(decoder).ok()?;
+    decoded.apply_orientation(orientation);
+    let rgb = decoded.into_rgb8();
+
+    Some(Image::from(ImageXObject {
+        width: Px(rgb.width() as usize),
+        height: Px(rgb.height() as usize),
+        color_space: ColorSpace::Rgb,
+        bits_per_component: ColorBits::Bit8,
+        interpolate: true,
+        image_data: rgb.into_raw(),
+        image_filter: None,
+        smask: None,
+        clipping_bbox: None,
+    }))
+}
+
+// Number of color components in the frame header: 1 for grayscale, 3 for
+// YCbCr and 4 for CMYK or YCCK.
+fn frame_components(bytes: &[u8]) -> Option<u8> {
+    let mut position = 2;
+    while position + 4 <= bytes.len() {
+        if bytes[position] != 0xFF {
+            return None;
+        }
+        let marker = bytes[position + 1];
+        // SOF0 to SOF15, except DHT, JPG and DAC which share the range.
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            return bytes.get(position + 9).copied();
+        }
+        let length = u16::from_be_bytes([bytes[position + 2], bytes[position + 3]]);
+        position += 2 + length as usize;
+    }
+    None
+}
@@ -0,0 +1,438 @@
+#[cfg(feature = "hyphenation")]
+use crate::hyphenation::hyphenation_points;
+use crate::utils::{estimate_text_width, is_cjk_char, TextRun, VerticalAlign};
+
+// Word's default tab stops are every half inch.
+const DEFAULT_TAB_STOP: f32 = 12.7;
+// Superscript and subscript text is drawn smaller than the surrounding text.
+const SCRIPT_FONT_SCALE: f32 = 0.65;
+// Small capitals stand about as tall as lowercase letters.
+const SMALL_CAPS_FONT_SCALE: f32 = 0.8;
+// CJK punctuation that a line never starts with.
+const CLOSING_PUNCTUATION: &str = "、。，．：；？！）」』】〕〉》〙〗］｝ー々〜・";
+// Added to the first part of a hyphenated word.
+const HYPHEN: &str = "-";
+
+#[derive(Debug)]
+pub struct Piece<'a> {
+    pub text: &'a str,
+    pub run: &'a TextRun,
+    pub width: f32,
+    pub font_size: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct Word<'a> {
+    pub pieces: Vec<Piece<'a>>,
+    pub width: f32,
+    pub font_size: f32,
+    // Number of tab characters between this word and the previous one.
+    pub tabs_before: usize,
+    // Number of spaces before this word, only counted when spacing is preserved.
+    pub spaces_before: usize,
+    // Set when the word follows the previous one with no space between them,
+    // as CJK characters do, each of which can start a new line.
+    pub joined: bool,
+    // Offset from the start of the line, assigned when the line is wrapped.
+    pub x: f32,
+}
+
+impl<'a> Word<'a> {
+    fn push(&mut self, text: &'a str, run: &'a TextRun, paragraph_font_size: f32) {
+        let mut font_size = run.font_size.unwrap_or(paragraph_font_size);
+        if run.vertical_align != VerticalAlign::Baseline {
+            font_size *= SCRIPT_FONT_SCALE;
+        }
+        if run.small_caps {
+            font_size *= SMALL_CAPS_FONT_SCALE;
+        }
+        self.push_piece(text, run, estimate_text_width(text, font_size), font_size);
+    }
+
+    fn push_piece(&mut self, text: &'a str, run: &'a TextRun, width: f32, font_size: f32) {
+        if text.is_empty() && run.image.is_none() {
+            return;
+        }
+        self.pieces.push(Piece {
+            text,
+            run,
+            width,
+            font_size,
+        });
+        self.width += width;
+        self.font_size = self.font_size.max(font_size);
+    }
+
+    // Whether the word sits a single space after the previous one, so both can
+    // be drawn as one piece of text.
+    pub fn follows_single_space(&self) -> bool {
+        self.tabs_before == 0 && self.spaces_before <= 1
+    }
+}
+
+#[derive(Debug, Default)]
+struct Separator {
+    tabs: usize,
+    spaces: usize,
+    joined: bool,
+}
+
+#[derive(Debug)]
+pub struct TextLine<'a> {
+    pub words: Vec<Word<'a>>,
+    pub words_width: f32,
+    pub width: f32,
+    pub is_last: bool,
+    // Largest font size on the line, used to make room for bigger runs.
+    pub font_size: f32,
+    // Set for lines of right-to-left paragraphs, which are drawn mirrored.
+    pub rtl: bool,
+}
+
+impl<'a> TextLine<'a> {
+    fn new(words: Vec<Word<'a>>, is_last: bool) -> Self {
+        TextLine {
+            words_width: words.iter().map(|word| word.width).sum(),
+            width: words.last().map_or(0.0, |word| word.x + word.width),
+            font_size: words.iter().map(|word| word.font_size).fold(0.0, f32::max),
+            words,
+            is_last,
+            rtl: false,
+        }
+    }
+
+    pub fn has_tabs(&self) -> bool {
+        self.words.iter().any(|word| word.tabs_before > 0)
+    }
+}
+
+// Splits the runs of a paragraph into words, one list per hard line break.
+// A word can span several runs when there is no whitespace between them.
+// Runs of spaces collapse into one unless `preserve_spaces` is set.
+// Chinese, Japanese and Korean text has no spaces, so each CJK character is a
+// word of its own, joined to the words around it.
+pub fn split_words(runs: &[TextRun], font_size: f32, preserve_spaces: bool) -> Vec<Vec<Word<'_>>> {
+    let mut segments = vec![Vec::new()];
+    let mut current_word = Word::default();
+    let mut separator = Separator::default();
+    // Whether the last character was a CJK one, which a line can break after.
+    let mut after_cjk = false;
+
+    for run in runs {
+        // An inline image is a piece of its own, joined to the text around
+        // it like the runs of a word.
+        if let Some(size) = run.image.as_ref().and_then(|image| image.size) {
+            current_word.push_piece("", run, size.width, font_size);
+            continue;
+        }
+
+        let mut piece_start = None;
+        for (index, ch) in run.text.char_indices() {
+            if !ch.is_whitespace() {
+                let cjk = is_cjk_char(ch);
+                if (cjk || after_cjk) && !is_closing_punctuation(ch) {
+                    if let Some(start) = piece_start.take() {
+                        current_word.push(&run.text[start..index], run, font_size);
+                    }
+                    if !current_word.pieces.is_empty() {
+                        finish_word(&mut segments, &mut current_word, &mut separator);
+                        separator.joined = true;
+                    }
+                }
+                piece_start.get_or_insert(index);
+                after_cjk = cjk;
+                continue;
+            }
+            after_cjk = false;
+
+            if let Some(start) = piece_start.take() {
+                current_word.push(&run.text[start..index], run, font_size);
+            }
+            finish_word(&mut segments, &mut current_word, &mut separator);
+
+            match ch {
+                '\n' => {
+                    segments.push(Vec::new());
+                    separator = Separator::default();
+                }
+                '\t' => {
+                    separator.tabs += 1;
+                    separator.spaces = 0;
+                }
+                _ if preserve_spaces => separator.spaces += 1,
+                _ => {}
+            }
+        }
+
+        if let Some(start) = piece_start {
+            current_word.push(&run.text[start..], run, font_size);
+        }
+    }
+
+    finish_word(&mut segments, &mut current_word, &mut separator);
+    segments
+}
+
+fn finish_word<'a>(
+    segments: &mut [Vec<Word<'a>>],
+    current_word: &mut Word<'a>,
+    separator: &mut Separator,
+) {
+    if current_word.pieces.is_empty() {
+        return;
+    }
+    let Separator {
+        tabs,
+        spaces,
+        joined,
+    } = std::mem::take(separator);
+    current_word.tabs_before = tabs;
+    current_word.spaces_before = spaces;
+    current_word.joined = joined;
+    if let Some(segment) = segments.last_mut() {
+        segment.push(std::mem::take(current_word));
+    }
+}
+
+// Punctuation that closes a phrase, such as "。" or "）", stays on the line of
+// the character before it.
+fn is_closing_punctuation(ch: char) -> bool {
+    CLOSING_PUNCTUATION.contains(ch) || (ch.is_ascii_punctuation() && !"([{\"'".contains(ch))
+}
+
+fn next_tab_stop(x: f32) -> f32 {
+    ((x / DEFAULT_TAB_STOP).floor() + 1.0) * DEFAULT_TAB_STOP
+}
+
+// Where a word starts when it follows a line that currently ends at `line_end`.
+fn word_start(word: &Word, line_end: f32, is_first: bool, space_width: f32) -> f32 {
+    let spaces = word.spaces_before as f32 * space_width;
+    if word.tabs_before > 0 {
+        (0..word.tabs_before).fold(line_end, |x, _| next_tab_stop(x)) + spaces
+    } else if is_first || word.joined {
+        line_end + spaces
+    } else {
+        line_end + spaces.max(space_width)
+    }
+}
+
+// `first_line_indent` shifts the first line relative to the others, and is
+// negative for hanging indents. With `hyphenate`, a word that overflows the
+// line is broken with a hyphen when it can be.
+pub fn wrap_words(
+    words: Vec<Word<'_>>,
+    font_size: f32,
+    max_width: f32,
+    first_line_indent: f32,
+    hyphenate: bool,
+) -> Vec<TextLine<'_>> {
+    let space_width = estimate_text_width(" ", font_size);
+    let mut lines = Vec::new();
+    let mut current_line: Vec<Word> = Vec::new();
+    let mut current_width = first_line_indent;
+
+    let mut words = words
+        .into_iter()
+        .flat_map(|word| break_long_word(word, max_width));
+    let mut rest_of_word = None;
+    while let Some(mut word) = rest_of_word.take().or_else(|| words.next()) {
+        let mut x = word_start(&word, current_width, current_line.is_empty(), space_width);
+        if x + word.width > max_width && !current_line.is_empty() {
+            if hyphenate {
+                match hyphenate_word(word, max_width - x) {
+                    Ok((head, tail)) => {
+                        word = head;
+                        word.x = x;
+                        current_line.push(word);
+                        rest_of_word = Some(tail);
+                        lines.push(TextLine::new(std::mem::take(&mut current_line), false));
+                        current_width = 0.0;
+                        continue;
+                    }
+                    Err(whole) => word = whole,
+                }
+            }
+            lines.push(TextLine::new(std::mem::take(&mut current_line), false));
+            // The spaces a line was broken at are not carried to the next line.
+            word.spaces_before = 0;
+            x = word_start(&word, 0.0, true, space_width);
+        }
+
+        word.x = x;
+        current_width = x + word.width;
+        current_line.push(word);
+    }
+
+    if !current_line.is_empty() {
+        lines.push(TextLine::new(current_line, true));
+    }
+    lines
+}
+
+// Splits a word at the last hyphenation point that leaves room for its start
+// and a hyphen in `room`. Words holding inline images are left whole.
+fn hyphenate_word(word: Word<'_>, room: f32) -> Result<(Word<'_>, Word<'_>), Word<'_>> {
+    if word.pieces.iter().any(|piece| piece.run.image.is_some()) {
+        return Err(word);
+    }
+    let text: String = word.pieces.iter().map(|piece| piece.text).collect();
+    let head_width = |point: usize| {
+        let mut width = 0.0;
+        let mut start = 0;
+        for piece in &word.pieces {
+            let end = start + piece.text.len();
+            let hyphen_width = estimate_text_width(HYPHEN, piece.font_size);
+            if point <= end {
+                let text = &piece.text[..point - start];
+                return width + estimate_text_width(text, piece.font_size) + hyphen_width;
+            }
+            width += piece.width;
+            start = end;
+        }
+        width
+    };
+    let Some(point) = hyphenation_points(&text)
+        .into_iter()
+        .rev()
+        .filter(|&point| point > 0 && point < text.len() && text.is_char_boundary(point))
+        .find(|&point| head_width(point) <= room)
+    else {
+        return Err(word);
+    };
+
+    let mut head = Word {
+        tabs_before: word.tabs_before,
+        spaces_before: word.spaces_before,
+        joined: word.joined,
+        ..Default::default()
+    };
+    let mut tail = Word::default();
+    let mut start = 0;
+    for piece in word.pieces {
+        let end = start + piece.text.len();
+        if end <= point {
+            head.push_piece(piece.text, piece.run, piece.width, piece.font_size);
+        } else if start >= point {
+            tail.push_piece(piece.text, piece.run, piece.width, piece.font_size);
+        } else {
+            let (before, after) = piece.text.split_at(point - start);
+            let before_width = estimate_text_width(before, piece.font_size);
+            head.push_piece(before, piece.run, before_width, piece.font_size);
+            let after_width = piece.width - before_width;
+            tail.push_piece(after, piece.run, after_width, piece.font_size);
+        }
+        if start < point && point <= end {
+            let hyphen_width = estimate_text_width(HYPHEN, piece.font_size);
+            head.push_piece(HYPHEN, piece.run, hyphen_width, piece.font_size);
+        }
+        start = end;
+    }
+    Ok((head, tail))
+}
+
+// Without the `hyphenation` feature no word is hyphenated.
+#[cfg(not(feature = "hyphenation"))]
+fn hyphenation_points(_: &str) -> Vec<usize> {
+    Vec::new()
+}
+
+// A word wider than the whole line, such as a long URL or hash, is hard-broken
+// between characters into parts that each fit on a line.
+fn break_long_word(word: Word<'_>, max_width: f32) -> Vec<Word<'_>> {
+    if word.width <= max_width {
+        return vec![word];
+    }
+
+    let mut parts = Vec::new();
+    let mut current = Word {
+        tabs_before: word.tabs_before,
+        spaces_before: word.spaces_before,
+        joined: word.joined,
+        ..Default::default()
+    };
+    for piece in word.pieces {
+        if piece.run.image.is_some() {
+            if !current.pieces.is_empty() && current.width + piece.width > max_width {
+                parts.push(std::mem::take(&mut current));
+            }
+            current.push_piece(piece.text, piece.run, piece.width, piece.font_size);
+            continue;
+        }
+
+        let mut start = 0;
+        let mut width = 0.0;
+        for (index, ch) in piece.text.char_indices() {
+            let char_width = estimate_text_width(ch.encode_utf8(&mut [0; 4]), piece.font_size);
+            let has_text = index > start || !current.pieces.is_empty();
+            if has_text && current.width + width + char_width > max_width {
+                current.push_piece(&piece.text[start..index], piece.run, width, piece.font_size);
+                parts.push(std::mem::take(&mut current));
+                start = index;
+                width = 0.0;
+            }
+            width += char_width;
+        }
+        current.push_piece(&piece.text[start..], piece.run, width, piece.font_size);
+    }
+    parts.push(current);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap(text: &str, max_width: f32) -> Vec<Vec<String>> {
+        let runs = [TextRun {
+            text: text.to_string(),
+            ..Default::default()
+        }];
+        let words = split_words(&runs, 10.0, false).remove(0);
+        wrap_words(words, 10.0, max_width, 0.0, false)
+            .iter()
+            .map(|line| {
+                line.words
+                    .iter()
+                    .map(|word| word.pieces.iter().map(|piece| piece.text).collect())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cjk_characters_are_full_width_whatever_their_length_in_bytes() {
+        assert_eq!(
+            estimate_text_width("日本", 10.0),
+            estimate_text_width("abcd", 10.0)
+        );
+        assert_eq!(
+            estimate_text_width("éש", 10.0),
+            estimate_text_width("ab", 10.0)
+        );
+    }
+
+    #[test]
+    fn cjk_text_breaks_between_any_two_characters() {
+        // Each character is 5mm wide at 10pt, so six fit on a 30mm line.
+        let lines = wrap("一二三四五六七八九十", 30.0);
+        assert_eq!(
+            lines,
+            [
+                vec!["一", "二", "三", "四", "五", "六"],
+                vec!["七", "八", "九", "十"],
+            ]
+        );
+    }
+
+    #[test]
+    fn closing_punctuation_stays_with_the_character_before() {
+        let lines = wrap("一二三四五。", 25.0);
+        assert_eq!(lines, [vec!["一", "二", "三", "四"], vec!["五。"]]);
+    }
+
+    #[test]
+    fn latin_words_next_to_cjk_text_are_not_split() {
+        let lines = wrap("漢字 docx 漢字", 100.0);
+        assert_eq!(lines, [vec!["漢", "字", "docx", "漢", "字"]]);
+    }
+}
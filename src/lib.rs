@@ -0,0 +1,212 @@
+use log::{info, warn};
+use std::fs;
+use std::io::{Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+
+mod docx_reader;
+mod downsample;
+mod encryption;
+mod error;
+mod fields;
+#[cfg(feature = "hyphenation")]
+mod hyphenation;
+mod inspect;
+mod jpeg;
+mod layout;
+mod numbering;
+mod omml;
+mod options;
+mod package;
+mod pdf_writer;
+mod pdfa;
+mod postprocess;
+mod renderer;
+mod styles;
+#[cfg(feature = "svg")]
+mod svg;
+mod utils;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use crate::inspect::document_json;
+use crate::pdf_writer::{
+    convert_paragraphs_to_bytes, convert_paragraphs_to_pdf, render_paragraphs,
+};
+
+pub use crate::docx_reader::{read_docx, read_docx_from};
+pub use crate::error::{ConversionError, Result};
+pub use crate::options::{ConversionOptions, ConversionOptionsBuilder};
+pub use crate::pdf_writer::{
+    ConversionLimits, PageLabelRange, PageLabelStyle, PdfOptions, TrackChangesMode,
+    TypographyConfig, UnsupportedImageMode,
+};
+pub use crate::renderer::{FontFace, RasterColorSpace, RasterImage, Renderer};
+pub use crate::utils::{
+    Alignment, Border, BorderStyle, CellBorders, CellContent, CellVerticalAlign, Columns, Comment,
+    DocContent, DocumentMetadata, DocxDocument, FontFamily, FontMap, ImageContent, ImageSize,
+    Indentation, LineSpacing, ListItem, Note, PageField, PageSize, Paragraph, RgbColor, RowContent,
+    Section, SourceFile, Strike, TableBorders, TableContent, TextRun, VerticalAlign,
+};
+
+pub const PAGE_WIDTH: f32 = 210.0;
+pub const PAGE_HEIGHT: f32 = 297.0;
+pub const MARGIN: f32 = 10.0;
+pub const LINE_HEIGHT: f32 = 6.0;
+pub const PARAGRAPH_SPACING: f32 = 8.0;
+pub const FONT_SIZE: f32 = 11.0;
+pub const LIST_INDENT: f32 = 6.0;
+pub const HEADING_SPACING: f32 = 4.0;
+pub const IMAGE_DPI: f32 = 96.0;
+pub const CELL_PADDING: f32 = 1.9;
+
+/// Converts a DOCX to a PDF with options put together with
+/// [`ConversionOptions::builder`], such as
+/// `ConversionOptions::builder().font_size(12.0).watermark("DRAFT").build()`.
+pub fn convert(docx_path: &str, pdf_path: &str, options: &ConversionOptions) -> Result<()> {
+    convert_docx_to_pdf(docx_path, pdf_path, &options.typography, &options.pdf)
+}
+
+pub fn convert_docx_to_pdf(
+    docx_path: &str,
+    pdf_path: &str,
+    typography: &TypographyConfig,
+    pdf_options: &PdfOptions,
+) -> Result<()> {
+    convert_with_progress(docx_path, pdf_path, typography, pdf_options, |_, _| {})
+}
+
+/// Same as [`convert_docx_to_pdf`], calling `progress(done, total)` after each
+/// content item of the document is laid out.
+pub fn convert_with_progress(
+    docx_path: &str,
+    pdf_path: &str,
+    typography: &TypographyConfig,
+    pdf_options: &PdfOptions,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<()> {
+    let document = read_docx(docx_path, pdf_options)?;
+    info!("Successfully read DOCX file. Converting to PDF...");
+    convert_paragraphs_to_pdf(document, pdf_path, typography, pdf_options, &mut progress)?;
+    Ok(())
+}
+
+/// Writes a document read with [`read_docx`] as a PDF, so that its content
+/// can be inspected or changed in between, for example to remove paragraphs
+/// or add a cover page.
+pub fn convert_document(
+    document: DocxDocument,
+    pdf_path: &str,
+    typography: &TypographyConfig,
+    pdf_options: &PdfOptions,
+) -> Result<()> {
+    convert_paragraphs_to_pdf(document, pdf_path, typography, pdf_options, &mut |_, _| {})
+}
+
+/// Converts a DOCX read from any seekable source, such as an in-memory
+/// `Cursor<Vec<u8>>`.
+pub fn convert_reader<R: Read + Seek>(
+    reader: R,
+    pdf_path: &str,
+    typography: &TypographyConfig,
+    pdf_options: &PdfOptions,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<()> {
+    let document = read_docx_from(reader, pdf_options)?;
+    info!("Successfully read DOCX file. Converting to PDF...");
+    convert_paragraphs_to_pdf(document, pdf_path, typography, pdf_options, &mut progress)?;
+    Ok(())
+}
+
+/// Lays out a DOCX on a custom [`Renderer`] instead of writing a PDF, such as
+/// one producing another output format. `fit_page` is ignored.
+pub fn render_docx<R: Renderer>(
+    docx_path: &str,
+    renderer: &mut R,
+    typography: &TypographyConfig,
+    pdf_options: &PdfOptions,
+) -> Result<()> {
+    let document = read_docx(docx_path, pdf_options)?;
+    info!("Successfully read DOCX file. Rendering...");
+    render_paragraphs(document, renderer, typography, pdf_options, &mut |_, _| {})
+}
+
+/// Lays out a DOCX read from any seekable source on a custom [`Renderer`],
+/// like [`render_docx`].
+pub fn render_docx_from<R: Read + Seek, Rn: Renderer>(
+    reader: R,
+    renderer: &mut Rn,
+    typography: &TypographyConfig,
+    pdf_options: &PdfOptions,
+) -> Result<()> {
+    let document = read_docx_from(reader, pdf_options)?;
+    info!("Successfully read DOCX file. Rendering...");
+    render_paragraphs(document, renderer, typography, pdf_options, &mut |_, _| {})
+}
+
+/// Converts a DOCX held in memory and returns the PDF, without touching the
+/// filesystem.
+pub fn convert_bytes(
+    docx: &[u8],
+    typography: &TypographyConfig,
+    pdf_options: &PdfOptions,
+) -> Result<Vec<u8>> {
+    let document = read_docx_from(Cursor::new(docx), pdf_options)?;
+    info!("Successfully read DOCX file. Converting to PDF...");
+    convert_paragraphs_to_bytes(document, typography, pdf_options, &mut |_, _| {})
+}
+
+/// Converts every `.docx` file of `input_dir` to a PDF of the same name in
+/// `output_dir`, which is created if needed. A failing file does not stop the
+/// batch; the result of each file is returned in file name order.
+pub fn convert_dir(
+    input_dir: &str,
+    output_dir: &str,
+    typography: &TypographyConfig,
+    pdf_options: &PdfOptions,
+) -> Result<Vec<(PathBuf, Result<()>)>> {
+    let mut inputs: Vec<PathBuf> = fs::read_dir(input_dir)
+        .map_err(|e| ConversionError::io(input_dir, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("docx"))
+        })
+        .collect();
+    inputs.sort();
+
+    fs::create_dir_all(output_dir).map_err(|e| ConversionError::io(output_dir, e))?;
+
+    info!("Converting {} files from {}", inputs.len(), input_dir);
+    Ok(inputs
+        .into_iter()
+        .map(|input| {
+            let output = pdf_output_path(&input, Path::new(output_dir));
+            let result = convert_docx_to_pdf(
+                &input.to_string_lossy(),
+                &output.to_string_lossy(),
+                typography,
+                pdf_options,
+            );
+            if let Err(e) = &result {
+                warn!("Failed to convert {}: {}", input.display(), e);
+            }
+            (input, result)
+        })
+        .collect())
+}
+
+fn pdf_output_path(input: &Path, output_dir: &Path) -> PathBuf {
+    let mut name = input.file_stem().unwrap_or_default().to_os_string();
+    name.push(".pdf");
+    output_dir.join(name)
+}
+
+/// Reads a DOCX and describes its content items, in document order, as JSON
+/// without writing any PDF. The options that affect reading apply, such as
+/// the password or `strict`.
+pub fn inspect_docx(docx_path: &str, pdf_options: &PdfOptions) -> Result<String> {
+    let document = read_docx(docx_path, pdf_options)?;
+    Ok(document_json(&document))
+}
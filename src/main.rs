@@ -1,47 +1,272 @@
 use anyhow::Result;
-use log::{error, info};
-
-mod docx_reader;
-mod pdf_writer;
-mod utils;
-
-use crate::docx_reader::read_docx;
-use crate::pdf_writer::convert_paragraphs_to_pdf;
-
-pub const PAGE_WIDTH: f32 = 210.0;
-pub const PAGE_HEIGHT: f32 = 297.0;
-pub const MARGIN: f32 = 10.0;
-pub const LINE_HEIGHT: f32 = 6.0;
-pub const PARAGRAPH_SPACING: f32 = 8.0;
-pub const FONT_SIZE: f32 = 11.0;
+use docx::{convert, convert_dir, inspect_docx, ConversionOptions};
+use log::{error, info, LevelFilter};
 
 fn main() -> Result<()> {
-    env_logger::init();
-
     let args: Vec<String> = std::env::args().collect();
-    if args.len() < 3 {
-        anyhow::bail!("Usage: {} <input.docx> <output.pdf>", args[0]);
+    let cli = parse_args(&args)?;
+
+    // -q/-v override RUST_LOG, which still works for finer-grained filters.
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = cli.log_level {
+        logger.filter_level(level);
     }
-    let docx_path = &args[1];
-    let pdf_path = &args[2];
+    logger.init();
+
+    let docx_path = &cli.docx_path;
+    let pdf_path = match &cli.mode {
+        Mode::Convert { pdf_path } => pdf_path,
+        Mode::Inspect => {
+            print!("{}", inspect_docx(docx_path, &cli.options.pdf)?);
+            return Ok(());
+        }
+        Mode::Batch { output_dir } => {
+            return convert_batch(docx_path, output_dir, &cli.options);
+        }
+    };
 
     info!("Starting conversion from {} to {}", docx_path, pdf_path);
 
-    match convert_docx_to_pdf(docx_path, pdf_path) {
+    match convert(docx_path, pdf_path, &cli.options) {
         Ok(_) => {
             info!("Conversion completed successfully");
             Ok(())
         }
         Err(e) => {
-            error!("Conversion failed: {:?}", e);
-            Err(e)
+            error!("Conversion failed: {}", e);
+            Err(e.into())
         }
     }
 }
 
-fn convert_docx_to_pdf(docx_path: &str, pdf_path: &str) -> Result<()> {
-    let content = read_docx(docx_path)?;
-    info!("Successfully read DOCX file. Converting to PDF...");
-    convert_paragraphs_to_pdf(content, pdf_path)?;
+// Prints one line per file and fails if any file could not be converted.
+fn convert_batch(input_dir: &str, output_dir: &str, options: &ConversionOptions) -> Result<()> {
+    let results = convert_dir(input_dir, output_dir, &options.typography, &options.pdf)?;
+    let mut failed = 0;
+    for (path, result) in &results {
+        match result {
+            Ok(()) => println!("ok      {}", path.display()),
+            Err(e) => {
+                failed += 1;
+                println!("failed  {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} files failed to convert", failed, results.len());
+    }
+    info!("Converted {} files", results.len());
     Ok(())
-}
\ No newline at end of file
+}
+
+enum Mode {
+    Convert { pdf_path: String },
+    // Only describes the document.
+    Inspect,
+    // `docx_path` is then a directory of DOCX files.
+    Batch { output_dir: String },
+}
+
+struct CliArgs {
+    docx_path: String,
+    mode: Mode,
+    options: ConversionOptions,
+    log_level: Option<LevelFilter>,
+}
+
+fn parse_args(args: &[String]) -> Result<CliArgs> {
+    let usage = format!(
+        "Usage: {0} [options] <input.docx> <output.pdf>\n       \
+         {0} [options] --batch <input_dir> <output_dir>\n       \
+         {0} [options] --inspect <input.docx>",
+        args[0]
+    );
+    let mut positional = Vec::new();
+    let mut inspect = false;
+    let mut batch = false;
+    let mut builder = ConversionOptions::builder();
+    let mut log_level = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        match flag {
+            "--on-unsupported-image" => {
+                let mode = flag_value(flag, inline_value, &mut iter)?.parse()?;
+                builder = builder.on_unsupported_image(mode);
+            }
+            "--track-changes" => {
+                let mode = flag_value(flag, inline_value, &mut iter)?.parse()?;
+                builder = builder.track_changes(mode);
+            }
+            "--font-size" => {
+                builder = builder.font_size(positive_value(flag, inline_value, &mut iter)?);
+            }
+            "--line-height" => {
+                builder = builder.line_height(positive_value(flag, inline_value, &mut iter)?);
+            }
+            "--paragraph-spacing" => {
+                builder = builder.paragraph_spacing(positive_value(flag, inline_value, &mut iter)?);
+            }
+            "--cell-padding" => {
+                builder = builder.cell_padding(non_negative_value(flag, inline_value, &mut iter)?);
+            }
+            "--font-family" => {
+                builder = builder.font_family(flag_value(flag, inline_value, &mut iter)?.parse()?);
+            }
+            "--map-font" => {
+                let value = flag_value(flag, inline_value, &mut iter)?;
+                let Some((name, family)) = value.rsplit_once('=') else {
+                    anyhow::bail!(
+                        "Invalid value for {}: {} (expected NAME=FAMILY)",
+                        flag,
+                        value
+                    );
+                };
+                builder = builder.map_font(name, family.parse()?);
+            }
+            "--preserve-spaces" => builder = builder.preserve_spaces(true),
+            "--inspect" => inspect = true,
+            "--batch" => batch = true,
+            "--footnotes-at-end" => builder = builder.footnotes_at_end(true),
+            "--strict" => builder = builder.strict(true),
+            "--widow-control" => builder = builder.widow_control(true),
+            "--embed-source" => builder = builder.embed_source(true),
+            "--cover-page" => builder = builder.cover_page(true),
+            "--fit-page" => builder = builder.fit_page(true),
+            "--tables-as-text" => builder = builder.tables_as_text(true),
+            "--auto-caption" => builder = builder.auto_caption(true),
+            "--optimize" => builder = builder.optimize(true),
+            "--pdfa" => builder = builder.pdfa(true),
+            "--font-file" => {
+                builder = builder.font(read_file(&flag_value(flag, inline_value, &mut iter)?)?);
+            }
+            "--bold-font-file" => {
+                let font = read_file(&flag_value(flag, inline_value, &mut iter)?)?;
+                builder = builder.bold_font(font);
+            }
+            "--password" => {
+                builder = builder.password(&flag_value(flag, inline_value, &mut iter)?);
+            }
+            "--page-labels" => {
+                let ranges = flag_value(flag, inline_value, &mut iter)?
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<docx::Result<_>>()?;
+                builder = builder.page_labels(ranges);
+            }
+            "--page-size" => {
+                builder = builder.page_size(flag_value(flag, inline_value, &mut iter)?.parse()?);
+            }
+            "--watermark" => {
+                builder = builder.watermark(&flag_value(flag, inline_value, &mut iter)?);
+            }
+            "--image-dpi" => {
+                builder = builder.image_dpi(positive_value(flag, inline_value, &mut iter)?);
+            }
+            "--max-image-dimension" => {
+                let pixels = positive_integer(flag, inline_value, &mut iter)?;
+                builder = builder.max_image_dimension(pixels);
+            }
+            "-q" | "--quiet" => log_level = Some(LevelFilter::Error),
+            "-v" | "--verbose" => log_level = Some(LevelFilter::Debug),
+            _ if flag.starts_with('-') => anyhow::bail!("Unknown option: {}\n{}", flag, usage),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let (docx_path, mode) = match (inspect, batch, positional.as_slice()) {
+        (false, false, [docx_path, pdf_path]) => (
+            docx_path.clone(),
+            Mode::Convert {
+                pdf_path: pdf_path.clone(),
+            },
+        ),
+        (false, true, [input_dir, output_dir]) => (
+            input_dir.clone(),
+            Mode::Batch {
+                output_dir: output_dir.clone(),
+            },
+        ),
+        (true, false, [docx_path]) => (docx_path.clone(), Mode::Inspect),
+        _ => anyhow::bail!("{}", usage),
+    };
+
+    Ok(CliArgs {
+        docx_path,
+        mode,
+        options: builder.build(),
+        log_level,
+    })
+}
+
+fn flag_value<'a>(
+    flag: &str,
+    inline_value: Option<String>,
+    iter: &mut impl Iterator<Item = &'a String>,
+) -> Result<String> {
+    match inline_value {
+        Some(value) => Ok(value),
+        None => iter
+            .next()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Missing value for {}", flag)),
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| anyhow::anyhow!("{}: {}", path, e))
+}
+
+fn positive_value<'a>(
+    flag: &str,
+    inline_value: Option<String>,
+    iter: &mut impl Iterator<Item = &'a String>,
+) -> Result<f32> {
+    let value = flag_value(flag, inline_value, iter)?;
+    match value.parse::<f32>() {
+        Ok(number) if number > 0.0 => Ok(number),
+        _ => anyhow::bail!(
+            "Invalid value for {}: {} (expected a positive number)",
+            flag,
+            value
+        ),
+    }
+}
+
+fn positive_integer<'a>(
+    flag: &str,
+    inline_value: Option<String>,
+    iter: &mut impl Iterator<Item = &'a String>,
+) -> Result<u32> {
+    let value = flag_value(flag, inline_value, iter)?;
+    match value.parse::<u32>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => anyhow::bail!(
+            "Invalid value for {}: {} (expected a positive whole number)",
+            flag,
+            value
+        ),
+    }
+}
+
+fn non_negative_value<'a>(
+    flag: &str,
+    inline_value: Option<String>,
+    iter: &mut impl Iterator<Item = &'a String>,
+) -> Result<f32> {
+    let value = flag_value(flag, inline_value, iter)?;
+    match value.parse::<f32>() {
+        Ok(number) if number >= 0.0 => Ok(number),
+        _ => anyhow::bail!(
+            "Invalid value for {}: {} (expected zero or a positive number)",
+            flag,
+            value
+        ),
+    }
+}
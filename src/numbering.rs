@@ -0,0 +1,124 @@
+use docx_rust::numbering::{Level, Numbering};
+use std::collections::HashMap;
+
+use crate::utils::ListItem;
+
+const BULLET_MARKER: &str = "•";
+
+pub struct ListTracker<'a> {
+    numbering: Option<&'a Numbering<'a>>,
+    counters: HashMap<usize, Vec<usize>>,
+}
+
+impl<'a> ListTracker<'a> {
+    pub fn new(numbering: Option<&'a Numbering<'a>>) -> Self {
+        ListTracker {
+            numbering,
+            counters: HashMap::new(),
+        }
+    }
+
+    pub fn next_item(&mut self, num_id: usize, level: usize) -> ListItem {
+        let level_def = self.find_level(num_id, level);
+
+        // Without a definition in numbering.xml there is no way to know the
+        // format, so fall back to a plain bullet.
+        let format = level_def
+            .and_then(|def| def.number_format.as_ref())
+            .map(|format| format.value.as_ref());
+        let is_bullet = format.is_none_or(|format| format == "bullet");
+        if is_bullet {
+            return ListItem {
+                level,
+                marker: BULLET_MARKER.to_string(),
+            };
+        }
+
+        let start = level_def
+            .and_then(|def| def.start.as_ref())
+            .and_then(|start| start.value)
+            .unwrap_or(1)
+            .max(0) as usize;
+
+        // A new item at this level restarts the counters of any deeper level.
+        let counters = self.counters.entry(num_id).or_default();
+        counters.resize(level + 1, 0);
+        counters[level] += 1;
+
+        ListItem {
+            level,
+            marker: format!(
+                "{}.",
+                format_number(format.unwrap_or_default(), start + counters[level] - 1)
+            ),
+        }
+    }
+
+    fn find_level(&self, num_id: usize, level: usize) -> Option<&'a Level<'a>> {
+        let numbering = self.numbering?;
+        let abstract_id = numbering
+            .numberings
+            .iter()
+            .find(|num| num.num_id == Some(num_id as isize))?
+            .abstract_num_id
+            .as_ref()?
+            .value?;
+
+        numbering
+            .abstract_numberings
+            .iter()
+            .find(|abstract_num| abstract_num.abstract_num_id == Some(abstract_id))?
+            .levels
+            .iter()
+            .find(|def| def.i_level == Some(level as isize))
+    }
+}
+
+// The counter of a list item in the w:numFmt of its level. Unknown formats
+// fall back to decimal.
+fn format_number(format: &str, number: usize) -> String {
+    match format {
+        "lowerRoman" => lower_roman(number),
+        "upperRoman" => lower_roman(number).to_uppercase(),
+        "lowerLetter" => letter(number),
+        "upperLetter" => letter(number).to_uppercase(),
+        "decimalZero" => format!("{:02}", number),
+        _ => number.to_string(),
+    }
+}
+
+// Word counts a, b, ..., z, then aa, bb, ..., zz, then aaa and so on.
+pub fn letter(number: usize) -> String {
+    let Some(index) = number.checked_sub(1) else {
+        return String::new();
+    };
+    let letter = char::from(b'a' + (index % 26) as u8);
+    letter.to_string().repeat(index / 26 + 1)
+}
+
+// Lowercase roman numeral, as used by Word for endnote marks.
+pub fn lower_roman(mut number: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut roman = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            roman.push_str(numeral);
+            number -= value;
+        }
+    }
+    roman
+}
@@ -0,0 +1,117 @@
+use crate::package::{attribute_value, next_tag, tag_name, unescape};
+
+// The default n-ary operator of m:nary, when m:chr is left out.
+const INTEGRAL: &str = "∫";
+
+// Writes an Office Math (OMML) equation as linear text, such as "x^2+y^2=z^2"
+// or "(a+b)/2", from the XML inside its m:oMath element. Superscripts,
+// subscripts, fractions, radicals, delimiters, n-ary operators and functions
+// are linearized; the other structures keep only their text.
+pub fn linear_text(omml: &str) -> String {
+    let mut text = String::new();
+    let mut stack: Vec<&str> = Vec::new();
+    // Where the text of each open group starts, to drop the parentheses
+    // around a single term once it is closed.
+    let mut groups: Vec<usize> = Vec::new();
+    // Opening and closing characters of each open m:d, and whether its first
+    // element was written.
+    let mut delimiters: Vec<(String, String, bool)> = Vec::new();
+    let mut nary_chr: Option<String> = None;
+
+    let mut position = 0;
+    while let Some((tag, end)) = next_tag(omml, position) {
+        let text_start = end;
+        position = end;
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = tag_name(name);
+            stack.pop();
+            let parent = stack.last().copied();
+            match name {
+                "m:num" | "m:den" | "m:sup" | "m:sub" => {
+                    let start = groups.pop().unwrap_or(text.len());
+                    close_group(&mut text, start);
+                }
+                "m:deg" if text.ends_with('[') => {
+                    text.pop();
+                }
+                "m:deg" => text.push(']'),
+                "m:e" if matches!(parent, Some("m:rad" | "m:func")) => text.push(')'),
+                "m:naryPr" => text.push_str(nary_chr.take().as_deref().unwrap_or(INTEGRAL)),
+                "m:d" => {
+                    if let Some((_, close, _)) = delimiters.pop() {
+                        text.push_str(&close);
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let name = tag_name(tag);
+        let parent = stack.last().copied();
+        match name {
+            "m:t" if !self_closing => {
+                let text_end = omml[text_start..]
+                    .find('<')
+                    .map_or(omml.len(), |end| text_start + end);
+                text.push_str(&unescape(&omml[text_start..text_end]));
+            }
+            "m:num" | "m:den" | "m:sup" | "m:sub" if !self_closing => {
+                text.push_str(match name {
+                    "m:den" => "/(",
+                    "m:sup" => "^(",
+                    "m:sub" => "_(",
+                    _ => "(",
+                });
+                groups.push(text.len());
+            }
+            "m:deg" if !self_closing => text.push('['),
+            "m:rad" => text.push('√'),
+            "m:e" if matches!(parent, Some("m:rad" | "m:func")) => text.push('('),
+            "m:e" if parent == Some("m:d") => {
+                if let Some((open, _, started)) = delimiters.last_mut() {
+                    if *started {
+                        text.push(',');
+                    } else {
+                        text.push_str(open);
+                        *started = true;
+                    }
+                }
+            }
+            "m:d" => delimiters.push(("(".to_string(), ")".to_string(), false)),
+            "m:begChr" | "m:endChr" => {
+                let value = attribute_value(tag, "m:val")
+                    .map(unescape)
+                    .unwrap_or_default();
+                match delimiters.last_mut() {
+                    Some((open, _, _)) if name == "m:begChr" => *open = value,
+                    Some((_, close, _)) => *close = value,
+                    None => {}
+                }
+            }
+            "m:nary" => nary_chr = None,
+            "m:chr" if parent == Some("m:naryPr") => {
+                nary_chr = attribute_value(tag, "m:val").map(unescape);
+            }
+            _ => {}
+        }
+        if !self_closing {
+            stack.push(name);
+        }
+    }
+    text
+}
+
+// Closes the group whose text starts at `start`, right after its opening
+// parenthesis. A single term, such as "2" in "x^(2)", needs none.
+fn close_group(text: &mut String, start: usize) {
+    let content = &text[start..];
+    if !content.is_empty() && content.chars().all(char::is_alphanumeric) {
+        let content = content.to_string();
+        text.truncate(start - 1);
+        text.push_str(&content);
+    } else {
+        text.push(')');
+    }
+}
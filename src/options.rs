@@ -0,0 +1,198 @@
+use crate::pdf_writer::{
+    ConversionLimits, PageLabelRange, PdfOptions, TrackChangesMode, TypographyConfig,
+    UnsupportedImageMode,
+};
+use crate::utils::{FontFamily, PageSize};
+
+// The typography and PDF options of a conversion, as one value. Built with
+// ConversionOptions::builder(), the entry point for setting options, so that
+// new options don't change the signature of the conversion functions:
+// `ConversionOptions::builder().page_size(PageSize::LETTER).build()`.
+#[derive(Debug, Default)]
+pub struct ConversionOptions {
+    pub typography: TypographyConfig,
+    pub pdf: PdfOptions,
+}
+
+impl ConversionOptions {
+    pub fn builder() -> ConversionOptionsBuilder {
+        ConversionOptionsBuilder::default()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ConversionOptionsBuilder {
+    font_family: Option<FontFamily>,
+    font_size: Option<f32>,
+    line_height: Option<f32>,
+    paragraph_spacing: Option<f32>,
+    cell_padding: Option<f32>,
+    pdf: PdfOptions,
+}
+
+impl ConversionOptionsBuilder {
+    pub fn font_family(mut self, family: FontFamily) -> Self {
+        self.font_family = Some(family);
+        self
+    }
+
+    // The line height and cell padding keep their ratio to the font size,
+    // unless they are set too.
+    pub fn font_size(mut self, font_size: f32) -> Self {
+        self.font_size = Some(font_size);
+        self
+    }
+
+    pub fn line_height(mut self, line_height: f32) -> Self {
+        self.line_height = Some(line_height);
+        self
+    }
+
+    pub fn paragraph_spacing(mut self, spacing: f32) -> Self {
+        self.paragraph_spacing = Some(spacing);
+        self
+    }
+
+    pub fn cell_padding(mut self, padding: f32) -> Self {
+        self.cell_padding = Some(padding);
+        self
+    }
+
+    pub fn map_font(mut self, name: &str, family: FontFamily) -> Self {
+        self.pdf.font_map.add_mapping(name, family);
+        self
+    }
+
+    // A TrueType font embedded in the PDF instead of the built-in fonts.
+    pub fn font(mut self, font: Vec<u8>) -> Self {
+        self.pdf.font = Some(font);
+        self
+    }
+
+    pub fn bold_font(mut self, font: Vec<u8>) -> Self {
+        self.pdf.bold_font = Some(font);
+        self
+    }
+
+    pub fn on_unsupported_image(mut self, mode: UnsupportedImageMode) -> Self {
+        self.pdf.on_unsupported_image = mode;
+        self
+    }
+
+    pub fn track_changes(mut self, mode: TrackChangesMode) -> Self {
+        self.pdf.track_changes = mode;
+        self
+    }
+
+    pub fn image_dpi(mut self, dpi: f32) -> Self {
+        self.pdf.image_dpi = dpi;
+        self
+    }
+
+    pub fn max_image_dimension(mut self, pixels: u32) -> Self {
+        self.pdf.max_image_dimension = Some(pixels);
+        self
+    }
+
+    pub fn preserve_spaces(mut self, preserve: bool) -> Self {
+        self.pdf.preserve_spaces = preserve;
+        self
+    }
+
+    pub fn footnotes_at_end(mut self, at_end: bool) -> Self {
+        self.pdf.footnotes_at_end = at_end;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.pdf.strict = strict;
+        self
+    }
+
+    pub fn widow_control(mut self, widow_control: bool) -> Self {
+        self.pdf.widow_control = widow_control;
+        self
+    }
+
+    pub fn password(mut self, password: &str) -> Self {
+        self.pdf.password = Some(password.to_string());
+        self
+    }
+
+    pub fn embed_source(mut self, embed: bool) -> Self {
+        self.pdf.embed_source = embed;
+        self
+    }
+
+    pub fn cover_page(mut self, cover_page: bool) -> Self {
+        self.pdf.cover_page = cover_page;
+        self
+    }
+
+    pub fn fit_page(mut self, fit_page: bool) -> Self {
+        self.pdf.fit_page = fit_page;
+        self
+    }
+
+    pub fn watermark(mut self, text: &str) -> Self {
+        self.pdf.watermark = Some(text.to_string());
+        self
+    }
+
+    pub fn tables_as_text(mut self, as_text: bool) -> Self {
+        self.pdf.tables_as_text = as_text;
+        self
+    }
+
+    pub fn auto_caption(mut self, auto_caption: bool) -> Self {
+        self.pdf.auto_caption = auto_caption;
+        self
+    }
+
+    pub fn optimize(mut self, optimize: bool) -> Self {
+        self.pdf.optimize = optimize;
+        self
+    }
+
+    pub fn pdfa(mut self, pdfa: bool) -> Self {
+        self.pdf.pdfa = pdfa;
+        self
+    }
+
+    pub fn limits(mut self, limits: ConversionLimits) -> Self {
+        self.pdf.limits = limits;
+        self
+    }
+
+    pub fn page_labels(mut self, ranges: Vec<PageLabelRange>) -> Self {
+        self.pdf.page_labels = ranges;
+        self
+    }
+
+    pub fn page_size(mut self, size: PageSize) -> Self {
+        self.pdf.page_size = Some(size);
+        self
+    }
+
+    pub fn build(self) -> ConversionOptions {
+        let mut typography = self
+            .font_size
+            .map_or_else(TypographyConfig::default, TypographyConfig::with_font_size);
+        if let Some(family) = self.font_family {
+            typography.font_family = family;
+        }
+        if let Some(line_height) = self.line_height {
+            typography.line_height = line_height;
+        }
+        if let Some(spacing) = self.paragraph_spacing {
+            typography.paragraph_spacing = spacing;
+        }
+        if let Some(padding) = self.cell_padding {
+            typography.cell_padding = padding;
+        }
+        ConversionOptions {
+            typography,
+            pdf: self.pdf,
+        }
+    }
+}
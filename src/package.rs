@@ -0,0 +1,966 @@
+use crate::error::{ConversionError, Result};
+use crate::omml::linear_text;
+use crate::utils::{
+    emus_to_mm, CellVerticalAlign, Columns, Comment, DocumentMetadata, ImageSize, PageSize,
+    RgbColor, Section,
+};
+use crate::MARGIN;
+
+use docx_rust::DocxFile;
+use log::debug;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use zip::{result::ZipError, write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+const MACRO_ENABLED_MAIN: &str = "application/vnd.ms-word.document.macroEnabled.main+xml";
+const DOCUMENT_MAIN: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml";
+const STRICT_NAMESPACE_PREFIX: &str = "http://purl.oclc.org/ooxml/";
+// Signature of OLE compound files, the container of legacy Word .doc files.
+const OLE_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+// Strict OOXML uses its own namespaces for the parts docx-rust reads. Strict
+// measurement units (such as "12pt") are not converted.
+const STRICT_NAMESPACES: [(&str, &str); 6] = [
+    (
+        "http://purl.oclc.org/ooxml/wordprocessingml/main",
+        "http://schemas.openxmlformats.org/wordprocessingml/2006/main",
+    ),
+    (
+        "http://purl.oclc.org/ooxml/officeDocument/relationships",
+        "http://schemas.openxmlformats.org/officeDocument/2006/relationships",
+    ),
+    (
+        "http://purl.oclc.org/ooxml/drawingml/main",
+        "http://schemas.openxmlformats.org/drawingml/2006/main",
+    ),
+    (
+        "http://purl.oclc.org/ooxml/drawingml/wordprocessingDrawing",
+        "http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing",
+    ),
+    (
+        "http://purl.oclc.org/ooxml/drawingml/picture",
+        "http://schemas.openxmlformats.org/drawingml/2006/picture",
+    ),
+    (
+        "http://purl.oclc.org/ooxml/officeDocument/math",
+        "http://schemas.openxmlformats.org/officeDocument/2006/math",
+    ),
+];
+
+// Opens the document part of a .docx, .docm (macro-enabled) or strict OOXML
+// package. Macro-enabled and strict packages are rewritten in memory as a
+// regular transitional .docx first; the VBA storage is left untouched and
+// never read.
+pub fn open_docx_file<R: Read + Seek>(reader: &mut R) -> Result<DocxFile> {
+    if is_ole_file(reader)? {
+        return Err(ConversionError::LegacyDocUnsupported);
+    }
+    let rewritten = {
+        let mut zip = ZipArchive::new(&mut *reader)?;
+        if needs_rewrite(&mut zip)? {
+            debug!("Rewriting macro-enabled or strict package as a regular DOCX");
+            Some(rewrite_package(&mut zip)?)
+        } else {
+            None
+        }
+    };
+
+    let doc = match rewritten {
+        Some(bytes) => DocxFile::from_reader(Cursor::new(bytes)),
+        None => {
+            reader.seek(SeekFrom::Start(0)).map_err(ZipError::Io)?;
+            DocxFile::from_reader(&mut *reader)
+        }
+    };
+    doc.map_err(|e| ConversionError::DocxParse(format!("{:?}", e)))
+}
+
+// Legacy .doc files are OLE compound files instead of ZIP packages, which
+// would otherwise fail with an unhelpful archive error. So are password
+// protected DOCX files, decrypted before the package is opened.
+pub(crate) fn is_ole_file<R: Read + Seek>(reader: &mut R) -> Result<bool> {
+    let mut signature = [0; OLE_SIGNATURE.len()];
+    let read = reader.read_exact(&mut signature);
+    reader.seek(SeekFrom::Start(0)).map_err(ZipError::Io)?;
+    Ok(read.is_ok() && signature == OLE_SIGNATURE)
+}
+
+// Path in the package of a part the document relates to. Targets are
+// relative to word/, where the document is, such as "media/image1.png" or
+// "../media/image1.png", or absolute, such as "/word/media/image1.png".
+// Targets starting with "word/", written by some producers, are taken from the
+// root of the package too.
+pub fn document_part_path(target: &str) -> String {
+    let (base, target) = match target.strip_prefix('/') {
+        Some(target) => (None, target),
+        None if target.starts_with("word/") => (None, target),
+        None => (Some("word"), target),
+    };
+    let mut segments: Vec<&str> = base.into_iter().collect();
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+// Reads the title, author, subject, keywords and date of the document. A
+// package without docProps/core.xml simply has no metadata.
+pub fn read_core_properties<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<DocumentMetadata> {
+    let Some(core) = read_text_part(zip, "docProps/core.xml")? else {
+        return Ok(DocumentMetadata::default());
+    };
+    Ok(DocumentMetadata {
+        title: element_text(&core, "dc:title"),
+        author: element_text(&core, "dc:creator"),
+        subject: element_text(&core, "dc:subject"),
+        keywords: element_text(&core, "cp:keywords")
+            .map(|keywords| {
+                keywords
+                    .split([',', ';'])
+                    .map(str::trim)
+                    .filter(|keyword| !keyword.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        // W3CDTF dates, such as 2024-03-01T09:30:00Z.
+        date: element_text(&core, "dcterms:modified")
+            .or_else(|| element_text(&core, "dcterms:created"))
+            .and_then(|date| date.split('T').next().map(str::to_string)),
+    })
+}
+
+// docx-rust does not read w:basedOn, so the links between styles are taken
+// from styles.xml directly. Maps each style id to the id of its base style.
+pub fn read_style_bases<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+) -> Result<HashMap<String, String>> {
+    let Some(styles) = read_text_part(zip, "word/styles.xml")? else {
+        return Ok(HashMap::new());
+    };
+    let mut bases = HashMap::new();
+    for style in styles.split("<w:style ").skip(1) {
+        let style = style.split("</w:style>").next().unwrap_or(style);
+        let Some(id) = attribute_value(style, "w:styleId") else {
+            continue;
+        };
+        let base = style
+            .split_once("<w:basedOn ")
+            .and_then(|(_, based_on)| attribute_value(based_on, "w:val"));
+        if let Some(base) = base {
+            bases.insert(id.to_string(), base.to_string());
+        }
+    }
+    Ok(bases)
+}
+
+// What docx-rust does not read of the body of document.xml, gathered in a
+// single pass over it. Paragraph indexes count the top-level body paragraphs.
+#[derive(Default)]
+pub struct BodyMarkup {
+    // The first section, and the section after each paragraph that ends one,
+    // by paragraph index.
+    pub first_section: Section,
+    pub section_breaks: HashMap<usize, Section>,
+    // Text of the text boxes anchored in each paragraph, as the text of their
+    // own paragraphs.
+    pub text_boxes: HashMap<usize, Vec<Vec<String>>>,
+    pub anchored_images: HashMap<usize, Vec<AnchoredImage>>,
+    pub equations: HashMap<usize, Vec<Equation>>,
+    // Markup of each body table, for the tables in the order they start in
+    // document.xml: a table comes before the tables nested in its cells.
+    pub tables: Vec<TableMarkup>,
+    // Review comments by the index of the body paragraph or table they are
+    // anchored in, counting both.
+    pub comments: HashMap<usize, Vec<Comment>>,
+}
+
+// A tag of the body, and where it is.
+struct BodyTag<'d, 's> {
+    // The tag without its angle brackets, and its name, also for closing
+    // tags.
+    tag: &'d str,
+    name: &'d str,
+    closing: bool,
+    self_closing: bool,
+    // The elements open around the tag, from w:body down. A closing tag's
+    // own element is already left out.
+    stack: &'s [&'d str],
+    // Body paragraphs, and body paragraphs and tables, started so far.
+    body_paragraphs: usize,
+    body_items: usize,
+    // The document after the tag.
+    rest: &'d str,
+}
+
+impl<'d> BodyTag<'d, '_> {
+    // The text up to the next tag, such as the content of a w:t.
+    fn text(&self) -> &'d str {
+        let end = self.rest.find('<').unwrap_or(self.rest.len());
+        &self.rest[..end]
+    }
+}
+
+// Walks document.xml once, handing each tag of the body to the readers of
+// sections, text boxes, anchored images, equations, tables and comments.
+pub fn read_body_markup<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<BodyMarkup> {
+    let Some(document) = read_text_part(zip, "word/document.xml")? else {
+        return Ok(BodyMarkup::default());
+    };
+    let Some(body_start) = document.find("<w:body") else {
+        return Ok(BodyMarkup::default());
+    };
+    let comments_xml = read_text_part(zip, "word/comments.xml")?.unwrap_or_default();
+
+    let mut sections = SectionReader::default();
+    let mut text_boxes = TextBoxReader::default();
+    let mut anchored_images = AnchoredImageReader::default();
+    let mut equations = EquationReader::default();
+    let mut tables = TableReader::default();
+    let mut comments = CommentReader {
+        comments: comments_by_id(&comments_xml),
+        anchored: HashMap::new(),
+    };
+
+    let mut stack: Vec<&str> = Vec::new();
+    let mut body_paragraphs: usize = 0;
+    let mut body_items: usize = 0;
+    let mut position = body_start;
+    while let Some((tag, end)) = next_tag(&document, position) {
+        position = end;
+        let closing = tag.starts_with('/');
+        let self_closing = tag.ends_with('/');
+        let name = tag_name(tag.trim_start_matches('/'));
+        if closing {
+            stack.pop();
+        } else if stack.len() == 1 && matches!(name, "w:p" | "w:tbl") {
+            body_items += 1;
+            body_paragraphs += usize::from(name == "w:p");
+        }
+
+        let body_tag = BodyTag {
+            tag,
+            name,
+            closing,
+            self_closing,
+            stack: &stack,
+            body_paragraphs,
+            body_items,
+            rest: &document[position..],
+        };
+        sections.read(&body_tag);
+        text_boxes.read(&body_tag);
+        anchored_images.read(&body_tag);
+        equations.read(&body_tag);
+        tables.read(&body_tag);
+        comments.read(&body_tag);
+
+        if !closing && !self_closing {
+            stack.push(name);
+        }
+    }
+
+    let (first_section, section_breaks) = sections.breaks();
+    Ok(BodyMarkup {
+        first_section,
+        section_breaks,
+        text_boxes: text_boxes.text_boxes,
+        anchored_images: anchored_images.by_paragraph(&sections),
+        equations: equations.equations,
+        tables: tables.markup,
+        comments: comments.anchored,
+    })
+}
+
+// The page size, columns and margins of each section, from the w:pgSz, w:cols
+// and w:pgMar of its w:sectPr, which docx-rust does not read either. Every
+// section but the last ends with a body paragraph holding its w:sectPr, and
+// the last one's is at the end of the body.
+#[derive(Default)]
+struct SectionReader {
+    sections: Vec<SectionMarkup>,
+}
+
+struct SectionMarkup {
+    // The paragraph ending the section, none for the last one.
+    end: Option<usize>,
+    section: Section,
+    // Left and top page margins, in mm.
+    margins: [f32; 2],
+}
+
+impl SectionMarkup {
+    fn new(end: Option<usize>) -> Self {
+        SectionMarkup {
+            end,
+            section: Section::default(),
+            margins: [MARGIN; 2],
+        }
+    }
+}
+
+impl SectionReader {
+    fn read(&mut self, tag: &BodyTag) {
+        if tag.closing {
+            return;
+        }
+        match (tag.name, tag.stack) {
+            // Revisions of the section properties (w:sectPrChange) hold a
+            // w:sectPr of their own, deeper down.
+            ("w:sectPr", [_]) => self.sections.push(SectionMarkup::new(None)),
+            ("w:sectPr", [_, "w:p", "w:pPr"]) => {
+                let paragraph = tag.body_paragraphs.checked_sub(1);
+                self.sections.push(SectionMarkup::new(paragraph));
+            }
+            (name, [_, "w:sectPr"] | [_, "w:p", "w:pPr", "w:sectPr"]) => {
+                let Some(markup) = self.sections.last_mut() else {
+                    return;
+                };
+                match name {
+                    "w:pgSz" => markup.section.page_size = page_size(tag.tag),
+                    "w:cols" => markup.section.columns = columns(tag.tag),
+                    // In twips. A negative top margin is the same margin,
+                    // kept even when the header is taller.
+                    "w:pgMar" => {
+                        for (margin, name) in markup.margins.iter_mut().zip(["w:left", "w:top"]) {
+                            let twips = attribute_value(tag.tag, name)
+                                .and_then(|value| value.parse::<f32>().ok());
+                            if let Some(twips) = twips {
+                                *margin = twips.abs() / 1440.0 * 25.4;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Left and top margins of the section holding the given paragraph.
+    fn margins(&self, paragraph: usize) -> [f32; 2] {
+        self.sections
+            .iter()
+            .find(|markup| markup.end.is_none_or(|end| end >= paragraph))
+            .map_or([MARGIN; 2], |markup| markup.margins)
+    }
+
+    // The first section, and the section after each paragraph that ends one,
+    // keyed by the paragraph's index.
+    fn breaks(&self) -> (Section, HashMap<usize, Section>) {
+        let first = self
+            .sections
+            .first()
+            .map_or_else(Section::default, |markup| markup.section);
+        let breaks = self
+            .sections
+            .windows(2)
+            .filter_map(|pair| Some((pair[0].end?, pair[1].section)))
+            .collect();
+        (first, breaks)
+    }
+}
+
+fn columns(cols: &str) -> Columns {
+    let default = Columns::default();
+    Columns {
+        count: attribute_value(cols, "w:num")
+            .and_then(|num| num.parse::<usize>().ok())
+            .filter(|&count| count > 0)
+            .unwrap_or(default.count),
+        // w:space is in twips.
+        spacing: attribute_value(cols, "w:space")
+            .and_then(|space| space.parse::<f32>().ok())
+            .map_or(default.spacing, |space| space / 1440.0 * 25.4),
+    }
+}
+
+// w:w and w:h are in twips. Word already swaps them for landscape pages
+// (w:orient="landscape"); the orientation alone is honored when they are left
+// out.
+fn page_size(pg_sz: &str) -> PageSize {
+    let twips = |name| {
+        attribute_value(pg_sz, name)
+            .and_then(|value| value.parse::<f32>().ok())
+            .filter(|&value| value > 0.0)
+            .map(|value| value / 1440.0 * 25.4)
+    };
+    let default = PageSize::default();
+    let size = PageSize {
+        width: twips("w:w").unwrap_or(default.width),
+        height: twips("w:h").unwrap_or(default.height),
+    };
+    let landscape = attribute_value(pg_sz, "w:orient") == Some("landscape");
+    if landscape && size.width < size.height {
+        PageSize {
+            width: size.height,
+            height: size.width,
+        }
+    } else {
+        size
+    }
+}
+
+// Text boxes are wrapped in mc:AlternateContent, which docx-rust skips, so
+// their text is read from document.xml directly. Only the text boxes of
+// top-level body paragraphs are kept: the VML copy in mc:Fallback and text
+// boxes inside tables are left out.
+#[derive(Default)]
+struct TextBoxReader {
+    text_boxes: HashMap<usize, Vec<Vec<String>>>,
+    // Paragraph texts of the text box being read, and its depth.
+    current: Option<(Vec<String>, usize)>,
+}
+
+impl TextBoxReader {
+    fn read(&mut self, tag: &BodyTag) {
+        if tag.closing {
+            let closes_text_box = tag.name == "w:txbxContent"
+                && self
+                    .current
+                    .as_ref()
+                    .is_some_and(|(_, depth)| *depth == tag.stack.len());
+            if closes_text_box {
+                let (paragraphs, _) = self.current.take().expect("text box is being read");
+                if let Some(index) = tag.body_paragraphs.checked_sub(1) {
+                    self.text_boxes.entry(index).or_default().push(paragraphs);
+                }
+            }
+            return;
+        }
+
+        if let Some((paragraphs, _)) = &mut self.current {
+            if tag.name == "w:p" {
+                paragraphs.push(String::new());
+            } else if let Some(paragraph) = paragraphs.last_mut() {
+                match tag.name {
+                    "w:t" if !tag.self_closing => paragraph.push_str(&unescape(tag.text())),
+                    "w:tab" => paragraph.push('\t'),
+                    "w:br" | "w:cr" => paragraph.push('\n'),
+                    _ => {}
+                }
+            }
+        } else if tag.name == "w:txbxContent"
+            && tag.stack.get(1) == Some(&"w:p")
+            && !tag.stack.contains(&"mc:Fallback")
+        {
+            self.current = Some((Vec::new(), tag.stack.len()));
+        }
+    }
+}
+
+// A picture anchored in a body paragraph (wp:anchor).
+pub struct AnchoredImage {
+    // Relationship id of the picture.
+    pub embed: String,
+    pub size: Option<ImageSize>,
+    // Offset of the top left corner from the top left corner of the page, in
+    // mm, for images placed relative to the page or margins with no text
+    // wrapping around them. The others are set where they are anchored.
+    pub position: Option<(f32, f32)>,
+}
+
+// docx-rust only reads inline pictures, so floating ones are read from
+// document.xml directly, for the top-level body paragraphs they are anchored
+// in. Shapes and text boxes, which have no picture, are left out, as are the
+// copies in mc:Fallback.
+#[derive(Default)]
+struct AnchoredImageReader {
+    // The pictures read and the index of their paragraph, and which of their
+    // offsets start at the margins. The margins are those of the section,
+    // known once its end is read.
+    images: Vec<(usize, AnchoredImage, [bool; 2])>,
+    // The anchor being read and its depth.
+    current: Option<(AnchoredImage, usize)>,
+    // Whether text wraps around that anchor, and its horizontal and vertical
+    // offsets from the page or margins, unknown when relative to anything
+    // else.
+    wraps: bool,
+    offsets: [Option<f32>; 2],
+    from_margins: [bool; 2],
+    axis: usize,
+}
+
+impl AnchoredImageReader {
+    fn read(&mut self, tag: &BodyTag) {
+        if tag.closing {
+            let closes_anchor = tag.name == "wp:anchor"
+                && self
+                    .current
+                    .as_ref()
+                    .is_some_and(|(_, depth)| *depth == tag.stack.len());
+            if closes_anchor {
+                let (mut image, _) = self.current.take().expect("anchor is being read");
+                if !self.wraps {
+                    image.position = self.offsets[0].zip(self.offsets[1]);
+                }
+                if let Some(index) = tag.body_paragraphs.checked_sub(1) {
+                    if !image.embed.is_empty() {
+                        self.images.push((index, image, self.from_margins));
+                    }
+                }
+            }
+            return;
+        }
+
+        if let Some((image, _)) = &mut self.current {
+            match tag.name {
+                "wp:extent" => {
+                    let extent = |name| attribute_value(tag.tag, name)?.parse::<f32>().ok();
+                    if let (Some(cx), Some(cy)) = (extent("cx"), extent("cy")) {
+                        let size = ImageSize {
+                            width: emus_to_mm(cx),
+                            height: emus_to_mm(cy),
+                        };
+                        image.size = (size.width > 0.0 && size.height > 0.0).then_some(size);
+                    }
+                }
+                "wp:positionH" | "wp:positionV" => {
+                    self.axis = usize::from(tag.name == "wp:positionV");
+                    // The reference point on the page the offset starts at.
+                    let relative_from = attribute_value(tag.tag, "relativeFrom");
+                    self.offsets[self.axis] =
+                        matches!(relative_from, Some("page" | "margin")).then_some(0.0);
+                    self.from_margins[self.axis] = relative_from == Some("margin");
+                }
+                "wp:posOffset" if !tag.self_closing => {
+                    let offset = tag.text().trim().parse::<f32>().ok();
+                    let axis = self.axis;
+                    self.offsets[axis] = self.offsets[axis]
+                        .zip(offset)
+                        .map(|(base, offset)| base + emus_to_mm(offset));
+                }
+                "wp:align" => self.offsets[self.axis] = None,
+                "wp:wrapSquare" | "wp:wrapTight" | "wp:wrapThrough" | "wp:wrapTopAndBottom" => {
+                    self.wraps = true;
+                }
+                "a:blip" => {
+                    if let Some(embed) = attribute_value(tag.tag, "r:embed") {
+                        image.embed = embed.to_string();
+                    }
+                }
+                _ => {}
+            }
+        } else if tag.name == "wp:anchor"
+            && tag.stack.get(1) == Some(&"w:p")
+            && !tag.stack.contains(&"mc:Fallback")
+            && !tag.stack.contains(&"w:txbxContent")
+        {
+            let image = AnchoredImage {
+                embed: String::new(),
+                size: None,
+                position: None,
+            };
+            self.current = Some((image, tag.stack.len()));
+            self.wraps = false;
+            self.offsets = [None, None];
+            self.from_margins = [false, false];
+        }
+    }
+
+    // The pictures by paragraph index, placed from the page.
+    fn by_paragraph(self, sections: &SectionReader) -> HashMap<usize, Vec<AnchoredImage>> {
+        let mut images: HashMap<usize, Vec<AnchoredImage>> = HashMap::new();
+        for (index, mut image, from_margins) in self.images {
+            let margins = sections.margins(index);
+            if let Some((x, y)) = &mut image.position {
+                for ((offset, from_margin), margin) in
+                    [x, y].into_iter().zip(from_margins).zip(margins)
+                {
+                    if from_margin {
+                        *offset += margin;
+                    }
+                }
+            }
+            images.entry(index).or_default().push(image);
+        }
+        images
+    }
+}
+
+// An Office Math equation of a body paragraph, as linear text.
+pub struct Equation {
+    // Number of the paragraph's runs, its own or in hyperlinks and tracked
+    // changes, before it.
+    pub runs_before: usize,
+    pub text: String,
+}
+
+// docx-rust does not read Office Math (m:oMath), so equations are read from
+// document.xml directly, for each top-level body paragraph.
+#[derive(Default)]
+struct EquationReader {
+    equations: HashMap<usize, Vec<Equation>>,
+    // Runs of the paragraph read so far.
+    runs: usize,
+}
+
+impl EquationReader {
+    fn read(&mut self, tag: &BodyTag) {
+        if tag.closing {
+            return;
+        }
+        let stack = tag.stack;
+        let in_paragraph = stack.get(1) == Some(&"w:p");
+        match tag.name {
+            "w:p" if stack.len() == 1 => self.runs = 0,
+            "w:r"
+                if in_paragraph
+                    && matches!(stack[2..], [] | ["w:hyperlink" | "w:ins" | "w:del"]) =>
+            {
+                self.runs += 1
+            }
+            "m:oMath" if in_paragraph && stack[2..].iter().all(|&name| name == "m:oMathPara") => {
+                let Some(close) = tag.rest.find("</m:oMath>") else {
+                    return;
+                };
+                let text = linear_text(&tag.rest[..close]);
+                if !text.is_empty() {
+                    self.equations
+                        .entry(tag.body_paragraphs - 1)
+                        .or_default()
+                        .push(Equation {
+                            runs_before: self.runs,
+                            text,
+                        });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// What docx-rust does not read of a table.
+#[derive(Debug, Default)]
+pub struct TableMarkup {
+    // How many of the first rows are header rows (w:tblHeader).
+    pub header_rows: usize,
+    // The cells of each row.
+    pub cells: Vec<Vec<CellMarkup>>,
+}
+
+// What docx-rust does not read of a table cell.
+#[derive(Debug, Clone)]
+pub struct CellMarkup {
+    // Grid columns spanned by the cell (w:gridSpan).
+    pub span: usize,
+    // Background fill (w:shd in w:tcPr).
+    pub shading: Option<RgbColor>,
+    pub vertical_align: CellVerticalAlign,
+    // Text of a cell wrapped in a content control (w:sdt in w:tr), which
+    // docx-rust leaves out of the row.
+    pub content_control_text: Option<String>,
+}
+
+impl Default for CellMarkup {
+    fn default() -> Self {
+        CellMarkup {
+            span: 1,
+            shading: None,
+            vertical_align: CellVerticalAlign::Top,
+            content_control_text: None,
+        }
+    }
+}
+
+// Reads the markup of each body table. Tables in text boxes are left out, as
+// docx-rust does not read them either.
+#[derive(Default)]
+struct TableReader {
+    markup: Vec<TableMarkup>,
+    // Index in `markup` and number of rows read of each open table.
+    tables: Vec<(usize, usize)>,
+    text_box_depth: usize,
+    // Paragraphs and runs have their own w:shd.
+    in_cell_properties: bool,
+    // Depth of the content control cell being read.
+    control_cell: Option<usize>,
+}
+
+impl TableReader {
+    fn read(&mut self, tag: &BodyTag) {
+        let (name, closing, stack) = (tag.name, tag.closing, tag.stack);
+
+        // Only the text of a content control cell is kept, nested tables
+        // included, besides its own properties.
+        if let Some(depth) = self.control_cell {
+            let own_properties = matches!(&stack[depth..], ["w:tc"] | ["w:tc", "w:tcPr"])
+                && matches!(name, "w:tcPr" | "w:gridSpan" | "w:vAlign" | "w:shd");
+            let text = match (name, closing) {
+                ("w:tc", true) if stack.len() == depth => {
+                    self.control_cell = None;
+                    None
+                }
+                ("w:t", false) if !tag.self_closing => Some(unescape(tag.text())),
+                ("w:tab", false) => Some("\t".to_string()),
+                ("w:br" | "w:cr", false) => Some("\n".to_string()),
+                _ => None,
+            };
+            let cell_text = self
+                .current_cell()
+                .and_then(|cell| cell.content_control_text.as_mut());
+            if let (Some(cell_text), Some(text)) = (cell_text, text) {
+                cell_text.push_str(&text);
+            }
+            if !own_properties {
+                return;
+            }
+        }
+
+        match (name, closing) {
+            ("w:txbxContent", false) if !tag.self_closing => self.text_box_depth += 1,
+            ("w:txbxContent", true) => self.text_box_depth -= 1,
+            _ if self.text_box_depth > 0 => {}
+            ("w:tbl", false) if !tag.self_closing => {
+                self.tables.push((self.markup.len(), 0));
+                self.markup.push(TableMarkup::default());
+            }
+            ("w:tbl", true) => {
+                self.tables.pop();
+            }
+            ("w:tr", false) => {
+                if let Some((index, rows)) = self.tables.last_mut() {
+                    *rows += 1;
+                    self.markup[*index].cells.push(Vec::new());
+                }
+            }
+            ("w:tc", false) if !tag.self_closing => {
+                let in_content_control = stack.ends_with(&["w:tr", "w:sdt", "w:sdtContent"]);
+                if in_content_control {
+                    self.control_cell = Some(stack.len());
+                }
+                if let Some(&(index, _)) = self.tables.last() {
+                    if let Some(row) = self.markup[index].cells.last_mut() {
+                        row.push(CellMarkup {
+                            content_control_text: in_content_control.then(String::new),
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+            ("w:tcPr", false) => self.in_cell_properties = !tag.self_closing,
+            ("w:tcPr", true) => self.in_cell_properties = false,
+            ("w:gridSpan", false) => {
+                let span = attribute_value(tag.tag, "w:val").and_then(|value| value.parse().ok());
+                if let (Some(cell), Some(span)) = (self.current_cell(), span) {
+                    cell.span = usize::max(span, 1);
+                }
+            }
+            // "both" is only meaningful for vertical text, set as center.
+            ("w:vAlign", false) if self.in_cell_properties => {
+                let vertical_align = match attribute_value(tag.tag, "w:val") {
+                    Some("center" | "both") => CellVerticalAlign::Center,
+                    Some("bottom") => CellVerticalAlign::Bottom,
+                    _ => CellVerticalAlign::Top,
+                };
+                if let Some(cell) = self.current_cell() {
+                    cell.vertical_align = vertical_align;
+                }
+            }
+            // "auto" and other values that are not colors leave no fill.
+            ("w:shd", false) if self.in_cell_properties => {
+                let fill = attribute_value(tag.tag, "w:fill").and_then(RgbColor::from_hex);
+                if let Some(cell) = self.current_cell() {
+                    cell.shading = fill;
+                }
+            }
+            ("w:tblHeader", false) => {
+                let on = attribute_value(tag.tag, "w:val")
+                    .is_none_or(|value| !matches!(value, "0" | "false" | "off"));
+                // Only the rows at the top of the table repeat.
+                if let Some(&(index, rows)) = self.tables.last() {
+                    if on && self.markup[index].header_rows + 1 == rows {
+                        self.markup[index].header_rows = rows;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn current_cell(&mut self) -> Option<&mut CellMarkup> {
+        let &(index, _) = self.tables.last()?;
+        self.markup[index].cells.last_mut()?.last_mut()
+    }
+}
+
+// docx-rust does not read comments.xml. Comments are anchored in the body
+// paragraph or table where the commented range starts, or else the one
+// holding the comment reference.
+struct CommentReader<'c> {
+    comments: HashMap<&'c str, Comment>,
+    anchored: HashMap<usize, Vec<Comment>>,
+}
+
+impl CommentReader<'_> {
+    fn read(&mut self, tag: &BodyTag) {
+        if tag.closing || !matches!(tag.name, "w:commentRangeStart" | "w:commentReference") {
+            return;
+        }
+        let comment = attribute_value(tag.tag, "w:id").and_then(|id| self.comments.remove(id));
+        if let (Some(comment), Some(index)) = (comment, tag.body_items.checked_sub(1)) {
+            self.anchored.entry(index).or_default().push(comment);
+        }
+    }
+}
+
+// The comments of comments.xml by id. The paragraphs of a comment are kept as
+// lines of its text.
+fn comments_by_id(xml: &str) -> HashMap<&str, Comment> {
+    let mut comments = HashMap::new();
+    let mut current: Option<(&str, Comment)> = None;
+    let mut position = 0;
+    while let Some((tag, end)) = next_tag(xml, position) {
+        position = end;
+        match tag_name(tag.trim_start_matches('/')) {
+            "w:comment" if tag.starts_with('/') => {
+                if let Some((id, mut comment)) = current.take() {
+                    comment.text = comment.text.trim_end().to_string();
+                    comments.insert(id, comment);
+                }
+            }
+            "w:comment" => {
+                let Some(id) = attribute_value(tag, "w:id") else {
+                    continue;
+                };
+                let comment = Comment {
+                    author: attribute_value(tag, "w:author").map(unescape),
+                    text: String::new(),
+                };
+                current = Some((id, comment));
+            }
+            "w:p" if tag.starts_with('/') => {
+                if let Some((_, comment)) = &mut current {
+                    comment.text.push('\n');
+                }
+            }
+            "w:t" if !tag.starts_with('/') && !tag.ends_with('/') => {
+                if let Some((_, comment)) = &mut current {
+                    let text_end = xml[position..]
+                        .find('<')
+                        .map_or(xml.len(), |end| position + end);
+                    comment.text.push_str(&unescape(&xml[position..text_end]));
+                }
+            }
+            "w:tab" => {
+                if let Some((_, comment)) = &mut current {
+                    comment.text.push('\t');
+                }
+            }
+            _ => {}
+        }
+    }
+    comments
+}
+
+// The next tag at or after `position`, without its angle brackets, and the
+// position just past it. Declarations and comments are skipped.
+pub(crate) fn next_tag(xml: &str, mut position: usize) -> Option<(&str, usize)> {
+    loop {
+        let start = position + xml[position..].find('<')?;
+        let end = start + xml[start..].find('>')?;
+        let tag = &xml[start + 1..end];
+        position = end + 1;
+        if !(tag.starts_with('?') || tag.starts_with('!')) {
+            return Some((tag, position));
+        }
+    }
+}
+
+pub(crate) fn tag_name(tag: &str) -> &str {
+    tag.split(|ch: char| ch.is_whitespace() || ch == '/')
+        .next()
+        .unwrap_or_default()
+}
+
+// Value of the first `name="..."` attribute in `xml`.
+pub(crate) fn attribute_value<'x>(xml: &'x str, name: &str) -> Option<&'x str> {
+    let start = xml.find(&format!("{}=\"", name))? + name.len() + 2;
+    let end = start + xml[start..].find('"')?;
+    Some(&xml[start..end])
+}
+
+// Text of the first `<name>` element, unescaped and trimmed. Empty elements
+// yield None.
+fn element_text(xml: &str, name: &str) -> Option<String> {
+    let open = format!("<{}", name);
+    let mut start = 0;
+    let content_start = loop {
+        let tag_start = start + xml[start..].find(&open)?;
+        let tag_end = tag_start + xml[tag_start..].find('>')?;
+        let next = xml[tag_start + open.len()..].chars().next()?;
+        if next == '>' || next.is_whitespace() {
+            if xml[..tag_end].ends_with('/') {
+                return None;
+            }
+            break tag_end + 1;
+        }
+        start = tag_end;
+    };
+    let content_end = content_start + xml[content_start..].find(&format!("</{}>", name))?;
+
+    let text = unescape(&xml[content_start..content_end]);
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+pub(crate) fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn needs_rewrite<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<bool> {
+    let content_types = read_text_part(zip, "[Content_Types].xml")?.unwrap_or_default();
+    let root_rels = read_text_part(zip, "_rels/.rels")?.unwrap_or_default();
+    Ok(content_types.contains(MACRO_ENABLED_MAIN) || root_rels.contains(STRICT_NAMESPACE_PREFIX))
+}
+
+fn read_text_part<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str) -> Result<Option<String>> {
+    let mut part = match zip.by_name(name) {
+        Ok(part) => part,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut text = String::new();
+    part.read_to_string(&mut text)
+        .map_err(|e| ConversionError::io(name, e))?;
+    Ok(Some(text))
+}
+
+fn rewrite_package<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+    for index in 0..zip.len() {
+        let mut part = zip.by_index(index)?;
+        let name = part.name().to_string();
+        if !(name.ends_with(".xml") || name.ends_with(".rels")) {
+            writer.raw_copy_file(part)?;
+            continue;
+        }
+
+        let mut text = String::new();
+        part.read_to_string(&mut text)
+            .map_err(|e| ConversionError::io(&name, e))?;
+        let mut text = text.replace(MACRO_ENABLED_MAIN, DOCUMENT_MAIN);
+        for (strict, transitional) in STRICT_NAMESPACES {
+            text = text.replace(strict, transitional);
+        }
+
+        writer.start_file(name.as_str(), SimpleFileOptions::default())?;
+        writer
+            .write_all(text.as_bytes())
+            .map_err(|e| ConversionError::io(&name, e))?;
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
@@ -1,248 +1,2825 @@
-use anyhow::{Context, Result};
-use log::{debug, info};
+use log::{debug, info, warn};
+use printpdf::image_crate::codecs::bmp::BmpDecoder as PrintPdfBmpDecoder;
+use printpdf::image_crate::codecs::gif::GifDecoder as PrintPdfGifDecoder;
 use printpdf::image_crate::codecs::jpeg::JpegDecoder as PrintPdfJpegDecoder;
 use printpdf::image_crate::codecs::png::PngDecoder as PrintPdfPngDecoder;
+use printpdf::image_crate::codecs::tiff::TiffDecoder as PrintPdfTiffDecoder;
 use printpdf::image_crate::{guess_format, ImageFormat};
 use printpdf::*;
+use rayon::prelude::*;
+use std::collections::HashMap;
 use std::io::Cursor;
+use std::str::FromStr;
 use std::{fs::File, io::BufWriter};
 
-use crate::utils::{estimate_text_width, DocContent};
-use crate::{FONT_SIZE, LINE_HEIGHT, MARGIN, PAGE_HEIGHT, PAGE_WIDTH, PARAGRAPH_SPACING};
+use crate::downsample::downsample;
+use crate::error::{ConversionError, Result};
+use crate::jpeg::corrected_jpeg;
+use crate::layout::{split_words, wrap_words, TextLine};
+use crate::numbering::{letter, lower_roman};
+use crate::pdfa::make_pdfa;
+use crate::postprocess::{
+    add_page_labels, add_text_annotations, deduplicate_images, embed_file, optimize, TextAnnotation,
+};
+use crate::renderer::{FontFace, PdfRenderer, RasterImage, Renderer};
+#[cfg(feature = "svg")]
+use crate::svg::rasterize_svg;
+use crate::utils::{
+    estimate_text_width, is_rtl_char, Alignment, Border, CellContent, CellVerticalAlign, Columns,
+    Comment, DocContent, DocumentMetadata, DocxDocument, FontFamily, FontMap, ImageContent,
+    ImageSize, Note, PageField, PageSize, Paragraph, RgbColor, Section, Strike, TableContent,
+    TextRun, VerticalAlign,
+};
+use crate::{
+    CELL_PADDING, FONT_SIZE, HEADING_SPACING, IMAGE_DPI, LINE_HEIGHT, LIST_INDENT, MARGIN,
+    PARAGRAPH_SPACING,
+};
 
-pub fn convert_paragraphs_to_pdf(content: Vec<DocContent>, pdf_path: &str) -> Result<()> {
+const DOCX_MIME_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+const PLACEHOLDER_WIDTH: f32 = 60.0;
+const PLACEHOLDER_HEIGHT: f32 = 40.0;
+const UNDERLINE_OFFSET: f32 = 1.0;
+// Baseline shifts of superscript and subscript text, relative to its font size.
+const SUPERSCRIPT_RISE: f32 = 0.5;
+const SUBSCRIPT_DROP: f32 = 0.2;
+// Strikethrough lines sit around the middle of lowercase letters.
+const STRIKE_HEIGHT: f32 = 0.3;
+const DOUBLE_STRIKE_GAP: f32 = 0.08;
+// Extent of the highlight rectangle below and above the baseline, relative to
+// the font size.
+const HIGHLIGHT_DESCENT: f32 = 0.25;
+const HIGHLIGHT_ASCENT: f32 = 0.9;
+// Side of the checkboxes drawn for ☐, ☑ and ☒, relative to the font size.
+const CHECKBOX_SIZE: f32 = 0.7;
+// Height of capital letters above the baseline, relative to the font size.
+const CELL_TEXT_ASCENT: f32 = 0.75;
+const CELL_IMAGE_GAP: f32 = 1.0;
+// Room between an inline image and the line above it.
+const INLINE_IMAGE_GAP: f32 = 1.5;
+// Width of the lines drawn other than table borders, in points.
+const LINE_WIDTH: f32 = 1.0;
+// Comment icons, in the right margin.
+const COMMENT_ICON_SIZE: f32 = 6.0;
+const COMMENT_ICON_GAP: f32 = 2.0;
+const LINK_COLOR: RgbColor = RgbColor {
+    r: 0.0,
+    g: 0.0,
+    b: 0.8,
+};
+// Footnotes and endnotes are set smaller than the body text.
+const NOTE_SCALE: f32 = 0.8;
+// Room above the notes at the bottom of a page for the separator line.
+const NOTE_SEPARATOR_SPACE: f32 = 4.0;
+const NOTE_SEPARATOR_WIDTH: f32 = 50.0;
+// The watermark is set at most this size in points.
+const WATERMARK_MAX_FONT_SIZE: f32 = 120.0;
+// Share of the page diagonal taken by the watermark text.
+const WATERMARK_LENGTH: f32 = 0.7;
+// Sizes of the title and of the author and date on the cover page, relative
+// to the body font size.
+const COVER_TITLE_SCALE: f32 = 2.4;
+const COVER_TEXT_SCALE: f32 = 1.3;
+// Smallest body font size --fit-page shrinks text to, in points.
+const MIN_FIT_FONT_SIZE: f32 = 6.0;
+const FIT_PAGE_PASSES: usize = 5;
+// Extra shrink per pass, so the scale converges despite the fixed spacings.
+const FIT_PAGE_STEP: f32 = 0.98;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnsupportedImageMode {
+    #[default]
+    Skip,
+    Placeholder,
+    Fail,
+}
+
+impl FromStr for UnsupportedImageMode {
+    type Err = ConversionError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "skip" => Ok(UnsupportedImageMode::Skip),
+            "placeholder" => Ok(UnsupportedImageMode::Placeholder),
+            "fail" => Ok(UnsupportedImageMode::Fail),
+            _ => Err(ConversionError::InvalidOption(format!(
+                "Invalid unsupported image mode: {} (expected skip, placeholder or fail)",
+                value
+            ))),
+        }
+    }
+}
+
+// How tracked changes are rendered: as if accepted, so that insertions
+// (w:ins) stay and deletions (w:del) go, as if rejected, or both shown the way
+// Word marks them, insertions underlined and deletions struck through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrackChangesMode {
+    #[default]
+    Accept,
+    Reject,
+    Show,
+}
+
+impl FromStr for TrackChangesMode {
+    type Err = ConversionError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "accept" => Ok(TrackChangesMode::Accept),
+            "reject" => Ok(TrackChangesMode::Reject),
+            "show" => Ok(TrackChangesMode::Show),
+            _ => Err(ConversionError::InvalidOption(format!(
+                "Invalid track changes mode: {} (expected accept, reject or show)",
+                value
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageLabelStyle {
+    Arabic,
+    LowerRoman,
+    UpperRoman,
+    LowerLetters,
+    UpperLetters,
+}
+
+impl PageLabelStyle {
+    fn format(self, number: usize) -> String {
+        match self {
+            PageLabelStyle::Arabic => number.to_string(),
+            PageLabelStyle::LowerRoman => lower_roman(number),
+            PageLabelStyle::UpperRoman => lower_roman(number).to_uppercase(),
+            PageLabelStyle::LowerLetters => letter(number),
+            PageLabelStyle::UpperLetters => letter(number).to_uppercase(),
+        }
+    }
+
+    // Name of the style in a PDF page label dictionary.
+    fn pdf_name(self) -> &'static str {
+        match self {
+            PageLabelStyle::Arabic => "D",
+            PageLabelStyle::LowerRoman => "r",
+            PageLabelStyle::UpperRoman => "R",
+            PageLabelStyle::LowerLetters => "a",
+            PageLabelStyle::UpperLetters => "A",
+        }
+    }
+}
+
+// Pages labeled from `first_page`, counted from 1, up to the next range,
+// numbered from 1 in `style`: "1:roman" for front matter labeled i, ii, iii,
+// then "5:arabic" for a body labeled 1, 2, 3 from the fifth page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageLabelRange {
+    pub first_page: usize,
+    pub style: PageLabelStyle,
+}
+
+impl FromStr for PageLabelRange {
+    type Err = ConversionError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || {
+            ConversionError::InvalidOption(format!(
+                "Invalid page label range: {} (expected PAGE:STYLE, where STYLE is arabic, \
+                 roman, upper-roman, letters or upper-letters)",
+                value
+            ))
+        };
+        let (page, style) = value.split_once(':').ok_or_else(invalid)?;
+        let first_page = page
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|&page| page > 0)
+            .ok_or_else(invalid)?;
+        let style = match style.trim() {
+            "arabic" => PageLabelStyle::Arabic,
+            "roman" => PageLabelStyle::LowerRoman,
+            "upper-roman" => PageLabelStyle::UpperRoman,
+            "letters" => PageLabelStyle::LowerLetters,
+            "upper-letters" => PageLabelStyle::UpperLetters,
+            _ => return Err(invalid()),
+        };
+        Ok(PageLabelRange { first_page, style })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TypographyConfig {
+    pub font_family: FontFamily,
+    pub font_size: f32,
+    pub line_height: f32,
+    pub paragraph_spacing: f32,
+    // Space between the borders of a table cell and its content, in mm.
+    pub cell_padding: f32,
+}
+
+impl Default for TypographyConfig {
+    fn default() -> Self {
+        TypographyConfig {
+            font_family: FontFamily::default(),
+            font_size: FONT_SIZE,
+            line_height: LINE_HEIGHT,
+            paragraph_spacing: PARAGRAPH_SPACING,
+            cell_padding: CELL_PADDING,
+        }
+    }
+}
+
+impl TypographyConfig {
+    // Keeps the default line height and cell padding to font size ratios for
+    // the new size.
+    pub fn with_font_size(font_size: f32) -> Self {
+        TypographyConfig {
+            font_size,
+            line_height: LINE_HEIGHT * font_size / FONT_SIZE,
+            cell_padding: CELL_PADDING * font_size / FONT_SIZE,
+            ..Default::default()
+        }
+    }
+
+    fn scaled(&self, factor: f32) -> Self {
+        TypographyConfig {
+            font_size: self.font_size * factor,
+            line_height: self.line_height * factor,
+            paragraph_spacing: self.paragraph_spacing * factor,
+            cell_padding: self.cell_padding * factor,
+            ..*self
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PdfOptions {
+    pub on_unsupported_image: UnsupportedImageMode,
+    pub track_changes: TrackChangesMode,
+    pub preserve_spaces: bool,
+    // Resolution used to turn image pixels into a physical size.
+    pub image_dpi: f32,
+    // Images wider or taller than this many pixels are downsampled to fit,
+    // which keeps camera photos from bloating the PDF.
+    pub max_image_dimension: Option<u32>,
+    // Renders footnotes after the body, with the endnotes, instead of at the
+    // bottom of the page that references them.
+    pub footnotes_at_end: bool,
+    // Fails on broken references, such as an image whose relationship is
+    // missing, instead of skipping them with a warning.
+    pub strict: bool,
+    // Moves paragraph breaks that would leave a single line of a paragraph
+    // at the bottom or top of a page.
+    pub widow_control: bool,
+    // Opens password protected documents.
+    pub password: Option<String>,
+    // Attaches the DOCX file to the PDF.
+    pub embed_source: bool,
+    // Adds a first page with the title, author and date of the document.
+    pub cover_page: bool,
+    // Built-in fonts that stand in for fonts used by the document.
+    pub font_map: FontMap,
+    // Shrinks the content so that it all fits on a single page.
+    pub fit_page: bool,
+    // Text drawn diagonally across every page, such as "DRAFT".
+    pub watermark: Option<String>,
+    // Renders each table row as a paragraph of labeled cells instead of a
+    // grid.
+    pub tables_as_text: bool,
+    // Numbers the images and tables of the body, adding a "Figure N" or
+    // "Table N" caption to those that have none.
+    pub auto_caption: bool,
+    // Compresses the page contents and every other uncompressed stream.
+    pub optimize: bool,
+    // Writes a PDF/A-2b file, for archiving. PDF/A does not allow the
+    // built-in fonts, so `font` must be set.
+    pub pdfa: bool,
+    // TrueType fonts embedded in the PDF and used for all the text instead of
+    // the built-in fonts. Bold text uses the regular font when no bold one is
+    // given.
+    pub font: Option<Vec<u8>>,
+    pub bold_font: Option<Vec<u8>>,
+    // Bounds the work done on a single document.
+    pub limits: ConversionLimits,
+    // Labels shown for the pages by PDF viewers, and by PAGE fields in the
+    // header and footer, by range in page order. Pages are numbered in arabic
+    // when none is given.
+    pub page_labels: Vec<PageLabelRange>,
+    // Paper used for every section instead of the size given by the
+    // document. Landscape sections stay landscape.
+    pub page_size: Option<PageSize>,
+}
+
+// Limits that stop the conversion of huge or pathological documents with
+// `ConversionError::LimitExceeded`, such as untrusted uploads. None of them
+// is set by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConversionLimits {
+    pub max_pages: Option<usize>,
+    // Content items of the body, where each table cell counts as one.
+    pub max_content_items: Option<usize>,
+    // Total size of the images of the body, as stored in the DOCX.
+    pub max_image_bytes: Option<usize>,
+}
+
+impl ConversionLimits {
+    fn check(limit: &'static str, value: usize, max: Option<usize>) -> Result<()> {
+        match max {
+            Some(max) if value > max => Err(ConversionError::LimitExceeded { limit, max }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            on_unsupported_image: UnsupportedImageMode::default(),
+            track_changes: TrackChangesMode::default(),
+            preserve_spaces: false,
+            image_dpi: IMAGE_DPI,
+            max_image_dimension: None,
+            footnotes_at_end: false,
+            strict: false,
+            widow_control: false,
+            password: None,
+            embed_source: false,
+            cover_page: false,
+            font_map: FontMap::default(),
+            fit_page: false,
+            watermark: None,
+            tables_as_text: false,
+            auto_caption: false,
+            optimize: false,
+            pdfa: false,
+            font: None,
+            bold_font: None,
+            limits: ConversionLimits::default(),
+            page_labels: Vec::new(),
+            page_size: None,
+        }
+    }
+}
+
+pub fn convert_paragraphs_to_pdf(
+    document: DocxDocument,
+    pdf_path: &str,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<()> {
+    let mut pdf = build_pdf(document, typography, options, progress)?;
+
+    debug!("Saving PDF to {}", pdf_path);
+    let file = File::create(pdf_path).map_err(|e| ConversionError::io(pdf_path, e))?;
+    pdf.save_to(&mut BufWriter::new(file))
+        .map_err(|e| ConversionError::io(pdf_path, e))?;
+
+    let pdf_size = std::fs::metadata(pdf_path)
+        .map_err(|e| ConversionError::io(pdf_path, e))?
+        .len();
+    info!("PDF saved successfully. File size: {} bytes", pdf_size);
+
+    Ok(())
+}
+
+// Same as convert_paragraphs_to_pdf, returning the PDF instead of writing it
+// to a file.
+pub fn convert_paragraphs_to_bytes(
+    document: DocxDocument,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<Vec<u8>> {
+    let mut pdf = build_pdf(document, typography, options, progress)?;
+    let mut bytes = Vec::new();
+    pdf.save_to(&mut bytes)
+        .map_err(|e| ConversionError::io("PDF", e))?;
+    info!("PDF generated successfully. Size: {} bytes", bytes.len());
+    Ok(bytes)
+}
+
+// Lays the document out on any renderer. --fit-page is not applied, as it
+// lays the document out several times.
+pub fn render_paragraphs<R: Renderer>(
+    mut document: DocxDocument,
+    renderer: &mut R,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<()> {
+    check_page_labels(&options.page_labels)?;
+    prepare_content(&mut document, options);
+    let images = decode_body(&document, options)?;
+    let fit = PageFit::default();
+    render(
+        &document, renderer, typography, options, fit, &images, progress,
+    )?;
+    Ok(())
+}
+
+fn build_pdf(
+    mut document: DocxDocument,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<lopdf::Document> {
     debug!("Starting PDF conversion");
-    let (doc, page1, layer1) = PdfDocument::new(
-        "Converted Document",
-        Mm(PAGE_WIDTH),
-        Mm(PAGE_HEIGHT),
-        "Layer 1",
+    if options.pdfa && options.font.is_none() {
+        return Err(ConversionError::InvalidOption(
+            "PDF/A output needs a TrueType font to embed".to_string(),
+        ));
+    }
+    // PDF/A-2 only allows attaching PDF/A files.
+    if options.pdfa && options.embed_source {
+        return Err(ConversionError::InvalidOption(
+            "PDF/A output cannot embed the source DOCX".to_string(),
+        ));
+    }
+    if options.fit_page && options.cover_page {
+        return Err(ConversionError::InvalidOption(
+            "A cover page cannot be added when fitting the content on one page".to_string(),
+        ));
+    }
+    check_page_labels(&options.page_labels)?;
+    prepare_content(&mut document, options);
+    let images = decode_body(&document, options)?;
+    let families = font_families(&document, typography.font_family);
+    let new_renderer =
+        || PdfRenderer::new(&document.metadata, &families, document.page_size, options);
+    // An empty body gives a single blank page, with the header and footer if
+    // any: there is nothing to fit or paginate.
+    let (renderer, rendering) = if options.fit_page && !document.content.is_empty() {
+        render_fit_page(
+            &document,
+            typography,
+            options,
+            &images,
+            new_renderer,
+            progress,
+        )?
+    } else {
+        if document.content.is_empty() {
+            info!("Document body is empty; writing a single blank page");
+        }
+        let mut renderer = new_renderer()?;
+        let fit = PageFit::default();
+        let rendering = render(
+            &document,
+            &mut renderer,
+            typography,
+            options,
+            fit,
+            &images,
+            progress,
+        )?;
+        (renderer, rendering)
+    };
+
+    let mut pdf = renderer.into_pdf()?;
+    if !rendering.comments.is_empty() {
+        debug!("Adding {} comment annotations", rendering.comments.len());
+        add_text_annotations(&mut pdf, &rendering.comments)?;
+    }
+    if !options.page_labels.is_empty() {
+        let ranges: Vec<(usize, &str)> = options
+            .page_labels
+            .iter()
+            .map(|range| (range.first_page - 1, range.style.pdf_name()))
+            .collect();
+        add_page_labels(&mut pdf, &ranges)?;
+    }
+    let merged_images = deduplicate_images(&mut pdf);
+    if merged_images > 0 {
+        debug!("Merged {} duplicate images", merged_images);
+    }
+    if let Some(source) = &document.source {
+        debug!("Attaching {} ({} bytes)", source.name, source.bytes.len());
+        embed_file(&mut pdf, &source.name, DOCX_MIME_TYPE, &source.bytes)?;
+    }
+    if options.pdfa {
+        make_pdfa(&mut pdf)?;
+    }
+    if options.optimize {
+        let saved = optimize(&mut pdf);
+        debug!("Compression saved {} bytes", saved);
+    }
+    Ok(pdf)
+}
+
+// Page label ranges are written to the PDF, and looked up for PAGE fields, in
+// the order given, which must be the page order.
+fn check_page_labels(ranges: &[PageLabelRange]) -> Result<()> {
+    let ordered = ranges
+        .windows(2)
+        .all(|pair| pair[0].first_page < pair[1].first_page);
+    if !ordered {
+        return Err(ConversionError::InvalidOption(
+            "Page label ranges must be given in page order".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Shrinks the content until it fits on a single page. Margins, indents and a
+// few spacings keep their size, so the scale is refined over several passes,
+// the last one at the minimum legible font size. Below that the document is
+// paginated instead. Each pass draws on a new renderer, and progress is only
+// reported once the content fits or is paginated.
+fn render_fit_page<R: Renderer>(
+    document: &DocxDocument,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    images: &DecodedImages,
+    new_renderer: impl Fn() -> Result<R>,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<(R, Rendering)> {
+    let min_scale = (MIN_FIT_FONT_SIZE / typography.font_size).min(1.0);
+    let mut scale = 1.0;
+    for pass in 0..=FIT_PAGE_PASSES {
+        // The content gets one last try at the smallest scale.
+        if pass == FIT_PAGE_PASSES {
+            scale = min_scale;
+        }
+        let fit = PageFit {
+            scale,
+            single_page: true,
+        };
+        let mut renderer = new_renderer()?;
+        let rendering = render(
+            document,
+            &mut renderer,
+            typography,
+            options,
+            fit,
+            images,
+            &mut |_, _| {},
+        )?;
+        if rendering.overflow <= 0.0 {
+            debug!("Content fits on one page at scale {}", scale);
+            progress(document.content.len(), document.content.len());
+            return Ok((renderer, rendering));
+        }
+        if scale <= min_scale {
+            break;
+        }
+        let needed = rendering.available + rendering.overflow;
+        scale = (scale * rendering.available / needed * FIT_PAGE_STEP).max(min_scale);
+    }
+
+    warn!(
+        "Content does not fit on one page with text of at least {}pt; paginating instead",
+        MIN_FIT_FONT_SIZE
     );
-    let mut current_layer = doc.get_page(page1).get_layer(layer1);
+    let fit = PageFit {
+        scale: min_scale,
+        single_page: false,
+    };
+    let mut renderer = new_renderer()?;
+    let rendering = render(
+        document,
+        &mut renderer,
+        typography,
+        options,
+        fit,
+        images,
+        progress,
+    )?;
+    Ok((renderer, rendering))
+}
+
+// Checks the content item and image limits of the body, and decodes its
+// images once for every layout pass.
+fn decode_body(document: &DocxDocument, options: &PdfOptions) -> Result<DecodedImages> {
+    let item_count: usize = document
+        .content
+        .iter()
+        .map(|item| match item {
+            DocContent::Table { table, .. } => 1 + table.cell_count(),
+            _ => 1,
+        })
+        .sum();
+    ConversionLimits::check(
+        "content items",
+        item_count,
+        options.limits.max_content_items,
+    )?;
+    DecodedImages::decode(&document.content, options)
+}
+
+// Rewrites the body as asked by the options, before it is laid out.
+fn prepare_content(document: &mut DocxDocument, options: &PdfOptions) {
+    if let Some(paper) = options.page_size {
+        let resize = |size: PageSize| paper.oriented(size.width > size.height);
+        document.page_size = resize(document.page_size);
+        for item in &mut document.content {
+            if let DocContent::SectionBreak(section) = item {
+                section.page_size = resize(section.page_size);
+            }
+        }
+    }
+    if options.tables_as_text {
+        document.content = tables_as_text(std::mem::take(&mut document.content));
+    }
+    if options.auto_caption {
+        document.content = auto_captions(std::mem::take(&mut document.content));
+    }
+}
+
+// Replaces every table with one paragraph per row, such as
+// "Column 1: x, Column 2: y". Images and nested tables of a row follow its
+// paragraph.
+fn tables_as_text(content: Vec<DocContent>) -> Vec<DocContent> {
+    let mut linear = Vec::with_capacity(content.len());
+    for item in content {
+        match item {
+            DocContent::Table { table, .. } => push_table_as_text(table, &mut linear),
+            item => linear.push(item),
+        }
+    }
+    linear
+}
+
+fn push_table_as_text(table: TableContent, linear: &mut Vec<DocContent>) {
+    for row in table.rows {
+        let text = row
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(index, cell)| (index, cell.text()))
+            .filter(|(_, text)| !text.trim().is_empty())
+            .map(|(index, text)| format!("Column {}: {}", index + 1, text.trim()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if !text.is_empty() {
+            linear.push(DocContent::Paragraph(Paragraph {
+                runs: vec![TextRun {
+                    text,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }));
+        }
+        for cell in row.cells {
+            for image in cell.images {
+                linear.push(DocContent::Image {
+                    image,
+                    comments: Vec::new(),
+                });
+            }
+            for nested in cell.tables {
+                push_table_as_text(nested, linear);
+            }
+        }
+    }
+}
+
+// Adds a caption below each image and table of the body, numbered "Figure 1",
+// "Figure 2"... and "Table 1", "Table 2"... in document order. Those already
+// captioned in the document keep their caption but are counted all the same,
+// where a caption between two of them goes with the one before. Floating
+// images are left out, as they are placed apart from the text.
+fn auto_captions(content: Vec<DocContent>) -> Vec<DocContent> {
+    let is_figure = |item: &DocContent| match item {
+        DocContent::Image { image, .. } => image.position.is_none(),
+        DocContent::Table { .. } => true,
+        _ => false,
+    };
+    let is_caption =
+        |item: &DocContent| matches!(item, DocContent::Paragraph(paragraph) if paragraph.caption);
+    let mut captioned = Vec::with_capacity(content.len());
+    let (mut figures, mut tables) = (0, 0);
+    // Whether the last item is a caption that goes with the next figure.
+    let mut leading_caption = false;
+    let mut content = content.into_iter().peekable();
+    while let Some(item) = content.next() {
+        let label = match &item {
+            DocContent::Image { image, .. } if image.position.is_none() => {
+                figures += 1;
+                format!("Figure {}", figures)
+            }
+            DocContent::Table { .. } => {
+                tables += 1;
+                format!("Table {}", tables)
+            }
+            _ => {
+                leading_caption = is_caption(&item) && !captioned.last().is_some_and(is_figure);
+                captioned.push(item);
+                continue;
+            }
+        };
+        let has_caption = leading_caption || content.peek().is_some_and(is_caption);
+        leading_caption = false;
+        captioned.push(item);
+        if !has_caption {
+            captioned.push(DocContent::Paragraph(Paragraph {
+                runs: vec![TextRun {
+                    text: label,
+                    ..Default::default()
+                }],
+                caption: true,
+                ..Default::default()
+            }));
+        }
+    }
+    debug!("Numbered {} figures and {} tables", figures, tables);
+    captioned
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PageFit {
+    // Factor applied to font sizes, spacing and image sizes.
+    scale: f32,
+    // Keeps all content on the first page, past its bottom if need be.
+    single_page: bool,
+}
+
+impl Default for PageFit {
+    fn default() -> Self {
+        PageFit {
+            scale: 1.0,
+            single_page: false,
+        }
+    }
+}
+
+struct Rendering {
+    // Height of the body area of the last page, and how far the content runs
+    // past its bottom.
+    available: f32,
+    overflow: f32,
+    // Review comments, placed next to the content they are anchored in.
+    comments: Vec<TextAnnotation>,
+}
+
+fn render<R: Renderer>(
+    document: &DocxDocument,
+    renderer: &mut R,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    fit: PageFit,
+    images: &DecodedImages,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<Rendering> {
+    let typography = &typography.scaled(fit.scale);
+    let mut decoded_images = images.scaled(fit.scale);
+
+    let fonts = Fonts::new(typography.font_family);
+    let bold_fonts = fonts.all_bold();
+
+    // Every section must leave room for the body, not only the first one.
+    let page_size = document.page_size;
+    let columns = document.columns;
+    let first_section = Section { page_size, columns };
+    let section_breaks = document.content.iter().filter_map(|item| match item {
+        DocContent::SectionBreak(section) => Some(*section),
+        _ => None,
+    });
+    let sections: Vec<Section> = std::iter::once(first_section)
+        .chain(section_breaks)
+        .collect();
+    for section in &sections {
+        check_page_width(section.page_size, section.columns)?;
+    }
+    let max_width = page_size.width - 2.0 * MARGIN;
+    let header = layout_header_footer(&document.header, typography, options, max_width);
+    let footer = layout_header_footer(&document.footer, typography, options, max_width);
+
+    // The body is pushed down (or up) to make room for the header and footer.
+    let content_top = page_size.height - MARGIN - header_footer_height(&header, typography);
+    let content_bottom = MARGIN + header_footer_height(&footer, typography);
+    for section in &sections {
+        let size = section.page_size;
+        let body_height = content_top - content_bottom + size.height - page_size.height;
+        check_page_layout(typography, body_height)?;
+    }
+    let column_width = column_width(columns, page_size.width);
+    let mut cursor = PageCursor {
+        renderer,
+        footnotes: vec![Vec::new()],
+        page_size,
+        page_sizes: vec![page_size],
+        top: content_top,
+        bottom: content_bottom,
+        footnote_height: 0.0,
+        y: content_top,
+        columns,
+        column: 0,
+        column_width,
+        single_page: fit.single_page,
+        max_pages: options.limits.max_pages,
+        pages_exceeded: false,
+    };
 
-    debug!("Adding built-in font");
-    let font = doc.add_builtin_font(BuiltinFont::Helvetica)?;
-    let font_bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+    // The body starts on the page after the cover, if it has anything to
+    // show.
+    let cover_page = options.cover_page
+        && !fit.single_page
+        && draw_cover_page(
+            cursor.renderer,
+            &document.metadata,
+            page_size,
+            typography,
+            &fonts,
+        );
+    if cover_page {
+        cursor.new_page();
+    }
 
-    let mut y_position = PAGE_HEIGHT - MARGIN;
-    let max_width = PAGE_WIDTH - 2.0 * MARGIN;
-    let indent = 2.0;
+    // Notes are set at the width of a column, since those after the body
+    // continue in the last one.
+    let note_line_height = typography.line_height * NOTE_SCALE;
+    let footnotes = layout_notes(&document.footnotes, typography, options, column_width);
+    let endnotes = layout_notes(&document.endnotes, typography, options, column_width);
 
+    let mut comments = Vec::new();
+    let content = &document.content;
     debug!("Processing {} content items", content.len());
     for (index, item) in content.iter().enumerate() {
-        if !item.text.is_empty() {
-            if item.text.starts_with("TABLE_START") {
-                y_position =
-                    process_table_for_pdf(&item.text, &mut current_layer, y_position, &font)?;
-            } else {
-                let lines: Vec<&str> = item.text.split('\n').collect();
-                for (line_index, line) in lines.iter().enumerate() {
-                    let trimmed_line = line.trim();
-                    if trimmed_line.is_empty() {
-                        y_position -= PARAGRAPH_SPACING;
-                        continue;
+        // Where the item starts, and the top of its first line of text if any.
+        let item_start = (cursor.page(), cursor.y);
+        let mut first_line = None;
+
+        // The first of a figure and its caption moves to the next page or
+        // column when the two don't fit together.
+        let figure_height = figure_with_caption_height(
+            content,
+            index,
+            &cursor,
+            typography,
+            options,
+            &decoded_images,
+        );
+        if let Some(height) = figure_height {
+            cursor.ensure_space(height);
+        } else if let Some(height) = kept_height(content, index, &cursor, typography, options) {
+            cursor.ensure_space(height);
+        }
+        match item {
+            DocContent::PageBreak => {
+                // A page that has just been started is not left blank.
+                if cursor.y < cursor.top || cursor.column > 0 {
+                    cursor.new_page();
+                }
+            }
+            DocContent::SectionBreak(section) => cursor.new_section(*section),
+            DocContent::ColumnBreak => {
+                if cursor.y < cursor.top {
+                    cursor.next_column();
+                }
+            }
+            DocContent::TextBox(runs) => {
+                draw_text_box(runs, &mut cursor, typography, options, &fonts)
+            }
+            DocContent::Table { table, .. } => {
+                process_table_for_pdf(
+                    table,
+                    &mut cursor,
+                    typography,
+                    options,
+                    &mut decoded_images,
+                    &fonts,
+                )?;
+            }
+            DocContent::Image { image, .. } => {
+                debug!("Processing image at index {}", index);
+
+                match decoded_images.take(image) {
+                    Some(decoded) if image.position.is_some() => {
+                        // Floating images sit at their offset on the current page,
+                        // without taking room from the flow of text.
+                        let (x, y) = image.position.unwrap_or_default();
+                        let page = cursor.page_size;
+                        let placement = place_image(
+                            image,
+                            &decoded,
+                            options.image_dpi,
+                            decoded_images.scale,
+                            page.width - x,
+                            page.height - y,
+                        );
+                        cursor.renderer.draw_image(
+                            decoded.image,
+                            (x, page.height - y - placement.height),
+                            (placement.width, placement.height),
+                        );
+                    }
+                    Some(decoded) => {
+                        let placement = place_image(
+                            image,
+                            &decoded,
+                            options.image_dpi,
+                            decoded_images.scale,
+                            cursor.column_width,
+                            cursor.top - cursor.bottom,
+                        );
+                        cursor.ensure_space(placement.height);
+
+                        // Centralizando a imagem
+                        let x_position =
+                            cursor.column_x() + (cursor.column_width - placement.width) / 2.0;
+
+                        cursor.renderer.draw_image(
+                            decoded.image,
+                            (x_position, cursor.y - placement.height),
+                            (placement.width, placement.height),
+                        );
+
+                        cursor.y -= placement.height + typography.paragraph_spacing;
                     }
+                    None => match options.on_unsupported_image {
+                        UnsupportedImageMode::Fail if !is_vector_image(&image.bytes) => {
+                            return Err(unsupported_image(&image.bytes));
+                        }
+                        UnsupportedImageMode::Skip | UnsupportedImageMode::Fail => {
+                            warn!(
+                                "Skipping unsupported {} image at index {}",
+                                image_format_name(&image.bytes),
+                                index
+                            );
+                        }
+                        UnsupportedImageMode::Placeholder => {
+                            warn!(
+                                "Drawing placeholder for unsupported image at index {}",
+                                index
+                            );
+
+                            cursor.ensure_space(PLACEHOLDER_HEIGHT);
+                            draw_image_placeholder(
+                                cursor.renderer,
+                                cursor.column_x() + cursor.column_width / 2.0,
+                                cursor.y,
+                                typography.font_size,
+                                fonts.regular(),
+                            );
+                            cursor.y -= PLACEHOLDER_HEIGHT + typography.paragraph_spacing;
+                        }
+                    },
+                }
+            }
+            DocContent::Paragraph(paragraph) if !paragraph.runs.is_empty() => {
+                let (font_size, line_height, space_before) =
+                    paragraph_metrics(paragraph, typography);
+                // Space before is dropped at the top of a page or column.
+                if cursor.y < cursor.top {
+                    cursor.y -= space_before;
+                }
+
+                let list_indent = paragraph
+                    .list
+                    .as_ref()
+                    .map_or(0.0, |list| (list.level + 1) as f32 * LIST_INDENT);
+                let indentation = paragraph.indentation;
+                let line_max_width =
+                    cursor.column_width - list_indent - indentation.left - indentation.right;
+                // The list indent is on the start side: the right one for
+                // right-to-left paragraphs. Offsets are from the left of the
+                // column, which can change between lines.
+                let x_offset = if paragraph.rtl {
+                    indentation.left
+                } else {
+                    list_indent + indentation.left
+                };
+                let mut marker = paragraph.list.as_ref().map(|list| list.marker.as_str());
+
+                // Text after a hard line break is not indented again. Empty
+                // segments are blank lines.
+                let hyphenate = paragraph.alignment == Alignment::Justify;
+                let segments: Vec<Option<Vec<TextLine>>> =
+                    split_words(&paragraph.runs, font_size, options.preserve_spaces)
+                        .into_iter()
+                        .enumerate()
+                        .map(|(segment_index, words)| {
+                            let first_line_indent = if segment_index == 0 {
+                                indentation.first_line
+                            } else {
+                                0.0
+                            };
+                            (!words.is_empty()).then(|| {
+                                wrap_words(
+                                    words,
+                                    font_size,
+                                    line_max_width,
+                                    first_line_indent,
+                                    hyphenate,
+                                )
+                            })
+                        })
+                        .collect();
+                let segment_count = segments.len();
+                let line_count: usize = segments.iter().flatten().map(Vec::len).sum();
+                let mut line_index = 0;
+                for (segment_index, lines) in segments.into_iter().enumerate() {
+                    let Some(lines) = lines else {
+                        cursor.y -= typography.paragraph_spacing;
+                        continue;
+                    };
 
-                    let (font_to_use, x_position) = if trimmed_line.starts_with('-') {
-                        (&font, MARGIN + indent)
-                    } else if line_index == 0 && lines.len() > 1 {
-                        (&font_bold, MARGIN)
+                    let font_to_use = if paragraph.heading_level.is_some()
+                        || (segment_index == 0 && segment_count > 1)
+                    {
+                        &bold_fonts
                     } else {
-                        (&font, MARGIN)
+                        &fonts
                     };
 
-                    let words: Vec<&str> = trimmed_line.split_whitespace().collect();
-                    let mut current_line = String::new();
-                    let mut current_width = 0.0;
-
-                    for word in words {
-                        let word_width = estimate_text_width(word, FONT_SIZE);
-                        let space_width = estimate_text_width(" ", FONT_SIZE);
-
-                        if current_width + word_width + space_width > max_width
-                            && !current_line.is_empty()
-                        {
-                            debug!("Adding text at position {}", y_position);
-                            current_layer.use_text(
-                                current_line.clone(),
-                                FONT_SIZE,
-                                Mm(x_position),
-                                Mm(y_position),
-                                font_to_use,
-                            );
-                            y_position -= LINE_HEIGHT;
-                            current_line.clear();
-                            current_width = 0.0;
+                    for mut line in lines {
+                        line.rtl = paragraph.rtl;
+
+                        if options.widow_control && cursor.y < cursor.top {
+                            let room = cursor.y - cursor.content_bottom();
+                            let fitting = if room < 0.0 {
+                                0
+                            } else {
+                                (room / line_height) as usize + 1
+                            };
+                            if leaves_widow_or_orphan(line_index, line_count, fitting) {
+                                cursor.next_column();
+                            }
                         }
+                        line_index += 1;
 
-                        if !current_line.is_empty() {
-                            current_line.push(' ');
-                            current_width += space_width;
+                        // A footnote goes on the same page as its reference, so the
+                        // line moves to the next page when the note does not fit.
+                        let line_notes = if options.footnotes_at_end {
+                            Vec::new()
+                        } else {
+                            line_footnotes(&line)
+                        };
+                        if !line_notes.is_empty() {
+                            let notes_height: f32 = line_notes
+                                .iter()
+                                .map(|&note| footnotes[note].len() as f32 * note_line_height)
+                                .sum();
+                            cursor.ensure_space(line_height + notes_height + NOTE_SEPARATOR_SPACE);
+                            cursor.add_footnotes(&line_notes, notes_height);
+                        }
+
+                        // Lines holding runs larger than the paragraph font, or
+                        // inline images, get extra room above.
+                        let scaled_line_height = (line_height
+                            * (line.font_size / font_size).max(1.0))
+                        .max(line_image_height(&line, fit.scale) + INLINE_IMAGE_GAP);
+                        cursor.ensure_space(scaled_line_height - line_height);
+                        cursor.y -= scaled_line_height - line_height;
+                        let ascent: Mm = Pt(line.font_size * HIGHLIGHT_ASCENT).into();
+                        first_line.get_or_insert((cursor.page(), cursor.y + ascent.0));
+
+                        let x_position = cursor.column_x() + x_offset;
+                        if let Some(marker) = marker.take() {
+                            let marker_x = if paragraph.rtl {
+                                x_position + line_max_width + LIST_INDENT
+                                    - estimate_text_width(marker, font_size)
+                            } else {
+                                x_position - LIST_INDENT
+                            };
+                            cursor.renderer.draw_text(
+                                marker,
+                                fonts.regular(),
+                                font_size,
+                                (marker_x, cursor.y),
+                                RgbColor::BLACK,
+                            );
                         }
-                        current_line.push_str(word);
-                        current_width += word_width;
-                    }
 
-                    if !current_line.is_empty() {
-                        debug!("Adding text at position {}", y_position);
-                        current_layer.use_text(
-                            current_line,
-                            FONT_SIZE,
-                            Mm(x_position),
-                            Mm(y_position),
+                        debug!("Adding text at position {}", cursor.y);
+                        let inline_images = draw_text_line(
+                            cursor.renderer,
+                            &line,
+                            x_position,
+                            cursor.y,
+                            line_max_width,
+                            paragraph.alignment,
                             font_to_use,
                         );
-                        y_position -= LINE_HEIGHT;
+                        for (run, x) in inline_images {
+                            let origin = (x, cursor.y);
+                            draw_inline_image(
+                                cursor.renderer,
+                                run,
+                                origin,
+                                options,
+                                &mut decoded_images,
+                            )?;
+                        }
+                        cursor.y -= line_height;
+                        // A single paragraph can run over any number of pages.
+                        cursor.check_pages()?;
                     }
                 }
-                y_position -= PARAGRAPH_SPACING;
+                cursor.y -= paragraph
+                    .space_after
+                    .unwrap_or(typography.paragraph_spacing);
             }
+            DocContent::Paragraph(_) => {}
         }
 
-        if let Some(image) = &item.image {
-            debug!("Processing image at index {}", index);
+        if !item.comments().is_empty() {
+            let (page, top) = first_line.unwrap_or(item_start);
+            let page_width = cursor.page_sizes[page].width;
+            comments.extend(comment_annotations(item.comments(), page, top, page_width));
+        }
 
-            let mut reader = Cursor::new(&image.bytes);
+        // The next item starts in the next column when not even a line of it
+        // fits. The rest of a figure was given room above, even when it is
+        // short.
+        let room = cursor.y - cursor.content_bottom();
+        if room < typography.line_height && figure_height.is_none() {
+            cursor.next_column();
+        }
 
-            let printpdf_image = match guess_format(&image.bytes)? {
-                ImageFormat::Png => Image::try_from(PrintPdfPngDecoder::new(&mut reader)?)
-                    .context("Falha ao converter a imagem PNG para o formato PDF")?,
-                ImageFormat::Jpeg => Image::try_from(PrintPdfJpegDecoder::new(&mut reader)?)
-                    .context("Falha ao converter a imagem JPEG para o formato PDF")?,
-                _ => return Err(anyhow::anyhow!("Formato de imagem não suportado")),
-            };
+        cursor.check_pages()?;
+        progress(index + 1, content.len());
+    }
 
-            let image_width = printpdf_image.image.width.into_pt(400.0);
-            let image_height = printpdf_image.image.height.into_pt(400.0);
+    // Footnotes that were not placed on a page, because they are referenced
+    // from a table or were asked at the end, come before the endnotes.
+    let placed: Vec<usize> = cursor.footnotes.iter().flatten().copied().collect();
+    let end_notes: Vec<&Vec<TextLine>> = footnotes
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !placed.contains(index))
+        .map(|(_, lines)| lines)
+        .chain(&endnotes)
+        .collect();
+    if !end_notes.is_empty() {
+        debug!("Adding {} notes after the body", end_notes.len());
+        cursor.ensure_space(typography.paragraph_spacing + note_line_height);
+        let separator_x = cursor.column_x();
+        draw_horizontal_line(
+            cursor.renderer,
+            separator_x,
+            separator_x + NOTE_SEPARATOR_WIDTH,
+            cursor.y,
+            RgbColor::BLACK,
+        );
+        cursor.y -= typography.paragraph_spacing;
+        for lines in end_notes {
+            for line in lines {
+                cursor.ensure_space(0.0);
+                let x = cursor.column_x();
+                draw_text_line(
+                    cursor.renderer,
+                    line,
+                    x,
+                    cursor.y,
+                    column_width,
+                    Alignment::Left,
+                    &fonts,
+                );
+                cursor.y -= note_line_height;
+            }
+        }
+        cursor.check_pages()?;
+    }
 
-            let mut scale = (PAGE_WIDTH - 2.0 * MARGIN) / image_width.0;
+    for (page, page_notes) in cursor.footnotes.iter().enumerate() {
+        if page_notes.is_empty() {
+            continue;
+        }
+        cursor.renderer.begin_layer(page, "Footnotes");
+        let lines: Vec<&TextLine> = page_notes
+            .iter()
+            .flat_map(|&note| &footnotes[note])
+            .collect();
+        let text_top = cursor.bottom + lines.len() as f32 * note_line_height;
+        draw_horizontal_line(
+            cursor.renderer,
+            MARGIN,
+            MARGIN + NOTE_SEPARATOR_WIDTH,
+            text_top + NOTE_SEPARATOR_SPACE / 2.0,
+            RgbColor::BLACK,
+        );
+        let mut y_position = text_top - note_line_height * 0.75;
+        for line in lines {
+            draw_text_line(
+                cursor.renderer,
+                line,
+                MARGIN,
+                y_position,
+                column_width,
+                Alignment::Left,
+                &fonts,
+            );
+            y_position -= note_line_height;
+        }
+    }
+
+    let available = cursor.top - cursor.content_bottom();
+    let overflow = cursor.content_bottom() - cursor.y;
+    let pages = cursor.footnotes.len();
+    let page_sizes = cursor.page_sizes;
+    if !header.is_empty() || !footer.is_empty() {
+        debug!("Drawing header and footer on {} pages", pages);
+        // The cover page has neither. Unless page labels say otherwise, it is
+        // not counted either, so that the body starts at page 1.
+        let cover_pages = usize::from(cover_page);
+        let uncounted = if options.page_labels.is_empty() {
+            cover_pages
+        } else {
+            0
+        };
+        for (page, &size) in page_sizes.iter().enumerate().skip(cover_pages) {
+            renderer.begin_layer(page, "Header and Footer");
+            let width = size.width - 2.0 * MARGIN;
+            let label = page_label(&options.page_labels, page - uncounted);
+            let header_items = with_page_numbers(&document.header, &label, pages - uncounted);
+            let footer_items = with_page_numbers(&document.footer, &label, pages - uncounted);
+            let header = layout_header_footer(&header_items, typography, options, width);
+            let footer = layout_header_footer(&footer_items, typography, options, width);
+            let header_top = size.height - MARGIN;
+            draw_header_footer(renderer, &header, header_top, width, typography, &fonts);
+
+            let footer_top =
+                MARGIN + footer.len().saturating_sub(1) as f32 * typography.line_height;
+            draw_header_footer(renderer, &footer, footer_top, width, typography, &fonts);
+        }
+    }
+
+    if let Some(watermark) = &options.watermark {
+        debug!("Drawing watermark on {} pages", pages);
+        for (page, &size) in page_sizes.iter().enumerate() {
+            renderer.begin_layer(page, "Watermark");
+            draw_watermark(renderer, watermark, fonts.bold(), size);
+        }
+    }
+
+    Ok(Rendering {
+        available,
+        overflow,
+        comments,
+    })
+}
+
+// Refuses typography that would make the layout run backwards, or leave less
+// than a line of the page to the body, where every line would start a new
+// page.
+fn check_page_layout(typography: &TypographyConfig, body_height: f32) -> Result<()> {
+    let invalid = |message: String| Err(ConversionError::InvalidPageConfig(message));
+    if !(typography.font_size > 0.0 && typography.line_height > 0.0) {
+        return invalid(format!(
+            "font size ({}pt) and line height ({}mm) must be positive",
+            typography.font_size, typography.line_height
+        ));
+    }
+    if !(typography.paragraph_spacing >= 0.0 && typography.cell_padding >= 0.0) {
+        return invalid(format!(
+            "paragraph spacing ({}mm) and cell padding ({}mm) must not be negative",
+            typography.paragraph_spacing, typography.cell_padding
+        ));
+    }
+    if body_height < typography.line_height {
+        return invalid(format!(
+            "the header and footer leave {:.1}mm of the page to the body, less than a line",
+            body_height.max(0.0)
+        ));
+    }
+    Ok(())
+}
 
-            let max_height = y_position - MARGIN;
-            if image_height.0 * scale > max_height {
-                scale = max_height / image_height.0;
+// The margins, and the gaps between text columns, must leave room for the
+// body across the page.
+fn check_page_width(page_size: PageSize, columns: Columns) -> Result<()> {
+    let invalid = |message: String| Err(ConversionError::InvalidPageConfig(message));
+    let body_width = page_size.width - 2.0 * MARGIN;
+    if body_width <= 0.0 {
+        return invalid(format!(
+            "the margins leave no room for the body on {:.1}mm wide pages",
+            page_size.width
+        ));
+    }
+    if column_width(columns, page_size.width) <= 0.0 {
+        return invalid(format!(
+            "{} columns {:.1}mm apart leave no room for text on {:.1}mm wide pages",
+            columns.count, columns.spacing, page_size.width
+        ));
+    }
+    Ok(())
+}
+
+// Height of the image at `index` and the caption right after it, or of the
+// caption at `index` and the image after it. A caption between two images
+// goes with the one before.
+fn figure_with_caption_height<R>(
+    content: &[DocContent],
+    index: usize,
+    cursor: &PageCursor<R>,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    images: &DecodedImages,
+) -> Option<f32> {
+    let item = &content[index];
+    let next = content.get(index + 1)?;
+    let follows_image = index
+        .checked_sub(1)
+        .is_some_and(|previous| matches!(content[previous], DocContent::Image { .. }));
+    let (image, caption) = match (item, next) {
+        (DocContent::Image { image, .. }, DocContent::Paragraph(caption)) if caption.caption => {
+            (image, caption)
+        }
+        (DocContent::Paragraph(caption), DocContent::Image { image, .. })
+            if caption.caption && !follows_image =>
+        {
+            (image, caption)
+        }
+        _ => return None,
+    };
+    let placement = place_image(
+        image,
+        images.get(image)?,
+        options.image_dpi,
+        images.scale,
+        cursor.column_width,
+        cursor.top - cursor.bottom,
+    );
+    let caption_height = paragraph_height(caption, typography, options, cursor.column_width);
+    Some(placement.height + typography.paragraph_spacing + caption_height)
+}
+
+// Height of a paragraph whose lines are kept together (w:keepLines), or of
+// a chain of paragraphs each kept with the next (w:keepNext) and the first
+// line of the paragraph that ends it. Nothing is kept when the whole would
+// not fit in a column anyway.
+fn kept_height<R>(
+    content: &[DocContent],
+    index: usize,
+    cursor: &PageCursor<R>,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+) -> Option<f32> {
+    fn text_paragraph(item: &DocContent) -> Option<&Paragraph> {
+        match item {
+            DocContent::Paragraph(paragraph) if !paragraph.runs.is_empty() => Some(paragraph),
+            _ => None,
+        }
+    }
+    let item = text_paragraph(&content[index])?;
+    if !(item.keep_lines || item.keep_next) {
+        return None;
+    }
+
+    let width = cursor.column_width;
+    let mut height = paragraph_height(item, typography, options, width);
+    let mut previous = item;
+    for next in &content[index + 1..] {
+        let Some(next) = text_paragraph(next).filter(|_| previous.keep_next) else {
+            break;
+        };
+        height += if next.keep_lines || next.keep_next {
+            paragraph_height(next, typography, options, width)
+        } else {
+            let (_, line_height, space_before) = paragraph_metrics(next, typography);
+            space_before + line_height
+        };
+        previous = next;
+    }
+    (height <= cursor.top - cursor.bottom).then_some(height)
+}
+
+// Whether a paragraph must move on to the next column before its line
+// `index`, given how many lines still fit, so that none of its lines is left
+// alone at the bottom (orphan) or at the top (widow) of a column. Paragraphs
+// of up to three lines can't be split without doing either, so they move
+// whole.
+fn leaves_widow_or_orphan(index: usize, line_count: usize, fitting: usize) -> bool {
+    if fitting == 0 || fitting >= line_count - index {
+        return false;
+    }
+    match index {
+        0 => fitting == 1 || line_count <= 3,
+        _ => index + 2 == line_count && fitting == 1,
+    }
+}
+
+// Font size, line height and space before of a paragraph. Headings are set
+// larger and get some room above by default.
+fn paragraph_metrics(item: &Paragraph, typography: &TypographyConfig) -> (f32, f32, f32) {
+    let (font_size, line_height) = match item.heading_level {
+        Some(level) => {
+            let size = heading_font_size(level, typography.font_size);
+            (size, typography.line_height * size / typography.font_size)
+        }
+        None => (typography.font_size, typography.line_height),
+    };
+    let space_before = match item.space_before {
+        Some(space) => space,
+        None if item.heading_level.is_some() => HEADING_SPACING,
+        None => 0.0,
+    };
+    (
+        font_size,
+        item.line_spacing.line_height(line_height),
+        space_before,
+    )
+}
+
+// Height of a paragraph laid out at the given width.
+fn paragraph_height(
+    item: &Paragraph,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    width: f32,
+) -> f32 {
+    let (font_size, line_height, space_before) = paragraph_metrics(item, typography);
+    let width = width - item.indentation.left - item.indentation.right;
+    let first_line_indent = item.indentation.first_line;
+    let hyphenate = item.alignment == Alignment::Justify;
+    let lines: usize = split_words(&item.runs, font_size, options.preserve_spaces)
+        .into_iter()
+        .map(|words| {
+            wrap_words(words, font_size, width, first_line_indent, hyphenate)
+                .len()
+                .max(1)
+        })
+        .sum();
+    space_before
+        + lines as f32 * line_height
+        + item.space_after.unwrap_or(typography.paragraph_spacing)
+}
+
+// Icons in the right margin, from the top of the commented content down.
+fn comment_annotations(
+    comments: &[Comment],
+    page: usize,
+    top: f32,
+    page_width: f32,
+) -> Vec<TextAnnotation> {
+    let left = page_width - MARGIN + COMMENT_ICON_GAP;
+    comments
+        .iter()
+        .enumerate()
+        .map(|(index, comment)| {
+            let icon_top = top - index as f32 * (COMMENT_ICON_SIZE + COMMENT_ICON_GAP);
+            let points = |mm: f32| Pt::from(Mm(mm)).0;
+            TextAnnotation {
+                page,
+                rect: [
+                    points(left),
+                    points(icon_top - COMMENT_ICON_SIZE),
+                    points(left + COMMENT_ICON_SIZE),
+                    points(icon_top),
+                ],
+                author: comment.author.clone(),
+                text: comment.text.clone(),
             }
+        })
+        .collect()
+}
 
-            debug!("Escala da imagem: {}", scale);
+// Tracks the page being written and the vertical position on it.
+struct PageCursor<'a, R> {
+    renderer: &'a mut R,
+    // Footnotes placed at the bottom of each page, one entry per page.
+    footnotes: Vec<Vec<usize>>,
+    // Size of the current page, which changes at section breaks, and of each
+    // page so far.
+    page_size: PageSize,
+    page_sizes: Vec<PageSize>,
+    // Bounds of the body area, between the header and the footer.
+    top: f32,
+    bottom: f32,
+    // Room taken by the footnotes of the current page, above `bottom`.
+    footnote_height: f32,
+    y: f32,
+    // Text columns of the page, filled left to right.
+    columns: Columns,
+    column: usize,
+    column_width: f32,
+    // Never breaks the page, for measuring or fitting the content on one page.
+    single_page: bool,
+    max_pages: Option<usize>,
+    // Set when a page past `max_pages` was asked for. The page is not
+    // started, and check_pages fails.
+    pages_exceeded: bool,
+}
 
-            let scaled_width = image_width * scale;
-            let scaled_height = image_height * scale;
+impl<R: Renderer> PageCursor<'_, R> {
+    fn new_page(&mut self) {
+        if self.single_page {
+            return;
+        }
+        // Content past the limit is drawn over the last page until the next
+        // check_pages, rather than filling any number of pages.
+        let at_limit = self
+            .max_pages
+            .is_some_and(|max| self.footnotes.len() >= max);
+        if at_limit {
+            self.pages_exceeded = true;
+            self.column = 0;
+            self.y = self.top;
+            return;
+        }
+        debug!("Adding new page");
+        self.renderer.new_page(self.page_size);
+        self.footnotes.push(Vec::new());
+        self.page_sizes.push(self.page_size);
+        self.footnote_height = 0.0;
+        self.column = 0;
+        self.y = self.top;
+    }
 
-            if y_position - scaled_height.0 < MARGIN {
-                debug!("Adding new page for image");
-                let (page, layer1) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "New Page");
-                current_layer = doc.get_page(page).get_layer(layer1);
-                y_position = PAGE_HEIGHT - MARGIN;
+    // Starts the next section on a new page of its size, laid out in its
+    // columns. The body keeps its distance from the top and sides of the page.
+    fn new_section(&mut self, section: Section) {
+        let size = section.page_size;
+        // A page that has just been started is kept for the section.
+        let page_started = self.y >= self.top && self.column == 0;
+        let resized = size != self.page_size;
+        if self.single_page || (page_started && !resized && section.columns == self.columns) {
+            return;
+        }
+        debug!(
+            "Starting a section with {}x{}mm pages in {} columns",
+            size.width, size.height, section.columns.count
+        );
+        self.top += size.height - self.page_size.height;
+        self.columns = section.columns;
+        self.column_width = column_width(section.columns, size.width);
+        self.page_size = size;
+        if page_started {
+            // The blank page takes the size of the section instead of being
+            // left behind.
+            if resized {
+                self.renderer.resize_page(size);
+                *self.page_sizes.last_mut().expect("cursor has a page") = size;
             }
+            self.y = self.top;
+        } else {
+            self.new_page();
+        }
+    }
 
-            let x_position = (PAGE_WIDTH - scaled_width.0) / 2.0; // Centralizando a imagem
+    // Moves to the top of the next column, or of a new page after the last one.
+    fn next_column(&mut self) {
+        if self.column + 1 < self.columns.count {
+            self.column += 1;
+            self.y = self.top;
+        } else {
+            self.new_page();
+        }
+    }
 
-            printpdf_image.add_to_layer(
-                current_layer.clone(),
-                ImageTransform {
-                    translate_x: Some(Mm(x_position)),
-                    translate_y: Some(Mm(y_position - scaled_height.0)),
-                    scale_x: Some(4.0),
-                    scale_y: Some(4.0),
-                    ..Default::default()
-                },
+    // Index of the current page.
+    fn page(&self) -> usize {
+        self.footnotes.len() - 1
+    }
+
+    fn check_pages(&self) -> Result<()> {
+        let pages = self.footnotes.len() + usize::from(self.pages_exceeded);
+        ConversionLimits::check("pages", pages, self.max_pages)
+    }
+
+    fn column_x(&self) -> f32 {
+        MARGIN + self.column as f32 * (self.column_width + self.columns.spacing)
+    }
+
+    // Moves to the next column unless `height` still fits above the bottom
+    // margin.
+    fn ensure_space(&mut self, height: f32) {
+        if self.y - height < self.content_bottom() {
+            self.next_column();
+        }
+    }
+
+    fn content_bottom(&self) -> f32 {
+        self.bottom + self.footnote_height
+    }
+
+    fn add_footnotes(&mut self, notes: &[usize], height: f32) {
+        let page_notes = self.footnotes.last_mut().expect("cursor has a page");
+        if page_notes.is_empty() {
+            self.footnote_height += NOTE_SEPARATOR_SPACE;
+        }
+        page_notes.extend_from_slice(notes);
+        self.footnote_height += height;
+    }
+}
+
+// Picks the font face of the text. Runs that don't name a family use the
+// default one.
+#[derive(Clone, Copy)]
+struct Fonts {
+    default_family: FontFamily,
+    // Every run is drawn in bold, for paragraphs such as headings.
+    all_bold: bool,
+}
+
+impl Fonts {
+    fn new(default_family: FontFamily) -> Fonts {
+        Fonts {
+            default_family,
+            all_bold: false,
+        }
+    }
+
+    fn all_bold(&self) -> Fonts {
+        Fonts {
+            all_bold: true,
+            ..*self
+        }
+    }
+
+    // Regular face of the default family, for list markers and labels.
+    fn regular(&self) -> FontFace {
+        FontFace {
+            family: self.default_family,
+            bold: false,
+        }
+    }
+
+    fn bold(&self) -> FontFace {
+        FontFace {
+            family: self.default_family,
+            bold: true,
+        }
+    }
+
+    fn for_run(&self, run: &TextRun) -> FontFace {
+        FontFace {
+            family: run.font_family.unwrap_or(self.default_family),
+            bold: run.bold || self.all_bold,
+        }
+    }
+}
+
+// The font families of the text, for the renderer to load.
+fn font_families(document: &DocxDocument, default_family: FontFamily) -> Vec<FontFamily> {
+    let notes = document.footnotes.iter().chain(&document.endnotes);
+    let mut families: Vec<FontFamily> = document
+        .content
+        .iter()
+        .chain(&document.header)
+        .chain(&document.footer)
+        .flat_map(DocContent::runs)
+        .chain(notes.flat_map(|note| &note.runs))
+        .filter_map(|run| run.font_family)
+        .collect();
+    families.push(default_family);
+    families
+}
+
+// Width of each text column of a page `page_width` wide.
+fn column_width(columns: Columns, page_width: f32) -> f32 {
+    let body_width = page_width - 2.0 * MARGIN;
+    (body_width - (columns.count - 1) as f32 * columns.spacing) / columns.count as f32
+}
+
+// Draws the text along the diagonal of the page, centered.
+fn draw_watermark(renderer: &mut impl Renderer, text: &str, face: FontFace, page: PageSize) {
+    let angle = page.height.atan2(page.width);
+    let diagonal = page.width.hypot(page.height);
+    let font_size =
+        (WATERMARK_LENGTH * diagonal / estimate_text_width(text, 1.0)).min(WATERMARK_MAX_FONT_SIZE);
+    let width = estimate_text_width(text, font_size);
+    let cap_height: Mm = Pt(font_size * CELL_TEXT_ASCENT).into();
+
+    // The baseline starts half the text width before the center, and half the
+    // capital height below it.
+    let x = (page.width - width * angle.cos() + cap_height.0 * angle.sin()) / 2.0;
+    let y = (page.height - width * angle.sin() - cap_height.0 * angle.cos()) / 2.0;
+
+    renderer.draw_watermark(text, face, font_size, (x, y), angle);
+}
+
+// Draws the title, author and date of the document centered on the page, the
+// title larger and in bold. Those missing from the document are left out.
+// Returns whether anything was drawn.
+fn draw_cover_page(
+    renderer: &mut impl Renderer,
+    metadata: &DocumentMetadata,
+    page: PageSize,
+    typography: &TypographyConfig,
+    fonts: &Fonts,
+) -> bool {
+    debug!("Drawing the cover page");
+    let entries = [
+        (&metadata.title, COVER_TITLE_SCALE, true),
+        (&metadata.author, COVER_TEXT_SCALE, false),
+        (&metadata.date, COVER_TEXT_SCALE, false),
+    ];
+    let runs: Vec<(TextRun, f32)> = entries
+        .into_iter()
+        .filter_map(|(text, scale, bold)| {
+            let text = text.as_deref()?.trim();
+            let font_size = typography.font_size * scale;
+            let run = TextRun {
+                text: text.to_string(),
+                font_size: Some(font_size),
+                bold,
+                ..Default::default()
+            };
+            (!text.is_empty()).then_some((run, scale))
+        })
+        .collect();
+    if runs.is_empty() {
+        warn!("The document has no title, author or date; leaving out the cover page");
+        return false;
+    }
+
+    let max_width = page.width - 2.0 * MARGIN;
+    let blocks: Vec<(Vec<TextLine>, f32)> = runs
+        .iter()
+        .map(|(run, scale)| {
+            let font_size = typography.font_size * scale;
+            let lines = split_words(std::slice::from_ref(run), font_size, false)
+                .into_iter()
+                .flat_map(|words| wrap_words(words, font_size, max_width, 0.0, false))
+                .collect();
+            (lines, typography.line_height * scale)
+        })
+        .collect();
+    let spacing = 2.0 * typography.paragraph_spacing;
+    let height: f32 = blocks
+        .iter()
+        .map(|(lines, line_height)| lines.len() as f32 * line_height)
+        .sum::<f32>()
+        + (blocks.len() - 1) as f32 * spacing;
+
+    let mut y_position = (page.height + height) / 2.0;
+    for (lines, line_height) in &blocks {
+        for line in lines {
+            y_position -= line_height;
+            draw_text_line(
+                renderer,
+                line,
+                MARGIN,
+                y_position,
+                max_width,
+                Alignment::Center,
+                fonts,
             );
+        }
+        y_position -= spacing;
+    }
+    true
+}
+
+// Label of page `page`, counted from 0: its number in its range of labels.
+fn page_label(ranges: &[PageLabelRange], page: usize) -> String {
+    let range = ranges
+        .iter()
+        .rev()
+        .find(|range| range.first_page <= page + 1);
+    match range {
+        Some(range) => range.style.format(page + 2 - range.first_page),
+        None => (page + 1).to_string(),
+    }
+}
+
+// Headers and footers with the label of the page and the page count in place
+// of their PAGE and NUMPAGES fields.
+fn with_page_numbers(items: &[DocContent], label: &str, pages: usize) -> Vec<DocContent> {
+    items
+        .iter()
+        .map(|item| match item {
+            DocContent::Paragraph(paragraph) => DocContent::Paragraph(Paragraph {
+                runs: paragraph
+                    .runs
+                    .iter()
+                    .map(|run| match run.field {
+                        Some(PageField::Number) => TextRun {
+                            text: label.to_string(),
+                            ..run.clone()
+                        },
+                        Some(PageField::Count) => TextRun {
+                            text: pages.to_string(),
+                            ..run.clone()
+                        },
+                        None => run.clone(),
+                    })
+                    .collect(),
+                alignment: paragraph.alignment,
+                ..Default::default()
+            }),
+            item => item.clone(),
+        })
+        .collect()
+}
 
-            y_position -= scaled_height.0 + PARAGRAPH_SPACING;
+// Headers and footers are laid out once to make room for them, then again on
+// each page with its page number.
+fn layout_header_footer<'a>(
+    items: &'a [DocContent],
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    max_width: f32,
+) -> Vec<(TextLine<'a>, Alignment)> {
+    let mut lines = Vec::new();
+    for item in items {
+        let DocContent::Paragraph(paragraph) = item else {
+            continue;
+        };
+        for words in split_words(
+            &paragraph.runs,
+            typography.font_size,
+            options.preserve_spaces,
+        ) {
+            for line in wrap_words(words, typography.font_size, max_width, 0.0, false) {
+                lines.push((line, paragraph.alignment));
+            }
         }
+    }
+    lines
+}
+
+fn layout_notes<'a>(
+    notes: &'a [Note],
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    max_width: f32,
+) -> Vec<Vec<TextLine<'a>>> {
+    let font_size = typography.font_size * NOTE_SCALE;
+    notes
+        .iter()
+        .map(|note| {
+            split_words(&note.runs, font_size, options.preserve_spaces)
+                .into_iter()
+                .flat_map(|words| wrap_words(words, font_size, max_width, 0.0, false))
+                .collect()
+        })
+        .collect()
+}
+
+// Footnotes referenced from a line, in order.
+fn line_footnotes(line: &TextLine) -> Vec<usize> {
+    let mut notes: Vec<usize> = line
+        .words
+        .iter()
+        .flat_map(|word| &word.pieces)
+        .filter_map(|piece| piece.run.footnote)
+        .collect();
+    notes.dedup();
+    notes
+}
 
-        if y_position < MARGIN + 20.0 {
-            debug!("Adding new page");
-            let (page, layer1) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "New Page");
-            current_layer = doc.get_page(page).get_layer(layer1);
-            y_position = PAGE_HEIGHT - MARGIN;
+fn header_footer_height(lines: &[(TextLine, Alignment)], typography: &TypographyConfig) -> f32 {
+    if lines.is_empty() {
+        return 0.0;
+    }
+    lines.len() as f32 * typography.line_height + typography.paragraph_spacing
+}
+
+fn draw_header_footer(
+    renderer: &mut impl Renderer,
+    lines: &[(TextLine, Alignment)],
+    top: f32,
+    width: f32,
+    typography: &TypographyConfig,
+    fonts: &Fonts,
+) {
+    let mut y_position = top;
+    for (line, alignment) in lines {
+        draw_text_line(renderer, line, MARGIN, y_position, width, *alignment, fonts);
+        y_position -= typography.line_height;
+    }
+}
+
+// Returns the inline images of the line with their x position, for the
+// caller to draw.
+fn draw_text_line<'a>(
+    renderer: &mut impl Renderer,
+    line: &TextLine<'a>,
+    x: f32,
+    y: f32,
+    available_width: f32,
+    alignment: Alignment,
+    fonts: &Fonts,
+) -> Vec<(&'a TextRun, f32)> {
+    let word_count = line.words.len();
+
+    // Word leaves the last line of a justified paragraph ragged, so do the same.
+    // Lines with tabs keep their tab stop positions.
+    let justified =
+        alignment == Alignment::Justify && !line.is_last && word_count > 1 && !line.has_tabs();
+    let line_start = line.words.first().map_or(0.0, |word| word.x);
+    let gap = if justified {
+        (available_width - line_start - line.words_width) / (word_count - 1) as f32
+    } else {
+        0.0
+    };
+    let offset = match alignment {
+        Alignment::Center => (available_width - line.width) / 2.0,
+        Alignment::Right => available_width - line.width,
+        Alignment::Left | Alignment::Justify => 0.0,
+    };
+    let mut justified_x = x + line_start;
+
+    // Consecutive pieces of the same run are drawn with a single text operation
+    // unless the words have been spread apart for justification or tab stops.
+    let mut spans: Vec<TextSpan> = Vec::new();
+    for (word_index, word) in line.words.iter().enumerate() {
+        let word_x = if justified {
+            justified_x
+        } else {
+            x + offset.max(0.0) + word.x
+        };
+        let mut piece_x = word_x;
+        for (piece_index, piece) in word.pieces.iter().enumerate() {
+            let separator = if piece_index == 0 && word_index > 0 && !word.joined {
+                " "
+            } else {
+                ""
+            };
+            match spans.last_mut() {
+                Some(span)
+                    if std::ptr::eq(span.run, piece.run)
+                        && (piece_index > 0
+                            || (!justified && !line.rtl && word.follows_single_space())) =>
+                {
+                    span.text.push_str(separator);
+                    span.text.push_str(piece.text);
+                    span.width = piece_x + piece.width - span.x;
+                }
+                _ => spans.push(TextSpan {
+                    text: piece.text.to_string(),
+                    run: piece.run,
+                    x: piece_x,
+                    width: piece.width,
+                    font_size: piece.font_size,
+                }),
+            }
+            piece_x += piece.width;
         }
+        justified_x += word.width + gap;
     }
 
-    debug!("Saving PDF to {}", pdf_path);
-    doc.save(&mut BufWriter::new(File::create(pdf_path)?))
-        .with_context(|| format!("Failed to save PDF file: {}", pdf_path))?;
+    // Right-to-left lines are laid out as left-to-right ones and then mirrored,
+    // so the start alignment ends up against the right margin. Each word is its
+    // own span, and right-to-left words are drawn in visual order.
+    if line.rtl {
+        for span in &mut spans {
+            span.x = 2.0 * x + available_width - span.x - span.width;
+            if span.text.chars().any(is_rtl_char) {
+                span.text = span.text.chars().rev().collect();
+            }
+        }
+    }
 
-    let pdf_size = std::fs::metadata(pdf_path)?.len();
-    info!("PDF saved successfully. File size: {} bytes", pdf_size);
+    let mut images = Vec::new();
+    for span in spans {
+        if span.run.image.is_some() {
+            images.push((span.run, span.x));
+        } else {
+            draw_text_span(renderer, &span, y, fonts);
+        }
+    }
+    images
+}
+
+// Height of the tallest inline image of a line.
+fn line_image_height(line: &TextLine, scale: f32) -> f32 {
+    line.words
+        .iter()
+        .flat_map(|word| &word.pieces)
+        .filter_map(|piece| piece.run.image.as_ref()?.size)
+        .map(|size| size.height * scale)
+        .fold(0.0, f32::max)
+}
 
+// Inline images sit on the baseline, at the size set in the document.
+fn draw_inline_image(
+    renderer: &mut impl Renderer,
+    run: &TextRun,
+    origin: (f32, f32),
+    options: &PdfOptions,
+    decoded_images: &mut DecodedImages,
+) -> Result<()> {
+    let Some(image) = &run.image else {
+        return Ok(());
+    };
+    let Some(decoded) = decoded_images.take(image) else {
+        match options.on_unsupported_image {
+            UnsupportedImageMode::Fail if !is_vector_image(&image.bytes) => {
+                return Err(unsupported_image(&image.bytes));
+            }
+            UnsupportedImageMode::Skip | UnsupportedImageMode::Fail => {
+                warn!(
+                    "Skipping unsupported {} inline image",
+                    image_format_name(&image.bytes)
+                );
+            }
+            UnsupportedImageMode::Placeholder => {
+                warn!("Drawing placeholder for unsupported inline image");
+                // An outline in the room the line kept for the image, too
+                // small for the placeholder text.
+                if let Some(size) = image.size {
+                    let (x, y) = origin;
+                    let scale = decoded_images.scale;
+                    let outline = [x, y, x + size.width * scale, y + size.height * scale];
+                    renderer.stroke_rect(outline, LINE_WIDTH, RgbColor::BLACK);
+                }
+            }
+        }
+        return Ok(());
+    };
+    let placement = place_image(
+        image,
+        &decoded,
+        options.image_dpi,
+        decoded_images.scale,
+        f32::INFINITY,
+        f32::INFINITY,
+    );
+    renderer.draw_image(decoded.image, origin, (placement.width, placement.height));
     Ok(())
 }
 
-fn process_table_for_pdf(
-    table_content: &str,
-    current_layer: &mut PdfLayerReference,
-    mut y_position: f32,
-    font: &IndirectFontRef,
-) -> Result<f32> {
-    let rows: Vec<&str> = table_content.split('\n').collect();
-    let num_columns = rows[1].split('|').count() - 2;
-    let column_width = (PAGE_WIDTH - 2.0 * MARGIN) / num_columns as f32;
-    let initial_y = y_position;
+struct TextSpan<'a> {
+    text: String,
+    run: &'a TextRun,
+    x: f32,
+    width: f32,
+    font_size: f32,
+}
 
-    draw_horizontal_line(current_layer, MARGIN, y_position, num_columns, column_width);
+fn draw_text_span(renderer: &mut impl Renderer, span: &TextSpan, y: f32, fonts: &Fonts) {
+    let font_size = span.font_size;
+    let face = fonts.for_run(span.run);
+    let baseline_shift: Mm = match span.run.vertical_align {
+        VerticalAlign::Baseline => Mm(0.0),
+        VerticalAlign::Superscript => Pt(font_size * SUPERSCRIPT_RISE).into(),
+        VerticalAlign::Subscript => Pt(-font_size * SUBSCRIPT_DROP).into(),
+    };
+    let y = y + baseline_shift.0;
+    let link = span.run.link.as_ref();
+    let color = span
+        .run
+        .color
+        .or(link.map(|_| LINK_COLOR))
+        .unwrap_or(RgbColor::BLACK);
 
-    for (_, row) in rows.iter().enumerate().skip(1) {
-        if row.trim() == "TABLE_END" {
-            break;
+    if let Some(highlight) = span.run.highlight {
+        draw_highlight(renderer, span, y, highlight);
+    }
+
+    draw_span_text(renderer, &span.text, face, font_size, (span.x, y), color);
+    draw_strike(renderer, span, y, color);
+
+    let underline_y = y - UNDERLINE_OFFSET;
+    if span.run.underline || link.is_some() {
+        draw_horizontal_line(renderer, span.x, span.x + span.width, underline_y, color);
+    }
+
+    let Some(url) = link else {
+        return;
+    };
+
+    let text_height: Mm = Pt(font_size).into();
+    renderer.add_link(
+        [span.x, underline_y, span.x + span.width, y + text_height.0],
+        url,
+    );
+}
+
+// The built-in fonts have no checkbox glyphs, so checkboxes are drawn as
+// boxes in the room the layout gave their character.
+fn draw_span_text(
+    renderer: &mut impl Renderer,
+    text: &str,
+    face: FontFace,
+    font_size: f32,
+    (mut x, y): (f32, f32),
+    color: RgbColor,
+) {
+    let mut rest = text;
+    while let Some(index) = rest.find(is_checkbox) {
+        let (before, after) = rest.split_at(index);
+        if !before.is_empty() {
+            renderer.draw_text(before, face, font_size, (x, y), color);
+            x += estimate_text_width(before, font_size);
         }
+        let checkbox = after.chars().next().expect("a checkbox was found");
+        let (checkbox_text, after) = after.split_at(checkbox.len_utf8());
+        draw_checkbox(renderer, checkbox, font_size, (x, y), color);
+        x += estimate_text_width(checkbox_text, font_size);
+        rest = after;
+    }
+    if !rest.is_empty() {
+        renderer.draw_text(rest, face, font_size, (x, y), color);
+    }
+}
 
-        y_position -= LINE_HEIGHT;
+fn is_checkbox(ch: char) -> bool {
+    matches!(ch, '☐' | '☑' | '☒')
+}
 
-        let cells: Vec<&str> = row.split('|').collect();
-        for (col_index, cell) in cells.iter().enumerate().skip(1).take(num_columns) {
-            let x = MARGIN + (col_index - 1) as f32 * column_width;
-            current_layer.use_text(
-                cell.trim().to_string(),
-                FONT_SIZE,
-                Mm(x + 13.0),
-                Mm(y_position + 2.0),
-                font,
-            );
+fn draw_checkbox(
+    renderer: &mut impl Renderer,
+    checkbox: char,
+    font_size: f32,
+    (x, y): (f32, f32),
+    color: RgbColor,
+) {
+    let size: Mm = Pt(font_size * CHECKBOX_SIZE).into();
+    let size = size.0;
+    renderer.stroke_rect([x, y, x + size, y + size], LINE_WIDTH, color);
+    match checkbox {
+        '☑' => {
+            let corner = (x + 0.4 * size, y + 0.2 * size);
+            renderer.draw_line((x + 0.2 * size, y + 0.5 * size), corner, LINE_WIDTH, color);
+            renderer.draw_line(corner, (x + 0.8 * size, y + 0.8 * size), LINE_WIDTH, color);
+        }
+        '☒' => {
+            renderer.draw_line((x, y), (x + size, y + size), LINE_WIDTH, color);
+            renderer.draw_line((x, y + size), (x + size, y), LINE_WIDTH, color);
+        }
+        _ => {}
+    }
+}
+
+// The highlight is filled behind the text, from below the descenders to above
+// the capitals.
+fn draw_highlight(renderer: &mut impl Renderer, span: &TextSpan, y: f32, highlight: RgbColor) {
+    let descent: Mm = Pt(span.font_size * HIGHLIGHT_DESCENT).into();
+    let ascent: Mm = Pt(span.font_size * HIGHLIGHT_ASCENT).into();
+    renderer.fill_rect(
+        [span.x, y - descent.0, span.x + span.width, y + ascent.0],
+        highlight,
+    );
+}
+
+fn draw_strike(renderer: &mut impl Renderer, span: &TextSpan, y: f32, color: RgbColor) {
+    let offsets: &[f32] = match span.run.strike {
+        Strike::None => return,
+        Strike::Single => &[0.0],
+        Strike::Double => &[-DOUBLE_STRIKE_GAP, DOUBLE_STRIKE_GAP],
+    };
+
+    for offset in offsets {
+        let line_y: Mm = Pt(span.font_size * (STRIKE_HEIGHT + offset)).into();
+        draw_horizontal_line(renderer, span.x, span.x + span.width, y + line_y.0, color);
+    }
+}
+
+// Images are decoded up front, in parallel, since decoding dominates the
+// conversion time of image-heavy documents. Layout then takes them in order.
+#[derive(Clone)]
+struct DecodedImages {
+    images: HashMap<*const ImageContent, Option<DecodedImage>>,
+    // Factor applied to the size of every image.
+    scale: f32,
+}
 
-            draw_vertical_line(current_layer, x, initial_y, y_position);
+impl DecodedImages {
+    fn decode(content: &[DocContent], options: &PdfOptions) -> Result<Self> {
+        let sources: Vec<&ImageContent> = content
+            .iter()
+            .flat_map(|item| -> Vec<&ImageContent> {
+                match item {
+                    DocContent::Image { image, .. } => vec![image],
+                    DocContent::Table { table, .. } => table.images(),
+                    item => item
+                        .runs()
+                        .iter()
+                        .filter_map(|run| run.image.as_ref())
+                        .collect(),
+                }
+            })
+            .collect();
+        let image_bytes = sources.iter().map(|image| image.bytes.len()).sum();
+        ConversionLimits::check("image bytes", image_bytes, options.limits.max_image_bytes)?;
+
+        debug!("Decoding {} images", sources.len());
+        let decoded = sources
+            .par_iter()
+            .map(|image| {
+                let Some(xobject) = decode_image(&image.bytes)? else {
+                    return Ok(None);
+                };
+                let pixel_size = (xobject.width, xobject.height);
+                let xobject = match options.max_image_dimension {
+                    Some(max_dimension) => downsample(xobject, max_dimension),
+                    None => xobject,
+                };
+                Ok(RasterImage::from_xobject(xobject)
+                    .map(|image| DecodedImage { image, pixel_size }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(DecodedImages {
+            images: sources
+                .into_iter()
+                .map(|image| image as *const ImageContent)
+                .zip(decoded)
+                .collect(),
+            scale: 1.0,
+        })
+    }
+
+    // A copy for one layout pass, which takes the images it draws.
+    fn scaled(&self, scale: f32) -> Self {
+        DecodedImages {
+            images: self.images.clone(),
+            scale,
+        }
+    }
+
+    fn take(&mut self, image: &ImageContent) -> Option<DecodedImage> {
+        self.images
+            .remove(&(image as *const ImageContent))
+            .flatten()
+    }
+
+    fn get(&self, image: &ImageContent) -> Option<&DecodedImage> {
+        self.images.get(&(image as *const ImageContent))?.as_ref()
+    }
+}
+
+// An image ready to draw, and its size in pixels before any downsampling,
+// which gives its natural size on the page.
+#[derive(Clone)]
+struct DecodedImage {
+    image: RasterImage,
+    pixel_size: (Px, Px),
+}
+
+fn decode_image(bytes: &[u8]) -> Result<Option<ImageXObject>> {
+    #[cfg(feature = "svg")]
+    if is_svg(bytes) {
+        return Ok(rasterize_svg(bytes).map(|image| image.image));
+    }
+
+    let Ok(format) = guess_format(bytes) else {
+        return Ok(None);
+    };
+
+    let mut reader = Cursor::new(bytes);
+
+    let printpdf_image = match format {
+        ImageFormat::Png => PrintPdfPngDecoder::new(&mut reader).and_then(Image::try_from),
+        ImageFormat::Jpeg => match corrected_jpeg(bytes) {
+            Some(corrected) => Ok(corrected),
+            None => PrintPdfJpegDecoder::new(&mut reader).and_then(Image::try_from),
+        },
+        ImageFormat::Gif => PrintPdfGifDecoder::new(&mut reader).and_then(Image::try_from),
+        ImageFormat::Bmp => PrintPdfBmpDecoder::new(&mut reader).and_then(Image::try_from),
+        ImageFormat::Tiff => PrintPdfTiffDecoder::new(&mut reader).and_then(Image::try_from),
+        _ => return Ok(None),
+    };
+
+    printpdf_image
+        .map(|image| Some(image.image))
+        .map_err(|source| ConversionError::ImageDecode {
+            format: image_format_name(bytes),
+            source,
+        })
+}
+
+fn unsupported_image(bytes: &[u8]) -> ConversionError {
+    ConversionError::UnsupportedImage {
+        format: image_format_name(bytes),
+    }
+}
+
+pub(crate) fn image_format_name(bytes: &[u8]) -> String {
+    if let Some(format) = vector_image_format(bytes) {
+        return format.to_string();
+    }
+    guess_format(bytes).map_or_else(|_| "unknown".to_string(), |format| format!("{:?}", format))
+}
+
+// Word often embeds pasted charts and shapes as EMF or WMF. These vector
+// formats can't be embedded, and are skipped even when unsupported images
+// should fail the conversion. So is SVG, unless the `svg` feature is enabled
+// to rasterize it.
+fn vector_image_format(bytes: &[u8]) -> Option<&'static str> {
+    if is_svg(bytes) {
+        Some("SVG")
+    } else if bytes.starts_with(&[0x01, 0x00, 0x00, 0x00]) && bytes.get(40..44) == Some(b" EMF") {
+        Some("EMF")
+    } else if bytes.starts_with(&[0xD7, 0xCD, 0xC6, 0x9A])
+        || bytes.starts_with(&[0x01, 0x00, 0x09, 0x00])
+        || bytes.starts_with(&[0x02, 0x00, 0x09, 0x00])
+    {
+        Some("WMF")
+    } else {
+        None
+    }
+}
+
+// An XML document whose root, after the prolog and comments, is <svg>.
+fn is_svg(bytes: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    (head.starts_with("<?xml") || head.starts_with("<svg") || head.starts_with("<!--"))
+        && head.contains("<svg")
+}
+
+fn is_vector_image(bytes: &[u8]) -> bool {
+    vector_image_format(bytes).is_some()
+}
+
+fn draw_image_placeholder(
+    renderer: &mut impl Renderer,
+    center_x: f32,
+    y_position: f32,
+    font_size: f32,
+    face: FontFace,
+) {
+    let left = center_x - PLACEHOLDER_WIDTH / 2.0;
+    let right = left + PLACEHOLDER_WIDTH;
+    let bottom = y_position - PLACEHOLDER_HEIGHT;
+
+    let outline = [left, bottom, right, y_position];
+    renderer.stroke_rect(outline, LINE_WIDTH, RgbColor::BLACK);
+
+    renderer.draw_text(
+        "Imagem não suportada",
+        face,
+        font_size,
+        (left + 4.0, bottom + PLACEHOLDER_HEIGHT / 2.0),
+        RgbColor::BLACK,
+    );
+}
+
+// Sizes for Heading1..Heading5 relative to an 11pt body; deeper levels use the body size.
+const HEADING_SIZES: [f32; 5] = [20.0, 16.0, 14.0, 13.0, 12.0];
+
+fn heading_font_size(level: u8, body_font_size: f32) -> f32 {
+    HEADING_SIZES
+        .get(usize::from(level.saturating_sub(1)))
+        .map_or(body_font_size, |size| size * body_font_size / FONT_SIZE)
+}
+
+fn process_table_for_pdf<R: Renderer>(
+    table: &TableContent,
+    cursor: &mut PageCursor<R>,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    decoded_images: &mut DecodedImages,
+    fonts: &Fonts,
+) -> Result<()> {
+    let layout = layout_table(
+        table,
+        cursor.column_width,
+        typography,
+        options,
+        decoded_images,
+    )?;
+    let header_rows = table.rows.iter().take_while(|row| row.header).count();
+
+    // A row that does not fit moves to the next column or page, below the
+    // header rows drawn again. A row taller than a whole page is drawn
+    // anyway. The header rows stay with the first row below them, so they
+    // are never left alone at the bottom of a page.
+    for row_index in 0..layout.rows.len() {
+        let height = if row_index == 0 && header_rows < layout.rows.len() {
+            layout.rows[..=header_rows]
+                .iter()
+                .map(|row| row.height)
+                .sum()
+        } else {
+            layout.rows[row_index].height
+        };
+        if cursor.y - height < cursor.content_bottom() && cursor.y < cursor.top {
+            cursor.next_column();
+            if row_index >= header_rows {
+                for header_index in 0..header_rows {
+                    draw_table_row(&layout, header_index, cursor, typography, options, fonts);
+                }
+            }
         }
-        draw_horizontal_line(current_layer, MARGIN, y_position, num_columns, column_width);
+        draw_table_row(&layout, row_index, cursor, typography, options, fonts);
+        cursor.check_pages()?;
     }
+    Ok(())
+}
+
+fn draw_table_row<R: Renderer>(
+    layout: &TableLayout,
+    row_index: usize,
+    cursor: &mut PageCursor<R>,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    fonts: &Fonts,
+) {
+    let top_left = (cursor.column_x(), cursor.y);
+    draw_row(
+        layout,
+        row_index,
+        cursor.renderer,
+        top_left,
+        typography,
+        options,
+        fonts,
+    );
+    cursor.y -= layout.rows[row_index].height;
+}
+
+// Text boxes are drawn where they are anchored, as a bordered block the width
+// of the column with the same padding as table cells.
+fn draw_text_box<R: Renderer>(
+    runs: &[TextRun],
+    cursor: &mut PageCursor<R>,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    fonts: &Fonts,
+) {
+    let padding = typography.cell_padding;
+    let width = cursor.column_width;
+    let text_width = cell_text_width(width, typography);
+    let lines: Vec<TextLine> = split_words(runs, typography.font_size, options.preserve_spaces)
+        .into_iter()
+        .flat_map(|words| wrap_words(words, typography.font_size, text_width, 0.0, false))
+        .collect();
+    let height = lines.len().max(1) as f32 * typography.line_height + 2.0 * padding;
+    cursor.ensure_space(height);
 
-    draw_vertical_line(
-        current_layer,
-        MARGIN + num_columns as f32 * column_width,
-        initial_y,
-        y_position,
+    let x = cursor.column_x();
+    let top = cursor.y;
+    let ascent: Mm = Pt(typography.font_size * CELL_TEXT_ASCENT).into();
+    let mut line_y = top - padding - ascent.0;
+    for line in &lines {
+        draw_text_line(
+            cursor.renderer,
+            line,
+            x + padding,
+            line_y,
+            text_width,
+            Alignment::Left,
+            fonts,
+        );
+        line_y -= typography.line_height;
+    }
+    cursor.renderer.stroke_rect(
+        [x, top - height, x + width, top],
+        LINE_WIDTH,
+        RgbColor::BLACK,
     );
 
-    draw_horizontal_line(current_layer, MARGIN, y_position, num_columns, column_width);
+    cursor.y -= height + typography.paragraph_spacing;
+}
+
+struct TableLayout<'a> {
+    table: &'a TableContent,
+    column_widths: Vec<f32>,
+    rows: Vec<RowLayout<'a>>,
+}
+
+impl TableLayout<'_> {
+    fn height(&self) -> f32 {
+        self.rows.iter().map(|row| row.height).sum()
+    }
+}
 
-    Ok(y_position)
+struct RowLayout<'a> {
+    height: f32,
+    cells: Vec<CellLayout<'a>>,
+}
+
+struct CellLayout<'a> {
+    // First grid column of the cell and its width, spanning its columns.
+    column: usize,
+    width: f32,
+    line_count: usize,
+    images: Vec<CellImage>,
+    tables: Vec<TableLayout<'a>>,
+}
+
+impl CellLayout<'_> {
+    // Height of the text lines, images and nested tables of the cell.
+    fn content_height(&self, typography: &TypographyConfig) -> f32 {
+        self.line_count as f32 * typography.line_height
+            + self
+                .images
+                .iter()
+                .map(|image| image.placement.height + CELL_IMAGE_GAP)
+                .sum::<f32>()
+            + self
+                .tables
+                .iter()
+                .map(|nested| nested.height() + CELL_IMAGE_GAP)
+                .sum::<f32>()
+    }
+}
+
+// Tables are measured before they are drawn so that a nested table, sized to
+// the width of its cell, can make the parent row taller.
+fn layout_table<'a>(
+    table: &'a TableContent,
+    width: f32,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    decoded_images: &mut DecodedImages,
+) -> Result<TableLayout<'a>> {
+    let column_widths = column_widths(table, width);
+
+    let mut rows = Vec::with_capacity(table.rows.len());
+    for row in &table.rows {
+        let mut cells = Vec::with_capacity(row.cells.len());
+        // Cells are placed on the grid one after the other, each taking as
+        // many columns as it spans. Those past the last column of a ragged
+        // row are dropped.
+        let mut column = 0;
+        for cell in &row.cells {
+            if column >= column_widths.len() {
+                debug!("Skipping table cell past the last grid column");
+                break;
+            }
+            let span = cell.column_span.max(1).min(column_widths.len() - column);
+            let width = column_widths[column..column + span].iter().sum();
+            let text_width = cell_text_width(width, typography);
+            let line_count = cell_lines(cell, text_width, typography, options).len();
+            let images = decode_cell_images(cell, text_width, options, decoded_images)?;
+            let tables = cell
+                .tables
+                .iter()
+                .map(|nested| layout_table(nested, text_width, typography, options, decoded_images))
+                .collect::<Result<Vec<_>>>()?;
+            cells.push(CellLayout {
+                column,
+                width,
+                line_count,
+                images,
+                tables,
+            });
+            column += span;
+        }
+
+        // The row is as tall as its tallest cell: text lines, images and
+        // nested tables.
+        let height = cells
+            .iter()
+            .map(|cell| cell.content_height(typography))
+            .fold(typography.line_height, f32::max)
+            + 2.0 * typography.cell_padding;
+        rows.push(RowLayout { height, cells });
+    }
+
+    Ok(TableLayout {
+        table,
+        column_widths,
+        rows,
+    })
+}
+
+// The grid column widths, scaled to fill the available width. Tables without
+// a grid covering all of their cells get equal columns.
+fn column_widths(table: &TableContent, width: f32) -> Vec<f32> {
+    let num_columns = table.column_count().max(1);
+    let grid = &table.column_widths;
+    let grid_width: f32 = grid.iter().sum();
+    if grid.len() >= num_columns && grid.iter().all(|&column| column > 0.0) {
+        grid.iter()
+            .map(|column| column * width / grid_width)
+            .collect()
+    } else {
+        vec![width / num_columns as f32; num_columns]
+    }
+}
+
+// Nested tables are drawn whole, inside the row of their parent.
+fn draw_table(
+    layout: &TableLayout,
+    renderer: &mut impl Renderer,
+    x_start: f32,
+    mut y_position: f32,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    fonts: &Fonts,
+) {
+    for (row_index, row_layout) in layout.rows.iter().enumerate() {
+        let top_left = (x_start, y_position);
+        draw_row(
+            layout, row_index, renderer, top_left, typography, options, fonts,
+        );
+        y_position -= row_layout.height;
+    }
+}
+
+fn draw_row(
+    layout: &TableLayout,
+    row_index: usize,
+    renderer: &mut impl Renderer,
+    (x_start, row_top): (f32, f32),
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+    fonts: &Fonts,
+) {
+    let table = layout.table;
+    let last_row = table.rows.len().saturating_sub(1);
+    let borders = &table.borders;
+    let column_widths = &layout.column_widths;
+    let padding = typography.cell_padding;
+    let ascent: Mm = Pt(typography.font_size * CELL_TEXT_ASCENT).into();
+    let row = &table.rows[row_index];
+    let row_layout = &layout.rows[row_index];
+    let y_position = row_top - row_layout.height;
+    let bold_fonts;
+    let fonts = if row.header {
+        bold_fonts = fonts.all_bold();
+        &bold_fonts
+    } else {
+        fonts
+    };
+    let cells = || row.cells.iter().zip(&row_layout.cells);
+    let cell_x = |column: usize| x_start + column_widths[..column].iter().sum::<f32>();
+
+    // The whole row is filled first, for the borders of the cells next to a
+    // shaded cell to be drawn on top of it.
+    for (cell, cell_layout) in cells() {
+        if let Some(shading) = cell.shading {
+            let x = cell_x(cell_layout.column);
+            renderer.fill_rect([x, y_position, x + cell_layout.width, row_top], shading);
+        }
+    }
+
+    for (cell, cell_layout) in cells() {
+        let x = cell_x(cell_layout.column);
+        let text_width = cell_text_width(cell_layout.width, typography);
+        let content_x = x + padding;
+        // Room left in the row below the content of a shorter cell.
+        let free_height =
+            (row_layout.height - 2.0 * padding - cell_layout.content_height(typography)).max(0.0);
+        let content_y = row_top
+            - padding
+            - match cell.vertical_align {
+                CellVerticalAlign::Top => 0.0,
+                CellVerticalAlign::Center => free_height / 2.0,
+                CellVerticalAlign::Bottom => free_height,
+            };
+        let mut line_y = content_y - ascent.0;
+        for line in &cell_lines(cell, text_width, typography, options) {
+            draw_text_line(
+                renderer,
+                line,
+                content_x,
+                line_y,
+                text_width,
+                cell.alignment,
+                fonts,
+            );
+            line_y -= typography.line_height;
+        }
+
+        let mut content_top = content_y - cell_layout.line_count as f32 * typography.line_height;
+        for image in &cell_layout.images {
+            content_top -= CELL_IMAGE_GAP;
+            // Header rows can be drawn on several pages.
+            renderer.draw_image(
+                image.image.clone(),
+                (content_x, content_top - image.placement.height),
+                (image.placement.width, image.placement.height),
+            );
+            content_top -= image.placement.height;
+        }
+        for nested in &cell_layout.tables {
+            content_top -= CELL_IMAGE_GAP;
+            let nested_height = nested.height();
+            draw_table(
+                nested,
+                renderer,
+                content_x,
+                content_top,
+                typography,
+                options,
+                fonts,
+            );
+            content_top -= nested_height;
+        }
+
+        let top = cell.borders.top.unwrap_or(if row_index == 0 {
+            borders.top
+        } else {
+            borders.inside_horizontal
+        });
+        let bottom = cell.borders.bottom.unwrap_or(if row_index == last_row {
+            borders.bottom
+        } else {
+            borders.inside_horizontal
+        });
+        let left = cell.borders.left.unwrap_or(if cell_layout.column == 0 {
+            borders.left
+        } else {
+            borders.inside_vertical
+        });
+        let last_column = cell_layout.column + cell.column_span.max(1) >= column_widths.len();
+        let right = cell.borders.right.unwrap_or(if last_column {
+            borders.right
+        } else {
+            borders.inside_vertical
+        });
+
+        let x_end = x + cell_layout.width;
+        draw_border(renderer, top, (x, row_top), (x_end, row_top));
+        draw_border(renderer, bottom, (x, y_position), (x_end, y_position));
+        draw_border(renderer, left, (x, row_top), (x, y_position));
+        draw_border(renderer, right, (x_end, row_top), (x_end, y_position));
+    }
+}
+
+fn cell_text_width(column_width: f32, typography: &TypographyConfig) -> f32 {
+    (column_width - 2.0 * typography.cell_padding).max(0.0)
+}
+
+// The lines of the paragraphs of a cell, wrapped as in the body.
+fn cell_lines<'a>(
+    cell: &'a CellContent,
+    text_width: f32,
+    typography: &TypographyConfig,
+    options: &PdfOptions,
+) -> Vec<TextLine<'a>> {
+    let font_size = typography.font_size;
+    let hyphenate = cell.alignment == Alignment::Justify;
+    cell.paragraphs
+        .iter()
+        .flat_map(|runs| split_words(runs, font_size, options.preserve_spaces))
+        .flat_map(|words| wrap_words(words, font_size, text_width, 0.0, hyphenate))
+        .collect()
+}
+
+struct ImagePlacement {
+    width: f32,
+    height: f32,
+}
+
+// Images get the display size set in the document or, without one, their
+// natural size at the configured resolution, times `scale`. They are shrunk to
+// fit the available space but never enlarged, by a single factor for both
+// sides so that they keep their aspect ratio.
+fn place_image(
+    image: &ImageContent,
+    decoded: &DecodedImage,
+    dpi: f32,
+    scale: f32,
+    max_width: f32,
+    max_height: f32,
+) -> ImagePlacement {
+    let (pixel_width, pixel_height) = decoded.pixel_size;
+    let natural_width: Mm = pixel_width.into_pt(dpi).into();
+    let natural_height: Mm = pixel_height.into_pt(dpi).into();
+    let size = image.size.unwrap_or(ImageSize {
+        width: natural_width.0,
+        height: natural_height.0,
+    });
+
+    let fit = (max_width / size.width)
+        .min(max_height / size.height)
+        .min(scale);
+    debug!("Escala da imagem: {}", fit);
+
+    let width = size.width * fit;
+    let height = size.height * fit;
+    ImagePlacement { width, height }
+}
+
+struct CellImage {
+    image: RasterImage,
+    placement: ImagePlacement,
+}
+
+// Cell images are shrunk to fit the cell when too wide.
+fn decode_cell_images(
+    cell: &CellContent,
+    max_width: f32,
+    options: &PdfOptions,
+    decoded_images: &mut DecodedImages,
+) -> Result<Vec<CellImage>> {
+    let mut images = Vec::new();
+    for image in &cell.images {
+        let Some(decoded) = decoded_images.take(image) else {
+            if options.on_unsupported_image == UnsupportedImageMode::Fail
+                && !is_vector_image(&image.bytes)
+            {
+                return Err(unsupported_image(&image.bytes));
+            }
+            warn!(
+                "Skipping unsupported {} image in table cell",
+                image_format_name(&image.bytes)
+            );
+            continue;
+        };
+
+        let placement = place_image(
+            image,
+            &decoded,
+            options.image_dpi,
+            decoded_images.scale,
+            max_width,
+            f32::INFINITY,
+        );
+        images.push(CellImage {
+            image: decoded.image,
+            placement,
+        });
+    }
+    Ok(images)
+}
+
+fn draw_border(renderer: &mut impl Renderer, border: Border, from: (f32, f32), to: (f32, f32)) {
+    if !border.is_visible() {
+        return;
+    }
+    renderer.draw_line(from, to, border.width, RgbColor::BLACK);
 }
 
 fn draw_horizontal_line(
-    layer: &mut PdfLayerReference,
-    x: f32,
+    renderer: &mut impl Renderer,
+    x_start: f32,
+    x_end: f32,
     y: f32,
-    num_columns: usize,
-    column_width: f32,
+    color: RgbColor,
 ) {
-    let line = Line {
-        points: vec![
-            (Point::new(Mm(x), Mm(y)), false),
-            (
-                Point::new(Mm(x + num_columns as f32 * column_width), Mm(y)),
-                false,
-            ),
-        ],
-        is_closed: false,
-    };
-    layer.add_line(line);
-}
-
-fn draw_vertical_line(layer: &mut PdfLayerReference, x: f32, y_start: f32, y_end: f32) {
-    let line = Line {
-        points: vec![
-            (Point::new(Mm(x), Mm(y_start)), false),
-            (Point::new(Mm(x), Mm(y_end)), false),
-        ],
-        is_closed: false,
-    };
-    layer.add_line(line);
+    renderer.draw_line((x_start, y), (x_end, y), LINE_WIDTH, color);
 }
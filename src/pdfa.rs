@@ -0,0 +1,307 @@
+use crate::postprocess::{appearance, text_string};
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId, Result, Stream};
+
+// Info entries kept in a PDF/A file, each of which must match the XMP
+// metadata. The others printpdf writes, such as GTS_PDFXVersion, are dropped.
+const INFO_KEYS: [&str; 8] = [
+    "Title",
+    "Author",
+    "Subject",
+    "Keywords",
+    "Creator",
+    "Producer",
+    "CreationDate",
+    "ModDate",
+];
+
+// Annotation flag that makes an annotation print; PDF/A requires it.
+const ANNOTATION_PRINT: i64 = 4;
+
+// Sampled points of the sRGB tone curve in the ICC profile.
+const TONE_CURVE_POINTS: usize = 1024;
+
+// Turns the PDF written by printpdf into a PDF/A-2b file: sRGB output intent,
+// XMP metadata matching the Info dictionary, printable annotations and a named
+// optional content configuration. The fonts must already be embedded.
+pub fn make_pdfa(document: &mut Document) -> Result<()> {
+    // The header is followed by a comment of non-ASCII bytes, so that the file
+    // is recognised as binary. lopdf writes the version as is after "%PDF-".
+    document.version = "1.7\n%\u{e2}\u{e3}\u{cf}\u{d3}".to_string();
+
+    let info = clean_info(document)?;
+    let metadata = Stream::new(
+        Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Metadata".to_vec())),
+            ("Subtype", Object::Name(b"XML".to_vec())),
+        ]),
+        xmp_metadata(&info).into_bytes(),
+    )
+    // PDF/A does not allow the metadata stream to be filtered.
+    .with_compression(false);
+    let metadata_id = document.add_object(metadata);
+
+    let profile = Stream::new(
+        Dictionary::from_iter(vec![("N", Object::Integer(3))]),
+        srgb_profile(),
+    );
+    let profile_id = document.add_object(profile);
+    let output_intent = Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"OutputIntent".to_vec())),
+        ("S", Object::Name(b"GTS_PDFA1".to_vec())),
+        (
+            "OutputConditionIdentifier",
+            Object::string_literal("sRGB IEC61966-2.1"),
+        ),
+        ("Info", Object::string_literal("sRGB IEC61966-2.1")),
+        ("DestinationOutputProfile", Object::Reference(profile_id)),
+    ]);
+
+    let catalog = document.catalog_mut()?;
+    catalog.set("Metadata", Object::Reference(metadata_id));
+    catalog.set(
+        "OutputIntents",
+        Object::Array(vec![Object::Dictionary(output_intent)]),
+    );
+    if let Ok(Object::Dictionary(properties)) = catalog.get_mut(b"OCProperties") {
+        if let Ok(Object::Dictionary(config)) = properties.get_mut(b"D") {
+            config.set("Name", Object::string_literal("Default"));
+        }
+    }
+
+    let annotations: Vec<ObjectId> = document
+        .objects
+        .iter()
+        .filter(|(_, object)| object.as_dict().is_ok_and(|dict| dict.type_is(b"Annot")))
+        .map(|(&id, _)| id)
+        .collect();
+    for id in annotations {
+        let annotation = document.get_dictionary_mut(id)?;
+        annotation.set("F", Object::Integer(ANNOTATION_PRINT));
+        if annotation.has(b"AP") {
+            continue;
+        }
+        // Link annotations have no appearance; PDF/A wants one, even one that
+        // draws nothing. It is not left empty, as --optimize drops empty
+        // streams.
+        let rect: Vec<f32> = match annotation.get(b"Rect").and_then(Object::as_array) {
+            Ok(rect) => rect
+                .iter()
+                .filter_map(|value| value.as_float().ok())
+                .collect(),
+            Err(_) => continue,
+        };
+        let [left, bottom, right, top] = rect[..] else {
+            continue;
+        };
+        let empty = appearance((right - left).abs(), (top - bottom).abs(), b"q Q".to_vec());
+        let appearance_id = document.add_object(empty);
+        document.get_dictionary_mut(id)?.set(
+            "AP",
+            Dictionary::from_iter(vec![("N", Object::Reference(appearance_id))]),
+        );
+    }
+    Ok(())
+}
+
+// Keeps the non-empty Info entries PDF/A has an XMP equivalent for, stored as
+// text strings, and returns them as (key, text) pairs.
+fn clean_info(document: &mut Document) -> Result<Vec<(&'static str, String)>> {
+    let info_id = document.trailer.get(b"Info")?.as_reference()?;
+    let info = document.get_dictionary_mut(info_id)?;
+    let entries: Vec<(&'static str, String)> = INFO_KEYS
+        .iter()
+        .filter_map(|&key| {
+            let bytes = info.get(key.as_bytes()).and_then(Object::as_str).ok()?;
+            let text = String::from_utf8_lossy(bytes).into_owned();
+            (!text.is_empty()).then_some((key, text))
+        })
+        .collect();
+
+    *info = Dictionary::new();
+    for (key, text) in &entries {
+        info.set(*key, text_string(text));
+    }
+    Ok(entries)
+}
+
+fn xmp_metadata(info: &[(&str, String)]) -> String {
+    let value = |key: &str| {
+        info.iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, text)| xml_escape(text))
+    };
+
+    let mut properties = String::new();
+    let alternative = |name: &str, text: String| {
+        format!(
+            "<{0}><rdf:Alt><rdf:li xml:lang=\"x-default\">{1}</rdf:li></rdf:Alt></{0}>\n",
+            name, text
+        )
+    };
+    if let Some(title) = value("Title") {
+        properties.push_str(&alternative("dc:title", title));
+    }
+    if let Some(author) = value("Author") {
+        properties.push_str(&format!(
+            "<dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>\n",
+            author
+        ));
+    }
+    if let Some(subject) = value("Subject") {
+        properties.push_str(&alternative("dc:description", subject));
+    }
+    let simple = [
+        ("Keywords", "pdf:Keywords"),
+        ("Producer", "pdf:Producer"),
+        ("Creator", "xmp:CreatorTool"),
+    ];
+    for (key, name) in simple {
+        if let Some(text) = value(key) {
+            properties.push_str(&format!("<{0}>{1}</{0}>\n", name, text));
+        }
+    }
+    for (key, name) in [
+        ("CreationDate", "xmp:CreateDate"),
+        ("ModDate", "xmp:ModifyDate"),
+    ] {
+        if let Some(date) = value(key).as_deref().and_then(xmp_date) {
+            properties.push_str(&format!("<{0}>{1}</{0}>\n", name, date));
+        }
+    }
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         <rdf:Description rdf:about=\"\" \
+         xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+         xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\" \
+         xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">\n\
+         <pdfaid:part>2</pdfaid:part>\n\
+         <pdfaid:conformance>B</pdfaid:conformance>\n\
+         {}\
+         </rdf:Description>\n\
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>",
+        properties
+    )
+}
+
+// "D:20240131120000+01'00'" to "2024-01-31T12:00:00+01:00".
+fn xmp_date(date: &str) -> Option<String> {
+    let digits = date.strip_prefix("D:")?;
+    if digits.len() < 14 || !digits.is_char_boundary(14) {
+        return None;
+    }
+    let (time, zone) = digits.split_at(14);
+    if !time.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let zone = match zone.replace('\'', "").as_str() {
+        "" | "Z" => "Z".to_string(),
+        offset if offset.len() == 5 => format!("{}:{}", &offset[..3], &offset[3..]),
+        _ => return None,
+    };
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}{}",
+        &time[..4],
+        &time[4..6],
+        &time[6..8],
+        &time[8..10],
+        &time[10..12],
+        &time[12..14],
+        zone
+    ))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// A minimal ICC version 2 display profile for sRGB: the D50-adapted primaries
+// and the sRGB tone curve, the same for the three channels.
+fn srgb_profile() -> Vec<u8> {
+    let xyz = |values: [f64; 3]| {
+        let mut data = b"XYZ \0\0\0\0".to_vec();
+        for value in values {
+            data.extend(((value * 65536.0).round() as i32).to_be_bytes());
+        }
+        data
+    };
+    let text = |text: &str| {
+        let mut data = b"text\0\0\0\0".to_vec();
+        data.extend(text.bytes());
+        data.push(0);
+        data
+    };
+
+    let mut description = b"desc\0\0\0\0".to_vec();
+    let name = b"sRGB IEC61966-2.1\0";
+    description.extend((name.len() as u32).to_be_bytes());
+    description.extend(name);
+    // Empty Unicode and ScriptCode descriptions.
+    description.extend([0; 8]);
+    description.extend([0; 3]);
+    description.extend([0; 67]);
+
+    let mut curve = b"curv\0\0\0\0".to_vec();
+    curve.extend((TONE_CURVE_POINTS as u32).to_be_bytes());
+    for index in 0..TONE_CURVE_POINTS {
+        let encoded = index as f64 / (TONE_CURVE_POINTS - 1) as f64;
+        let linear = if encoded <= 0.04045 {
+            encoded / 12.92
+        } else {
+            ((encoded + 0.055) / 1.055).powf(2.4)
+        };
+        curve.extend(((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    let tags: [(&[u8; 4], Vec<u8>); 9] = [
+        (b"desc", description),
+        (b"cprt", text("No copyright, use freely")),
+        (b"wtpt", xyz([0.9642, 1.0, 0.8249])),
+        (b"rXYZ", xyz([0.4361, 0.2225, 0.0139])),
+        (b"gXYZ", xyz([0.3851, 0.7169, 0.0971])),
+        (b"bXYZ", xyz([0.1431, 0.0606, 0.7141])),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+    ];
+
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let data_start = 128 + 4 + 12 * tags.len();
+    for (signature, tag) in &tags {
+        table.extend(*signature);
+        table.extend(((data_start + data.len()) as u32).to_be_bytes());
+        table.extend((tag.len() as u32).to_be_bytes());
+        data.extend(tag);
+        // Tags start on a four-byte boundary.
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+
+    let size = 128 + table.len() + data.len();
+    let mut profile = Vec::with_capacity(size);
+    profile.extend((size as u32).to_be_bytes());
+    profile.extend([0; 4]);
+    profile.extend(0x0210_0000u32.to_be_bytes());
+    profile.extend(b"mntrRGB XYZ ");
+    // Creation date: 2000-01-01 00:00:00.
+    for field in [2000u16, 1, 1, 0, 0, 0] {
+        profile.extend(field.to_be_bytes());
+    }
+    profile.extend(b"acsp");
+    profile.extend([0; 24]);
+    // Perceptual rendering intent and the D50 illuminant.
+    profile.extend([0; 4]);
+    profile.extend(&xyz([0.9642, 1.0, 0.8249])[8..]);
+    profile.resize(128, 0);
+    profile.extend(table);
+    profile.extend(data);
+    profile
+}
@@ -0,0 +1,324 @@
+use printpdf::lopdf::{Dictionary, Document, Object, ObjectId, Result, Stream, StringFormat};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+// printpdf embeds an image XObject every time an image is drawn, so a logo
+// repeated on every page is stored once per page. Identical image streams are
+// merged into the first one and every reference is pointed at it.
+pub fn deduplicate_images(document: &mut Document) -> usize {
+    let mut seen: HashMap<u64, Vec<ObjectId>> = HashMap::new();
+    let mut replacements: HashMap<ObjectId, ObjectId> = HashMap::new();
+
+    for (&id, object) in &document.objects {
+        let Object::Stream(stream) = object else {
+            continue;
+        };
+        let is_image = stream
+            .dict
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .is_ok_and(|subtype| subtype == b"Image");
+        if !is_image {
+            continue;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        stream.content.hash(&mut hasher);
+        format!("{:?}", stream.dict).hash(&mut hasher);
+        let candidates = seen.entry(hasher.finish()).or_default();
+
+        let original = candidates.iter().copied().find(|candidate| {
+            matches!(
+                document.objects.get(candidate),
+                Some(Object::Stream(other))
+                    if other.content == stream.content
+                        && format!("{:?}", other.dict) == format!("{:?}", stream.dict)
+            )
+        });
+        match original {
+            Some(original) => {
+                replacements.insert(id, original);
+            }
+            None => candidates.push(id),
+        }
+    }
+
+    if replacements.is_empty() {
+        return 0;
+    }
+
+    for duplicate in replacements.keys() {
+        document.objects.remove(duplicate);
+    }
+    for object in document.objects.values_mut() {
+        replace_references(object, &replacements);
+    }
+    replacements.len()
+}
+
+fn replace_references(object: &mut Object, replacements: &HashMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            if let Some(original) = replacements.get(id) {
+                *id = *original;
+            }
+        }
+        Object::Array(items) => {
+            for item in items {
+                replace_references(item, replacements);
+            }
+        }
+        Object::Dictionary(dict) => {
+            for (_, value) in dict.iter_mut() {
+                replace_references(value, replacements);
+            }
+        }
+        Object::Stream(stream) => {
+            for (_, value) in stream.dict.iter_mut() {
+                replace_references(value, replacements);
+            }
+        }
+        _ => {}
+    }
+}
+
+// printpdf offers no way to set the fill alpha, so a graphics state with the
+// given name is added to every page here, for content streams that select it
+// with the `gs` operator.
+pub fn add_fill_alpha(document: &mut Document, name: &str, alpha: f32) -> Result<()> {
+    let mut state = Dictionary::new();
+    state.set("Type", Object::Name(b"ExtGState".to_vec()));
+    state.set("ca", Object::Real(alpha));
+    let state_id = document.add_object(state);
+
+    let pages: Vec<ObjectId> = document.get_pages().into_values().collect();
+    for page in pages {
+        document.add_graphics_state(page, name, state_id)?;
+    }
+    Ok(())
+}
+
+// Sets the size in points of the page at `index`, from 0, on every box
+// printpdf writes for it.
+pub fn set_page_size(document: &mut Document, index: usize, width: f32, height: f32) -> Result<()> {
+    let Some(&page_id) = document.get_pages().values().nth(index) else {
+        return Ok(());
+    };
+    let page = document.get_dictionary_mut(page_id)?;
+    let bounds = vec![0.0, 0.0, width, height];
+    let bounds: Vec<Object> = bounds.into_iter().map(Object::Real).collect();
+    for name in ["MediaBox", "TrimBox", "CropBox"] {
+        page.set(name, bounds.clone());
+    }
+    Ok(())
+}
+
+// Flate-compresses every stream that is not compressed yet, page contents
+// included (printpdf leaves those uncompressed), and drops the objects nothing
+// refers to anymore. Returns how many bytes the streams shrank by.
+pub fn optimize(document: &mut Document) -> usize {
+    let stream_size = |document: &Document| -> usize {
+        document
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .map(|stream| stream.content.len())
+            .sum()
+    };
+    let before = stream_size(document);
+    document.prune_objects();
+    document.delete_zero_length_streams();
+    document.compress();
+    before.saturating_sub(stream_size(document))
+}
+
+// A note shown as an icon on a page, which opens a popup with its text.
+pub struct TextAnnotation {
+    // Index of the page in the document, from 0.
+    pub page: usize,
+    // Left, bottom, right and top of the icon in points.
+    pub rect: [f32; 4],
+    pub author: Option<String>,
+    pub text: String,
+}
+
+// printpdf only writes link annotations, so text annotations are added to the
+// pages here.
+pub fn add_text_annotations(document: &mut Document, annotations: &[TextAnnotation]) -> Result<()> {
+    let pages: Vec<ObjectId> = document.get_pages().into_values().collect();
+    for annotation in annotations {
+        let Some(&page_id) = pages.get(annotation.page) else {
+            continue;
+        };
+        let mut dictionary = Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"Annot".to_vec())),
+            ("Subtype", Object::Name(b"Text".to_vec())),
+            (
+                "Rect",
+                Object::Array(annotation.rect.iter().map(|&v| Object::Real(v)).collect()),
+            ),
+            ("Contents", text_string(&annotation.text)),
+            ("Name", Object::Name(b"Comment".to_vec())),
+            (
+                "C",
+                Object::Array(vec![
+                    Object::Real(1.0),
+                    Object::Real(0.8),
+                    Object::Real(0.0),
+                ]),
+            ),
+            ("Open", Object::Boolean(false)),
+        ]);
+        if let Some(author) = &annotation.author {
+            dictionary.set("T", text_string(author));
+        }
+        // A yellow square, so that every viewer draws the icon the same way.
+        let [left, bottom, right, top] = annotation.rect;
+        let (width, height) = (right - left, top - bottom);
+        let icon = format!(
+            "1 0.8 0 rg 0.4 0.4 0.4 RG 0.5 w 0.25 0.25 {} {} re B",
+            width - 0.5,
+            height - 0.5
+        );
+        let appearance_id = document.add_object(appearance(width, height, icon.into_bytes()));
+        dictionary.set(
+            "AP",
+            Dictionary::from_iter(vec![("N", Object::Reference(appearance_id))]),
+        );
+        let annotation_id = document.add_object(dictionary);
+
+        let annots = document
+            .get_dictionary(page_id)?
+            .get(b"Annots")
+            .ok()
+            .cloned();
+        match annots {
+            Some(Object::Reference(array_id)) => document
+                .get_object_mut(array_id)?
+                .as_array_mut()?
+                .push(Object::Reference(annotation_id)),
+            Some(Object::Array(mut array)) => {
+                array.push(Object::Reference(annotation_id));
+                document.get_dictionary_mut(page_id)?.set("Annots", array);
+            }
+            _ => document
+                .get_dictionary_mut(page_id)?
+                .set("Annots", vec![Object::Reference(annotation_id)]),
+        }
+    }
+    Ok(())
+}
+
+// A form XObject of the given size in points, drawn for an annotation.
+pub fn appearance(width: f32, height: f32, content: Vec<u8>) -> Stream {
+    let bounds = vec![0.0, 0.0, width, height];
+    Stream::new(
+        Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"XObject".to_vec())),
+            ("Subtype", Object::Name(b"Form".to_vec())),
+            (
+                "BBox",
+                Object::Array(bounds.into_iter().map(Object::Real).collect()),
+            ),
+        ]),
+        content,
+    )
+}
+
+// Attaches a file to the document, listed in the catalog's EmbeddedFiles name
+// tree and marked as the source of the PDF.
+pub fn embed_file(
+    document: &mut Document,
+    name: &str,
+    mime_type: &str,
+    bytes: &[u8],
+) -> Result<()> {
+    let file = Stream::new(
+        Dictionary::from_iter(vec![
+            ("Type", Object::Name(b"EmbeddedFile".to_vec())),
+            ("Subtype", Object::Name(mime_type.as_bytes().to_vec())),
+            (
+                "Params",
+                Object::Dictionary(Dictionary::from_iter(vec![(
+                    "Size",
+                    Object::Integer(bytes.len() as i64),
+                )])),
+            ),
+        ]),
+        bytes.to_vec(),
+    );
+    let file_id = document.add_object(file);
+    let file_spec_id = document.add_object(Dictionary::from_iter(vec![
+        ("Type", Object::Name(b"Filespec".to_vec())),
+        ("F", text_string(name)),
+        ("UF", text_string(name)),
+        (
+            "EF",
+            Object::Dictionary(Dictionary::from_iter(vec![(
+                "F",
+                Object::Reference(file_id),
+            )])),
+        ),
+        ("AFRelationship", Object::Name(b"Source".to_vec())),
+    ]));
+
+    let catalog = document.catalog_mut()?;
+    catalog.set("AF", vec![Object::Reference(file_spec_id)]);
+    let embedded_files = Dictionary::from_iter(vec![(
+        "Names",
+        Object::Array(vec![text_string(name), Object::Reference(file_spec_id)]),
+    )]);
+    // The name dictionary may be an object of its own, whose destinations
+    // and scripts are kept all the same.
+    let names_id = catalog.get(b"Names").and_then(Object::as_reference).ok();
+    let names = match names_id {
+        Some(id) => document.get_object_mut(id).and_then(Object::as_dict_mut),
+        None => catalog.get_mut(b"Names").and_then(Object::as_dict_mut),
+    };
+    match names {
+        Ok(names) => names.set("EmbeddedFiles", embedded_files),
+        Err(_) => document.catalog_mut()?.set(
+            "Names",
+            Dictionary::from_iter(vec![("EmbeddedFiles", Object::Dictionary(embedded_files))]),
+        ),
+    }
+    Ok(())
+}
+
+// Numbers the pages in the catalog's PageLabels number tree. Each range is
+// given by the index of its first page, counted from 0, and its numbering
+// style (D, r, R, a or A), and is numbered from 1. Pages before the first
+// range are numbered in arabic; ranges past the last page are left out.
+pub fn add_page_labels(document: &mut Document, ranges: &[(usize, &str)]) -> Result<()> {
+    let pages = document.get_pages().len();
+    let style = |name: &str| {
+        Object::Dictionary(Dictionary::from_iter(vec![(
+            "S",
+            Object::Name(name.as_bytes().to_vec()),
+        )]))
+    };
+    let mut nums = Vec::new();
+    if ranges.first().is_some_and(|&(first, _)| first > 0) {
+        nums.extend([Object::Integer(0), style("D")]);
+    }
+    for &(first, name) in ranges.iter().filter(|&&(first, _)| first < pages) {
+        nums.extend([Object::Integer(first as i64), style(name)]);
+    }
+    document.catalog_mut()?.set(
+        "PageLabels",
+        Dictionary::from_iter(vec![("Nums", Object::Array(nums))]),
+    );
+    Ok(())
+}
+
+// A PDF text string: ASCII text as is, anything else in UTF-16BE with a byte
+// order mark.
+pub fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        return Object::string_literal(text);
+    }
+    let mut bytes = vec![0xFE, 0xFF];
+    bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
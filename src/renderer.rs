@@ -0,0 +1,427 @@
+use log::debug;
+use printpdf::path::PaintMode;
+use printpdf::*;
+use std::collections::HashMap;
+
+use crate::error::Result;
+use crate::pdf_writer::PdfOptions;
+use crate::postprocess::{add_fill_alpha, set_page_size};
+use crate::utils::{DocumentMetadata, FontFamily, PageSize, RgbColor};
+
+// The watermark is set in light gray and made translucent through a graphics
+// state added to every page once the PDF is written.
+const WATERMARK_GRAY: f32 = 0.5;
+const WATERMARK_ALPHA: f32 = 0.3;
+const WATERMARK_STATE: &str = "GSWatermark";
+
+/// A font family in its regular or bold face.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FontFace {
+    pub family: FontFamily,
+    pub bold: bool,
+}
+
+/// An image decoded for drawing: `width` by `height` pixels, row by row from
+/// the top, with `bits_per_component` bits (1, 8 or 16) for each component of
+/// its color space. Transparency is either a component of the color space or
+/// kept apart in `alpha`, one 8-bit opacity for each pixel.
+#[derive(Debug, Clone)]
+pub struct RasterImage {
+    pub width: usize,
+    pub height: usize,
+    pub color_space: RasterColorSpace,
+    pub bits_per_component: u8,
+    pub pixels: Vec<u8>,
+    pub alpha: Option<Vec<u8>>,
+}
+
+/// The components of each pixel of a [`RasterImage`], in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RasterColorSpace {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+    Cmyk,
+}
+
+impl RasterImage {
+    // Takes the pixels of an image decoded by printpdf. Compressed or
+    // paletted images are never produced by the decoders used.
+    pub(crate) fn from_xobject(image: ImageXObject) -> Option<RasterImage> {
+        if image.image_filter.is_some() {
+            return None;
+        }
+        let color_space = match image.color_space {
+            ColorSpace::Greyscale => RasterColorSpace::Gray,
+            ColorSpace::GreyscaleAlpha => RasterColorSpace::GrayAlpha,
+            ColorSpace::Rgb => RasterColorSpace::Rgb,
+            ColorSpace::Rgba => RasterColorSpace::Rgba,
+            ColorSpace::Cmyk => RasterColorSpace::Cmyk,
+            ColorSpace::Palette => return None,
+        };
+        let bits_per_component = match image.bits_per_component {
+            ColorBits::Bit1 => 1,
+            ColorBits::Bit8 => 8,
+            ColorBits::Bit16 => 16,
+        };
+        Some(RasterImage {
+            width: image.width.0,
+            height: image.height.0,
+            color_space,
+            bits_per_component,
+            pixels: image.image_data,
+            alpha: image
+                .smask
+                .map(|mask| mask.matte.into_iter().map(|alpha| alpha as u8).collect()),
+        })
+    }
+
+    fn into_xobject(self) -> ImageXObject {
+        let color_space = match self.color_space {
+            RasterColorSpace::Gray => ColorSpace::Greyscale,
+            RasterColorSpace::GrayAlpha => ColorSpace::GreyscaleAlpha,
+            RasterColorSpace::Rgb => ColorSpace::Rgb,
+            RasterColorSpace::Rgba => ColorSpace::Rgba,
+            RasterColorSpace::Cmyk => ColorSpace::Cmyk,
+        };
+        let bits_per_component = match self.bits_per_component {
+            1 => ColorBits::Bit1,
+            16 => ColorBits::Bit16,
+            _ => ColorBits::Bit8,
+        };
+        let smask = self.alpha.map(|alpha| SMask {
+            width: self.width as i64,
+            height: self.height as i64,
+            interpolate: false,
+            bits_per_component: 8,
+            matte: alpha.into_iter().map(i64::from).collect(),
+        });
+        ImageXObject {
+            width: Px(self.width),
+            height: Px(self.height),
+            color_space,
+            bits_per_component,
+            interpolate: true,
+            image_data: self.pixels,
+            image_filter: None,
+            smask,
+            clipping_bbox: None,
+        }
+    }
+}
+
+/// Draws the laid out document. Positions are in millimeters from the bottom
+/// left corner of the page, font sizes and line widths in points. Everything
+/// is drawn on the current page, which is the first one to begin with.
+pub trait Renderer {
+    /// Starts a new page of `size` and makes it the current one. Pages after
+    /// a section break can differ in size from the first one, such as a
+    /// landscape section in a portrait document.
+    fn new_page(&mut self, size: PageSize);
+
+    /// Changes the size of the current page, which nothing was drawn on yet,
+    /// for a section starting right after a page break.
+    fn resize_page(&mut self, size: PageSize);
+
+    /// Makes an earlier page, counted from 0, the current one, for content
+    /// drawn over the body once it is laid out, such as the header and footer.
+    /// `name` says what the content is.
+    fn begin_layer(&mut self, page: usize, name: &str);
+
+    /// Draws text with its baseline starting at `origin`.
+    fn draw_text(
+        &mut self,
+        text: &str,
+        face: FontFace,
+        font_size: f32,
+        origin: (f32, f32),
+        color: RgbColor,
+    );
+
+    fn draw_line(&mut self, from: (f32, f32), to: (f32, f32), width: f32, color: RgbColor);
+
+    /// Outlines the rectangle given by its left, bottom, right and top.
+    fn stroke_rect(&mut self, rect: [f32; 4], width: f32, color: RgbColor);
+
+    fn fill_rect(&mut self, rect: [f32; 4], color: RgbColor);
+
+    /// Draws an image stretched to `size`, with its bottom left corner at
+    /// `origin`.
+    fn draw_image(&mut self, image: RasterImage, origin: (f32, f32), size: (f32, f32));
+
+    /// Makes the rectangle a link to `url`.
+    fn add_link(&mut self, rect: [f32; 4], url: &str);
+
+    /// Draws text light and translucent across the page, rotated
+    /// counterclockwise by `angle` radians around `origin`.
+    fn draw_watermark(
+        &mut self,
+        text: &str,
+        face: FontFace,
+        font_size: f32,
+        origin: (f32, f32),
+        angle: f32,
+    );
+}
+
+// The default renderer, writing a PDF with printpdf.
+pub struct PdfRenderer {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    pages: Vec<PdfPageIndex>,
+    // The regular and bold font of each family in use.
+    fonts: HashMap<FontFamily, (IndirectFontRef, IndirectFontRef)>,
+    // Colors and line width of the current layer, set again only when they
+    // change.
+    fill_color: RgbColor,
+    outline_color: RgbColor,
+    outline_width: f32,
+    has_watermark: bool,
+    // printpdf can't change the size of a page once added, so pages resized
+    // for a section get their new size once the PDF is written.
+    resized_pages: Vec<(usize, PageSize)>,
+}
+
+impl PdfRenderer {
+    // Loads the fonts of the given families: the built-in ones, or the
+    // TrueType fonts of the options for all of them. The first page is of
+    // `page_size`.
+    pub fn new(
+        metadata: &DocumentMetadata,
+        families: &[FontFamily],
+        page_size: PageSize,
+        options: &PdfOptions,
+    ) -> Result<PdfRenderer> {
+        let (doc, page1, layer1) = PdfDocument::new(
+            metadata.title.as_deref().unwrap_or("Converted Document"),
+            Mm(page_size.width),
+            Mm(page_size.height),
+            "Layer 1",
+        );
+        let doc = doc.with_keywords(metadata.keywords.clone());
+        let doc = match &metadata.author {
+            Some(author) => doc.with_author(author.as_str()),
+            None => doc,
+        };
+        let doc = match &metadata.subject {
+            Some(subject) => doc.with_subject(subject.as_str()),
+            None => doc,
+        };
+
+        let mut fonts = HashMap::new();
+        // printpdf embeds a subset of each TrueType font, with only the glyphs
+        // drawn on some page, when the PDF is written. A large CJK font used
+        // for a few hundred characters then takes little room.
+        if let Some(font) = &options.font {
+            debug!("Embedding the TrueType font for all families");
+            let regular = doc.add_external_font(font.as_slice())?;
+            let bold = match &options.bold_font {
+                Some(bold) => doc.add_external_font(bold.as_slice())?,
+                None => regular.clone(),
+            };
+            for &family in families {
+                fonts.insert(family, (regular.clone(), bold.clone()));
+            }
+        }
+        for &family in families {
+            if fonts.contains_key(&family) {
+                continue;
+            }
+            debug!("Adding built-in {:?} font", family);
+            let (regular, bold) = builtin_faces(family);
+            fonts.insert(
+                family,
+                (doc.add_builtin_font(regular)?, doc.add_builtin_font(bold)?),
+            );
+        }
+
+        Ok(PdfRenderer {
+            layer: doc.get_page(page1).get_layer(layer1),
+            doc,
+            pages: vec![page1],
+            fonts,
+            fill_color: RgbColor::BLACK,
+            outline_color: RgbColor::BLACK,
+            outline_width: 1.0,
+            has_watermark: false,
+            resized_pages: Vec::new(),
+        })
+    }
+
+    // The written PDF, loaded for post-processing.
+    pub fn into_pdf(self) -> Result<lopdf::Document> {
+        let mut pdf = lopdf::Document::load_mem(&self.doc.save_to_bytes()?)?;
+        if self.has_watermark {
+            add_fill_alpha(&mut pdf, WATERMARK_STATE, WATERMARK_ALPHA)?;
+        }
+        for &(page, size) in &self.resized_pages {
+            let width: Pt = Mm(size.width).into();
+            let height: Pt = Mm(size.height).into();
+            set_page_size(&mut pdf, page, width.0, height.0)?;
+        }
+        Ok(pdf)
+    }
+
+    fn set_layer(&mut self, layer: PdfLayerReference) {
+        self.layer = layer;
+        self.fill_color = RgbColor::BLACK;
+        self.outline_color = RgbColor::BLACK;
+        self.outline_width = 1.0;
+    }
+
+    fn font(&self, face: FontFace) -> &IndirectFontRef {
+        let (regular, bold) = &self.fonts[&face.family];
+        if face.bold {
+            bold
+        } else {
+            regular
+        }
+    }
+
+    fn set_fill_color(&mut self, color: RgbColor) {
+        if self.fill_color != color {
+            self.layer.set_fill_color(pdf_color(color));
+            self.fill_color = color;
+        }
+    }
+
+    fn set_outline(&mut self, width: f32, color: RgbColor) {
+        if self.outline_width != width {
+            self.layer.set_outline_thickness(width);
+            self.outline_width = width;
+        }
+        if self.outline_color != color {
+            self.layer.set_outline_color(pdf_color(color));
+            self.outline_color = color;
+        }
+    }
+}
+
+impl Renderer for PdfRenderer {
+    fn new_page(&mut self, size: PageSize) {
+        let (page, layer) = self
+            .doc
+            .add_page(Mm(size.width), Mm(size.height), "New Page");
+        self.pages.push(page);
+        self.set_layer(self.doc.get_page(page).get_layer(layer));
+    }
+
+    fn resize_page(&mut self, size: PageSize) {
+        self.resized_pages.push((self.pages.len() - 1, size));
+    }
+
+    fn begin_layer(&mut self, page: usize, name: &str) {
+        let layer = self.doc.get_page(self.pages[page]).add_layer(name);
+        self.set_layer(layer);
+    }
+
+    fn draw_text(
+        &mut self,
+        text: &str,
+        face: FontFace,
+        font_size: f32,
+        (x, y): (f32, f32),
+        color: RgbColor,
+    ) {
+        self.set_fill_color(color);
+        let font = self.font(face);
+        self.layer.use_text(text, font_size, Mm(x), Mm(y), font);
+    }
+
+    fn draw_line(&mut self, from: (f32, f32), to: (f32, f32), width: f32, color: RgbColor) {
+        self.set_outline(width, color);
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(from.0), Mm(from.1)), false),
+                (Point::new(Mm(to.0), Mm(to.1)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    fn stroke_rect(&mut self, rect: [f32; 4], width: f32, color: RgbColor) {
+        self.set_outline(width, color);
+        self.layer
+            .add_rect(pdf_rect(rect).with_mode(PaintMode::Stroke));
+    }
+
+    fn fill_rect(&mut self, rect: [f32; 4], color: RgbColor) {
+        self.set_fill_color(color);
+        self.layer
+            .add_rect(pdf_rect(rect).with_mode(PaintMode::Fill));
+    }
+
+    fn draw_image(&mut self, image: RasterImage, (x, y): (f32, f32), (width, height): (f32, f32)) {
+        // At 72 dpi, a pixel of the image is a point.
+        let natural_width: Mm = Pt(image.width as f32).into();
+        let natural_height: Mm = Pt(image.height as f32).into();
+        Image::from(image.into_xobject()).add_to_layer(
+            self.layer.clone(),
+            ImageTransform {
+                translate_x: Some(Mm(x)),
+                translate_y: Some(Mm(y)),
+                scale_x: Some(width / natural_width.0),
+                scale_y: Some(height / natural_height.0),
+                dpi: Some(72.0),
+                ..Default::default()
+            },
+        );
+    }
+
+    fn add_link(&mut self, rect: [f32; 4], url: &str) {
+        self.layer.add_link_annotation(LinkAnnotation::new(
+            pdf_rect(rect),
+            Some(BorderArray::Solid([0.0, 0.0, 0.0])),
+            Some(ColorArray::Transparent),
+            Actions::uri(url.to_string()),
+            None,
+        ));
+    }
+
+    fn draw_watermark(
+        &mut self,
+        text: &str,
+        face: FontFace,
+        font_size: f32,
+        (x, y): (f32, f32),
+        angle: f32,
+    ) {
+        self.has_watermark = true;
+        let font = self.font(face);
+        let layer = &self.layer;
+        layer.save_graphics_state();
+        layer.add_operation(lopdf::content::Operation::new(
+            "gs",
+            vec![lopdf::Object::Name(WATERMARK_STATE.as_bytes().to_vec())],
+        ));
+        layer.set_fill_color(Color::Greyscale(Greyscale::new(WATERMARK_GRAY, None)));
+        layer.begin_text_section();
+        layer.set_font(font, font_size);
+        layer.set_text_matrix(TextMatrix::TranslateRotate(
+            Mm(x).into(),
+            Mm(y).into(),
+            angle.to_degrees(),
+        ));
+        layer.write_text(text, font);
+        layer.end_text_section();
+        layer.restore_graphics_state();
+    }
+}
+
+// The regular and bold faces of a font family.
+fn builtin_faces(family: FontFamily) -> (BuiltinFont, BuiltinFont) {
+    match family {
+        FontFamily::Helvetica => (BuiltinFont::Helvetica, BuiltinFont::HelveticaBold),
+        FontFamily::Times => (BuiltinFont::TimesRoman, BuiltinFont::TimesBold),
+        FontFamily::Courier => (BuiltinFont::Courier, BuiltinFont::CourierBold),
+    }
+}
+
+fn pdf_color(color: RgbColor) -> Color {
+    Color::Rgb(Rgb::new(color.r, color.g, color.b, None))
+}
+
+fn pdf_rect([left, bottom, right, top]: [f32; 4]) -> Rect {
+    Rect::new(Mm(left), Mm(bottom), Mm(right), Mm(top))
+}
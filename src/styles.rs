@@ -0,0 +1,98 @@
+use docx_rust::document::{Paragraph, Run};
+use docx_rust::formatting::{CharacterProperty, ParagraphProperty};
+use docx_rust::styles::{Style, Styles};
+use std::collections::HashMap;
+
+use crate::utils::FontMap;
+
+// Style chains deeper than this are cut, which also stops cycles.
+const MAX_STYLE_DEPTH: usize = 16;
+
+// Named styles from styles.xml. A paragraph or run takes the properties it does
+// not set itself from its style, then from the styles that one is based on.
+// The document defaults (w:docDefaults) are not applied: the typography
+// options stand in for them.
+pub struct StyleSheet<'a> {
+    styles: HashMap<&'a str, &'a Style<'a>>,
+    // Style id to the id of the style it is based on.
+    bases: HashMap<String, String>,
+    // Font names mapped to built-in fonts by the caller.
+    pub fonts: &'a FontMap,
+}
+
+impl<'a> StyleSheet<'a> {
+    pub fn new(styles: &'a Styles<'a>, bases: HashMap<String, String>, fonts: &'a FontMap) -> Self {
+        StyleSheet {
+            styles: styles
+                .styles
+                .iter()
+                .map(|style| (style.style_id.as_ref(), style))
+                .collect(),
+            bases,
+            fonts,
+        }
+    }
+
+    // The style with the given id followed by the styles it is based on.
+    pub fn chain(&self, id: Option<&str>) -> Vec<&'a Style<'a>> {
+        let mut chain: Vec<&'a Style<'a>> = Vec::new();
+        let mut next = id;
+        while let Some(id) = next {
+            let Some(&style) = self.styles.get(id) else {
+                break;
+            };
+            if chain.len() == MAX_STYLE_DEPTH || chain.iter().any(|seen| std::ptr::eq(*seen, style))
+            {
+                break;
+            }
+            chain.push(style);
+            next = self.bases.get(id).map(String::as_str);
+        }
+        chain
+    }
+
+    // Paragraph properties that apply to a paragraph, nearest first.
+    pub fn paragraph_properties<'p>(
+        &'p self,
+        paragraph: &'p Paragraph,
+    ) -> Vec<&'p ParagraphProperty<'p>> {
+        let inline = paragraph.property.as_ref();
+        let styles = self.chain(paragraph_style_id(paragraph));
+        inline
+            .into_iter()
+            .chain(
+                styles
+                    .into_iter()
+                    .filter_map(|style| style.paragraph.as_ref()),
+            )
+            .collect()
+    }
+
+    // Character properties that apply to a run, nearest first: its own, those
+    // of its character style, then those of its paragraph's style.
+    pub fn run_properties<'p>(
+        &'p self,
+        paragraph: Option<&'p Paragraph>,
+        run: &'p Run,
+    ) -> Vec<&'p CharacterProperty<'p>> {
+        let inline = run.property.as_ref();
+        let run_style = inline
+            .and_then(|property| property.style_id.as_ref())
+            .map(|style_id| style_id.value.as_ref());
+        let paragraph_style = paragraph.and_then(paragraph_style_id);
+        inline
+            .into_iter()
+            .chain(
+                self.chain(run_style)
+                    .into_iter()
+                    .chain(self.chain(paragraph_style))
+                    .filter_map(|style| style.character.as_ref()),
+            )
+            .collect()
+    }
+}
+
+pub fn paragraph_style_id<'p>(paragraph: &'p Paragraph) -> Option<&'p str> {
+    let property = paragraph.property.as_ref()?;
+    Some(property.style_id.as_ref()?.value.as_ref())
+}
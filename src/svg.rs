@@ -0,0 +1,51 @@
+use log::{debug, warn};
+use printpdf::{ColorBits, ColorSpace, Image, ImageXObject, Px};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{Options, Tree};
+
+// Rasterizes an SVG at its own size (in CSS pixels) over a white background.
+// Returns None for SVGs that cannot be parsed or have no size.
+pub fn rasterize_svg(bytes: &[u8]) -> Option<Image> {
+    let tree = match Tree::from_data(bytes, &Options::default()) {
+        Ok(tree) => tree,
+        Err(e) => {
+            warn!("Failed to parse SVG image: {}", e);
+            return None;
+        }
+    };
+    let size = tree.size().to_int_size();
+    let mut pixmap = Pixmap::new(size.width(), size.height())?;
+    debug!(
+        "Rasterizing SVG image at {}x{}",
+        size.width(),
+        size.height()
+    );
+    resvg::render(&tree, Transform::default(), &mut pixmap.as_mut());
+
+    // The pixmap holds premultiplied RGBA, so blending over white adds the
+    // missing coverage to each channel.
+    let image_data = pixmap
+        .data()
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let uncovered = 255 - pixel[3];
+            [
+                pixel[0] + uncovered,
+                pixel[1] + uncovered,
+                pixel[2] + uncovered,
+            ]
+        })
+        .collect();
+
+    Some(Image::from(ImageXObject {
+        width: Px(size.width() as usize),
+        height: Px(size.height() as usize),
+        color_space: ColorSpace::Rgb,
+        bits_per_component: ColorBits::Bit8,
+        interpolate: true,
+        image_data,
+        image_filter: None,
+        smask: None,
+        clipping_bbox: None,
+    }))
+}
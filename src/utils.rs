@@ -1,16 +1,644 @@
-#[derive(Debug)]
+use crate::error::{ConversionError, Result};
+use crate::{PAGE_HEIGHT, PAGE_WIDTH};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// The built-in PDF font families. Built-in fonts need no embedding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FontFamily {
+    #[default]
+    Helvetica,
+    Times,
+    Courier,
+}
+
+impl FromStr for FontFamily {
+    type Err = ConversionError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "helvetica" => Ok(FontFamily::Helvetica),
+            "times" => Ok(FontFamily::Times),
+            "courier" => Ok(FontFamily::Courier),
+            _ => Err(ConversionError::InvalidOption(format!(
+                "Invalid font family: {} (expected helvetica, times or courier)",
+                value
+            ))),
+        }
+    }
+}
+
+// Font names mapped to the built-in family that stands in for them, such as a
+// corporate font that looks like Helvetica. These come before the common fonts
+// mapped by default. Names are matched ignoring case.
+#[derive(Debug, Clone, Default)]
+pub struct FontMap {
+    mappings: HashMap<String, FontFamily>,
+}
+
+impl FontMap {
+    pub fn new() -> Self {
+        FontMap::default()
+    }
+
+    pub fn add_mapping(&mut self, name: &str, family: FontFamily) -> &mut Self {
+        self.mappings.insert(font_key(name), family);
+        self
+    }
+
+    pub fn family(&self, name: &str) -> Option<FontFamily> {
+        self.mappings.get(&font_key(name)).copied()
+    }
+}
+
+fn font_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+#[derive(Debug, Clone)]
 pub struct ImageContent {
     pub bytes: Vec<u8>,
+    // Display size chosen by the author in the document, if any.
+    pub size: Option<ImageSize>,
+    // Top left corner of a floating image drawn at a fixed place on the page
+    // instead of in the flow of the text, in mm from the top left corner of
+    // the page.
+    pub position: Option<(f32, f32)>,
 }
 
-#[derive(Debug)]
-pub struct DocContent {
+// Width and height in millimeters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RgbColor {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl RgbColor {
+    pub const BLACK: RgbColor = RgbColor {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+    };
+
+    // Parses the "RRGGBB" form used by w:color; "auto" and malformed values yield None.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |range: std::ops::Range<usize>| {
+            u8::from_str_radix(&hex[range], 16)
+                .ok()
+                .map(|value| f32::from(value) / 255.0)
+        };
+        Some(RgbColor {
+            r: channel(0..2)?,
+            g: channel(2..4)?,
+            b: channel(4..6)?,
+        })
+    }
+
+    // Maps the named colors of w:highlight; "none" and unknown names yield None.
+    pub fn from_highlight(name: &str) -> Option<Self> {
+        let hex = match name {
+            "black" => "000000",
+            "blue" => "0000FF",
+            "cyan" => "00FFFF",
+            "green" => "00FF00",
+            "magenta" => "FF00FF",
+            "red" => "FF0000",
+            "yellow" => "FFFF00",
+            "white" => "FFFFFF",
+            "darkBlue" => "000080",
+            "darkCyan" => "008080",
+            "darkGreen" => "008000",
+            "darkMagenta" => "800080",
+            "darkRed" => "800000",
+            "darkYellow" => "808000",
+            "darkGray" => "808080",
+            "lightGray" => "C0C0C0",
+            _ => return None,
+        };
+        Self::from_hex(hex)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TextRun {
     pub text: String,
+    pub link: Option<String>,
+    pub font_size: Option<f32>,
+    // Built-in family matching the run's font. Unknown fonts use the default
+    // family.
+    pub font_family: Option<FontFamily>,
+    pub bold: bool,
+    pub color: Option<RgbColor>,
+    pub highlight: Option<RgbColor>,
+    pub vertical_align: VerticalAlign,
+    pub strike: Strike,
+    // Drawn underlined, as insertions are when tracked changes are shown.
+    pub underline: bool,
+    // Set on the lowercase letters of small caps text (w:smallCaps), which
+    // are uppercased in `text` and drawn smaller.
+    pub small_caps: bool,
+    // Index into `DocxDocument::footnotes` of the note this reference mark
+    // points to.
+    pub footnote: Option<usize>,
+    // Set on runs standing for a small inline image, drawn within the line
+    // instead of text. Their size is always known.
     pub image: Option<ImageContent>,
+    // Set on the page number or count of headers and footers (PAGE and
+    // NUMPAGES fields), whose text is replaced on each page.
+    pub field: Option<PageField>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageField {
+    Number,
+    Count,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strike {
+    #[default]
+    None,
+    Single,
+    Double,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VerticalAlign {
+    #[default]
+    Baseline,
+    Superscript,
+    Subscript,
 }
 
+#[derive(Debug, Clone)]
+pub struct ListItem {
+    pub level: usize,
+    pub marker: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Alignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+// Paragraph indentation in millimeters. A negative first line indent is a
+// hanging indent.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Indentation {
+    pub left: f32,
+    pub right: f32,
+    pub first_line: f32,
+}
+
+// Distance between the lines of a paragraph (w:spacing line and lineRule).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineSpacing {
+    // A multiple of the single line height: 1.5, 2.
+    Multiple(f32),
+    // A fixed height in millimeters.
+    Exact(f32),
+    // At least this height in millimeters, more for larger text.
+    AtLeast(f32),
+}
+
+impl Default for LineSpacing {
+    fn default() -> Self {
+        LineSpacing::Multiple(1.0)
+    }
+}
+
+impl LineSpacing {
+    // The line height for text whose single line height is `single`.
+    pub fn line_height(self, single: f32) -> f32 {
+        match self {
+            LineSpacing::Multiple(factor) => single * factor,
+            LineSpacing::Exact(height) => height,
+            LineSpacing::AtLeast(height) => single.max(height),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorderStyle {
+    None,
+    Single,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Border {
+    pub style: BorderStyle,
+    // Line thickness in points.
+    pub width: f32,
+}
+
+impl Border {
+    pub const NONE: Border = Border {
+        style: BorderStyle::None,
+        width: 0.0,
+    };
+
+    pub fn is_visible(&self) -> bool {
+        self.style != BorderStyle::None && self.width > 0.0
+    }
+}
+
+impl Default for Border {
+    fn default() -> Self {
+        Border {
+            style: BorderStyle::Single,
+            width: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableBorders {
+    pub top: Border,
+    pub bottom: Border,
+    pub left: Border,
+    pub right: Border,
+    pub inside_horizontal: Border,
+    pub inside_vertical: Border,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CellBorders {
+    pub top: Option<Border>,
+    pub bottom: Option<Border>,
+    pub left: Option<Border>,
+    pub right: Option<Border>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CellContent {
+    // Runs of each paragraph of the cell, laid out one below the other.
+    pub paragraphs: Vec<Vec<TextRun>>,
+    pub images: Vec<ImageContent>,
+    pub tables: Vec<TableContent>,
+    pub borders: CellBorders,
+    // Number of grid columns the cell covers (w:gridSpan). 0 counts as 1.
+    pub column_span: usize,
+    // Background color of the cell (w:shd).
+    pub shading: Option<RgbColor>,
+    pub vertical_align: CellVerticalAlign,
+    // Alignment of the text, from the justification of the first paragraph.
+    pub alignment: Alignment,
+}
+
+impl CellContent {
+    // The text of the cell, a line per paragraph.
+    pub fn text(&self) -> String {
+        self.paragraphs
+            .iter()
+            .map(|runs| runs.iter().map(|run| run.text.as_str()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// Where the content of a table cell sits in the height of its row (w:vAlign).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CellVerticalAlign {
+    #[default]
+    Top,
+    Center,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RowContent {
+    pub cells: Vec<CellContent>,
+    // Header rows (w:tblHeader) are set in bold and repeated at the top of
+    // each page the table continues on.
+    pub header: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TableContent {
+    pub rows: Vec<RowContent>,
+    pub borders: TableBorders,
+    // Widths of the columns of the table grid (w:gridCol), in mm. Empty when
+    // the document doesn't define them.
+    pub column_widths: Vec<f32>,
+}
+
+impl TableContent {
+    // Number of grid columns used by the widest row, counting the columns
+    // spanned by each cell.
+    pub fn column_count(&self) -> usize {
+        self.rows
+            .iter()
+            .map(|row| row.cells.iter().map(|cell| cell.column_span.max(1)).sum())
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Number of cells, including those of nested tables.
+    pub fn cell_count(&self) -> usize {
+        self.rows
+            .iter()
+            .flat_map(|row| &row.cells)
+            .map(|cell| {
+                1 + cell
+                    .tables
+                    .iter()
+                    .map(|table| table.cell_count())
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+
+    // Images of every cell, including those of nested tables.
+    pub fn images(&self) -> Vec<&ImageContent> {
+        self.rows
+            .iter()
+            .flat_map(|row| &row.cells)
+            .flat_map(|cell| {
+                cell.images
+                    .iter()
+                    .chain(cell.tables.iter().flat_map(|table| table.images()))
+            })
+            .collect()
+    }
+}
+
+// A content item of the body, a header or a footer, in document order.
+#[derive(Debug, Clone)]
+pub enum DocContent {
+    Paragraph(Paragraph),
+    Table {
+        table: TableContent,
+        comments: Vec<Comment>,
+    },
+    Image {
+        image: ImageContent,
+        comments: Vec<Comment>,
+    },
+    // Text of a text box or shape, drawn as a bordered block.
+    TextBox(Vec<TextRun>),
+    // Markers emitted for an explicit page or column break.
+    PageBreak,
+    ColumnBreak,
+    // Marker emitted where a section ends: the content after it starts on a
+    // new page of the size and columns of the next section.
+    SectionBreak(Section),
+}
+
+impl DocContent {
+    // The runs of a paragraph or text box, none for other items.
+    pub fn runs(&self) -> &[TextRun] {
+        match self {
+            DocContent::Paragraph(paragraph) => &paragraph.runs,
+            DocContent::TextBox(runs) => runs,
+            _ => &[],
+        }
+    }
+
+    // Review comments anchored in this paragraph, table or image, written to
+    // the PDF as annotations.
+    pub fn comments(&self) -> &[Comment] {
+        match self {
+            DocContent::Paragraph(paragraph) => &paragraph.comments,
+            DocContent::Table { comments, .. } | DocContent::Image { comments, .. } => comments,
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Paragraph {
+    pub runs: Vec<TextRun>,
+    pub list: Option<ListItem>,
+    pub heading_level: Option<u8>,
+    pub alignment: Alignment,
+    pub indentation: Indentation,
+    pub line_spacing: LineSpacing,
+    // Space above and below the paragraph in millimeters (w:spacing before
+    // and after). When unset the default paragraph spacing is used.
+    pub space_before: Option<f32>,
+    pub space_after: Option<f32>,
+    // Right-to-left paragraph (Hebrew, Arabic), laid out from the right margin.
+    pub rtl: bool,
+    // Figure or table caption (the Caption style), kept on the same page as
+    // an image right before or after it.
+    pub caption: bool,
+    // Pagination flags of the paragraph: its lines stay on one page
+    // (w:keepLines), and it stays on the page where the next one starts
+    // (w:keepNext).
+    pub keep_lines: bool,
+    pub keep_next: bool,
+    // Review comments anchored in this paragraph.
+    pub comments: Vec<Comment>,
+}
+
+// A review comment from comments.xml.
+#[derive(Debug, Clone, Default)]
+pub struct Comment {
+    pub author: Option<String>,
+    pub text: String,
+}
+
+// Everything read from a DOCX: the body plus the header and footer of the
+// document's section, which are repeated on every page.
+#[derive(Debug, Clone, Default)]
+pub struct DocxDocument {
+    pub content: Vec<DocContent>,
+    pub header: Vec<DocContent>,
+    pub footer: Vec<DocContent>,
+    pub footnotes: Vec<Note>,
+    pub endnotes: Vec<Note>,
+    pub metadata: DocumentMetadata,
+    // Text columns and page size of the first section. Section breaks in the
+    // content change them for the sections after.
+    pub columns: Columns,
+    pub page_size: PageSize,
+    // The DOCX file itself, kept to be attached to the PDF.
+    pub source: Option<SourceFile>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+// Word's default gap between columns, half an inch.
+const DEFAULT_COLUMN_SPACING: f32 = 12.7;
+
+// Text columns of the body, from the w:cols of the section. Columns have equal
+// widths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Columns {
+    pub count: usize,
+    // Gap between two columns, in mm.
+    pub spacing: f32,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns {
+            count: 1,
+            spacing: DEFAULT_COLUMN_SPACING,
+        }
+    }
+}
+
+// Page size and text columns of a section, from its w:sectPr.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Section {
+    pub page_size: PageSize,
+    pub columns: Columns,
+}
+
+// Size of the pages of a section in mm, from its w:pgSz. A4 portrait when
+// the document does not give one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for PageSize {
+    fn default() -> Self {
+        PageSize::A4
+    }
+}
+
+impl PageSize {
+    pub const A4: PageSize = PageSize {
+        width: PAGE_WIDTH,
+        height: PAGE_HEIGHT,
+    };
+    pub const A5: PageSize = PageSize {
+        width: 148.0,
+        height: 210.0,
+    };
+    pub const LETTER: PageSize = PageSize {
+        width: 215.9,
+        height: 279.4,
+    };
+    pub const LEGAL: PageSize = PageSize {
+        width: 215.9,
+        height: 355.6,
+    };
+
+    // The same paper, turned to landscape or portrait.
+    pub fn oriented(self, landscape: bool) -> PageSize {
+        if landscape == (self.width > self.height) {
+            self
+        } else {
+            PageSize {
+                width: self.height,
+                height: self.width,
+            }
+        }
+    }
+}
+
+// A paper name (a4, a5, letter or legal) or a WIDTHxHEIGHT size in mm.
+impl FromStr for PageSize {
+    type Err = ConversionError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let size = match value {
+            "a4" => Some(PageSize::A4),
+            "a5" => Some(PageSize::A5),
+            "letter" => Some(PageSize::LETTER),
+            "legal" => Some(PageSize::LEGAL),
+            _ => value.split_once('x').and_then(|(width, height)| {
+                let size = PageSize {
+                    width: width.parse().ok()?,
+                    height: height.parse().ok()?,
+                };
+                (size.width > 0.0 && size.height > 0.0).then_some(size)
+            }),
+        };
+        size.ok_or_else(|| {
+            ConversionError::InvalidOption(format!(
+                "Invalid page size: {} (expected a4, a5, letter, legal or WIDTHxHEIGHT in mm)",
+                value
+            ))
+        })
+    }
+}
+
+// A footnote or endnote, numbered in reference order. Its runs start with the
+// superscript reference mark.
+#[derive(Debug, Clone, Default)]
+pub struct Note {
+    pub runs: Vec<TextRun>,
+}
+
+// Document properties from docProps/core.xml.
+#[derive(Debug, Clone, Default)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Vec<String>,
+    // Date the document was last modified, or else created, as YYYY-MM-DD.
+    pub date: Option<String>,
+}
+
+// 914400 EMUs make an inch.
+pub fn emus_to_mm(emus: f32) -> f32 {
+    emus / 914400.0 * 25.4
+}
+
+// Hebrew and Arabic letters, which are written right to left.
+pub fn is_rtl_char(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}'
+    )
+}
+
+// Chinese, Japanese and Korean characters, written without spaces between the
+// words: kana, ideographs, Hangul and their punctuation and fullwidth forms.
+pub fn is_cjk_char(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{1100}'..='\u{11FF}'
+            | '\u{2E80}'..='\u{303F}'
+            | '\u{3040}'..='\u{9FFF}'
+            | '\u{A960}'..='\u{A97F}'
+            | '\u{AC00}'..='\u{D7AF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FE30}'..='\u{FE4F}'
+            | '\u{FF00}'..='\u{FFEF}'
+            | '\u{20000}'..='\u{3FFFF}'
+    )
+}
+
+// CJK characters are full width, about twice as wide as an average Latin
+// letter.
+const FULL_WIDTH_FACTOR: f32 = 2.0;
+
 pub fn estimate_text_width(text: &str, font_size: f32) -> f32 {
     let average_char_width = font_size * 0.25;
-    text.len() as f32 * average_char_width
+    let chars: f32 = text
+        .chars()
+        .map(|ch| {
+            if is_cjk_char(ch) {
+                FULL_WIDTH_FACTOR
+            } else {
+                1.0
+            }
+        })
+        .sum();
+    chars * average_char_width
 }
-
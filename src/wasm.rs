@@ -0,0 +1,12 @@
+use crate::{convert_bytes, PdfOptions, TypographyConfig};
+
+use wasm_bindgen::prelude::*;
+
+// Entry point for JavaScript: takes the bytes of a .docx and returns those of
+// the PDF, with the default typography and options. Errors become a JS
+// exception carrying the error message.
+#[wasm_bindgen(js_name = convertDocxToPdf)]
+pub fn convert_docx_to_pdf(docx: &[u8]) -> std::result::Result<Vec<u8>, JsError> {
+    convert_bytes(docx, &TypographyConfig::default(), &PdfOptions::default())
+        .map_err(|e| JsError::new(&e.to_string()))
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::body_with_parts;
+use docx::{convert_bytes, read_docx_from, DocContent, PdfOptions, TypographyConfig};
+use printpdf::lopdf::Document;
+use std::io::Cursor;
+
+const COMMENTED: &str = r#"<w:p><w:r><w:t>Introduction</w:t></w:r></w:p><w:p><w:commentRangeStart w:id="0"/><w:r><w:t>Revenue grew</w:t></w:r><w:commentRangeEnd w:id="0"/><w:r><w:commentReference w:id="0"/></w:r></w:p>"#;
+
+const COMMENTS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:comments xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:comment w:id="0" w:author="Ana" w:date="2024-01-01T00:00:00Z" w:initials="A"><w:p><w:r><w:t>Check this</w:t></w:r></w:p><w:p><w:r><w:t>figure</w:t></w:r></w:p></w:comment></w:comments>"#;
+
+fn package() -> Vec<u8> {
+    body_with_parts(COMMENTED, &[("comments.xml", COMMENTS.as_bytes())])
+}
+
+#[test]
+fn a_comment_is_anchored_in_the_paragraph_it_starts_in() {
+    let document =
+        read_docx_from(Cursor::new(package()), &PdfOptions::default()).expect("the document reads");
+
+    let comments: Vec<_> = document
+        .content
+        .iter()
+        .map(|item| item.comments().len())
+        .collect();
+    assert_eq!(comments, [0, 1]);
+    let DocContent::Paragraph(paragraph) = &document.content[1] else {
+        panic!("expected a paragraph, got {:?}", document.content[1]);
+    };
+    let comment = &paragraph.comments[0];
+    assert_eq!(comment.author.as_deref(), Some("Ana"));
+    assert_eq!(comment.text, "Check this\nfigure");
+}
+
+#[test]
+fn comments_are_written_as_text_annotations() {
+    let pdf = convert_bytes(
+        &package(),
+        &TypographyConfig::default(),
+        &PdfOptions::default(),
+    )
+    .expect("the document converts");
+    let pdf = Document::load_mem(&pdf).expect("the output is a valid PDF");
+
+    let page_id = pdf.get_pages()[&1];
+    let annots = pdf
+        .get_dictionary(page_id)
+        .unwrap()
+        .get(b"Annots")
+        .and_then(|annots| pdf.dereference(annots))
+        .and_then(|(_, annots)| annots.as_array())
+        .expect("the page has annotations");
+    let [annot] = &annots[..] else {
+        panic!("expected one annotation, got {:?}", annots);
+    };
+    let annot = pdf
+        .dereference(annot)
+        .and_then(|(_, annot)| annot.as_dict())
+        .unwrap();
+    let entry = |key: &[u8]| annot.get(key).unwrap().clone();
+    assert_eq!(entry(b"Subtype").as_name().unwrap(), b"Text");
+    assert_eq!(entry(b"Contents").as_str().unwrap(), b"Check this\nfigure");
+    assert_eq!(entry(b"T").as_str().unwrap(), b"Ana");
+}
@@ -0,0 +1,170 @@
+use docx::{
+    render_docx_from, FontFace, PageSize, PdfOptions, RasterImage, Renderer, RgbColor,
+    TypographyConfig,
+};
+use std::fmt::Write as _;
+use std::io::{Cursor, Write};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Default Extension="png" ContentType="image/png"/><Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/><Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/></Types>"#;
+
+const ROOT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/></Relationships>"#;
+
+const RELATIONSHIP_TYPES: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"></w:styles>"#;
+
+// A package holding only the parts every DOCX has, with the given body.
+#[allow(dead_code)]
+pub fn docx(document: &str) -> Vec<u8> {
+    docx_with_parts(document, &[])
+}
+
+// Same as docx, for the content of w:body alone. The namespaces of text,
+// relationships, pictures, shapes and math are declared.
+#[allow(dead_code)]
+pub fn body(xml: &str) -> Vec<u8> {
+    body_with_parts(xml, &[])
+}
+
+// Same as docx_with_parts, for the content of w:body alone.
+#[allow(dead_code)]
+pub fn body_with_parts(xml: &str, parts: &[(&str, &[u8])]) -> Vec<u8> {
+    let document = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing" xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" xmlns:pic="http://schemas.openxmlformats.org/drawingml/2006/picture" xmlns:mc="http://schemas.openxmlformats.org/markup-compatibility/2006" xmlns:wps="http://schemas.microsoft.com/office/word/2010/wordprocessingShape" xmlns:v="urn:schemas-microsoft-com:vml" xmlns:m="http://schemas.openxmlformats.org/officeDocument/2006/math"><w:body>{}</w:body></w:document>"#,
+        xml
+    );
+    docx_with_parts(&document, parts)
+}
+
+// Same as docx, with more parts of word/, such as numbering.xml or an image
+// in media/. The document relates to them as rId2, rId3 and so on, in order.
+// Parts of docProps/, such as core.xml, are kept at the package root, with
+// no relationship.
+#[allow(dead_code)]
+pub fn docx_with_parts(document: &str, parts: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut content_types = CONTENT_TYPES.trim_end_matches("</Types>").to_string();
+    let mut relationships = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="{}/styles" Target="styles.xml"/>"#,
+        RELATIONSHIP_TYPES
+    );
+    let related = parts
+        .iter()
+        .filter(|(name, _)| !name.starts_with("docProps/"));
+    for (index, (name, _)) in related.enumerate() {
+        let kind = match name.strip_suffix(".xml") {
+            Some(kind) => {
+                content_types.push_str(&format!(
+                    r#"<Override PartName="/word/{}" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.{}+xml"/>"#,
+                    name, kind
+                ));
+                kind
+            }
+            None => "image",
+        };
+        relationships.push_str(&format!(
+            r#"<Relationship Id="rId{}" Type="{}/{}" Target="{}"/>"#,
+            index + 2,
+            RELATIONSHIP_TYPES,
+            kind,
+            name
+        ));
+    }
+    content_types.push_str("</Types>");
+    relationships.push_str("</Relationships>");
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut add = |name: &str, content: &[u8]| {
+        zip.start_file(name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(content).unwrap();
+    };
+    add("[Content_Types].xml", content_types.as_bytes());
+    add("_rels/.rels", ROOT_RELS.as_bytes());
+    add("word/_rels/document.xml.rels", relationships.as_bytes());
+    add("word/styles.xml", STYLES.as_bytes());
+    add("word/document.xml", document.as_bytes());
+    for (name, content) in parts {
+        if name.starts_with("docProps/") {
+            add(name, content);
+        } else {
+            add(&format!("word/{}", name), content);
+        }
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+// Keeps what is drawn: the text, the size of the pages started after the
+// first or given to a blank page, and a trace with one line per page, text,
+// line and image drawn. Positions are left out of the trace, so that spacing
+// tweaks do not change every expectation.
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct Recorder {
+    pub trace: String,
+    pub texts: Vec<String>,
+    pub new_pages: Vec<PageSize>,
+    pub resized_pages: Vec<PageSize>,
+}
+
+impl Renderer for Recorder {
+    fn new_page(&mut self, size: PageSize) {
+        self.new_pages.push(size);
+        self.trace.push_str("page\n");
+    }
+
+    fn resize_page(&mut self, size: PageSize) {
+        self.resized_pages.push(size);
+        self.trace.push_str("resize page\n");
+    }
+
+    fn begin_layer(&mut self, _: usize, _: &str) {}
+
+    fn draw_text(&mut self, text: &str, face: FontFace, _: f32, _: (f32, f32), _: RgbColor) {
+        self.texts.push(text.to_string());
+        let bold = if face.bold { "bold " } else { "" };
+        let _ = writeln!(self.trace, "text {}{}", bold, text);
+    }
+
+    fn draw_line(&mut self, _: (f32, f32), _: (f32, f32), _: f32, _: RgbColor) {
+        self.trace.push_str("line\n");
+    }
+
+    fn stroke_rect(&mut self, _: [f32; 4], _: f32, _: RgbColor) {
+        self.trace.push_str("rect\n");
+    }
+
+    fn fill_rect(&mut self, _: [f32; 4], _: RgbColor) {
+        self.trace.push_str("fill\n");
+    }
+
+    fn draw_image(&mut self, _: RasterImage, _: (f32, f32), (width, height): (f32, f32)) {
+        let _ = writeln!(self.trace, "image {:.1}x{:.1}", width, height);
+    }
+
+    fn add_link(&mut self, _: [f32; 4], url: &str) {
+        let _ = writeln!(self.trace, "link {}", url);
+    }
+
+    fn draw_watermark(&mut self, text: &str, _: FontFace, _: f32, _: (f32, f32), _: f32) {
+        let _ = writeln!(self.trace, "watermark {}", text);
+    }
+}
+
+// Lays out a package, such as one made by docx, on a Recorder.
+#[allow(dead_code)]
+pub fn render_to_recorder(package: Vec<u8>, options: &PdfOptions) -> docx::Result<Recorder> {
+    let mut recorder = Recorder::default();
+    render_docx_from(
+        Cursor::new(package),
+        &mut recorder,
+        &TypographyConfig::default(),
+        options,
+    )?;
+    Ok(recorder)
+}
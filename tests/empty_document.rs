@@ -0,0 +1,31 @@
+mod common;
+
+use common::body;
+use docx::{convert_bytes, PdfOptions, TypographyConfig};
+use printpdf::lopdf;
+
+#[test]
+fn empty_body_gives_a_single_blank_page() {
+    let pdf = convert_bytes(
+        &body(""),
+        &TypographyConfig::default(),
+        &PdfOptions::default(),
+    )
+    .expect("an empty document converts");
+
+    let document = lopdf::Document::load_mem(&pdf).expect("the output is a valid PDF");
+    assert_eq!(document.get_pages().len(), 1);
+}
+
+#[test]
+fn empty_body_fits_on_one_page() {
+    let options = PdfOptions {
+        fit_page: true,
+        ..Default::default()
+    };
+    let pdf = convert_bytes(&body(""), &TypographyConfig::default(), &options)
+        .expect("an empty document converts");
+
+    let document = lopdf::Document::load_mem(&pdf).expect("the output is a valid PDF");
+    assert_eq!(document.get_pages().len(), 1);
+}
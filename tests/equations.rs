@@ -0,0 +1,22 @@
+mod common;
+
+use common::body;
+use docx::{read_docx_from, PdfOptions};
+use std::io::Cursor;
+
+// An inline fraction between two runs, and a display equation (m:oMathPara)
+// at the end of the paragraph.
+const EQUATIONS: &str = r#"<w:p><w:r><w:t xml:space="preserve">Half: </w:t></w:r><m:oMath><m:f><m:num><m:r><m:t>a+b</m:t></m:r></m:num><m:den><m:r><m:t>2</m:t></m:r></m:den></m:f></m:oMath><w:r><w:t xml:space="preserve"> and </w:t></w:r><m:oMathPara><m:oMath><m:sSup><m:e><m:r><m:t>x</m:t></m:r></m:e><m:sup><m:r><m:t>2</m:t></m:r></m:sup></m:sSup></m:oMath></m:oMathPara></w:p>"#;
+
+#[test]
+fn equations_are_set_as_linear_text_where_they_stand() {
+    let document = read_docx_from(Cursor::new(body(EQUATIONS)), &PdfOptions::default())
+        .expect("the document reads");
+
+    let texts: Vec<&str> = document.content[0]
+        .runs()
+        .iter()
+        .map(|run| run.text.as_str())
+        .collect();
+    assert_eq!(texts, ["Half: ", "(a+b)/2", " and ", "x^2"]);
+}
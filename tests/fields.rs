@@ -0,0 +1,25 @@
+mod common;
+
+use common::body;
+use docx::{read_docx_from, PdfOptions};
+use std::io::Cursor;
+
+const FIELDS: &str = r#"<w:p><w:r><w:fldChar w:fldCharType="begin"/></w:r><w:r><w:instrText xml:space="preserve"> TOC \o "1-3" </w:instrText></w:r><w:r><w:fldChar w:fldCharType="separate"/></w:r><w:r><w:t>Introduction 1</w:t></w:r><w:r><w:fldChar w:fldCharType="end"/></w:r></w:p><w:p><w:r><w:fldChar w:fldCharType="begin"/></w:r><w:r><w:instrText xml:space="preserve"> DATE \@ "yyyy" </w:instrText></w:r><w:r><w:fldChar w:fldCharType="separate"/></w:r><w:r><w:t>1999</w:t></w:r><w:r><w:fldChar w:fldCharType="end"/></w:r></w:p>"#;
+
+fn paragraph_texts(xml: &str) -> Vec<String> {
+    let document =
+        read_docx_from(Cursor::new(body(xml)), &PdfOptions::default()).expect("the document reads");
+    document
+        .content
+        .iter()
+        .map(|item| item.runs().iter().map(|run| run.text.as_str()).collect())
+        .collect()
+}
+
+#[test]
+fn a_toc_keeps_its_cached_result_and_a_date_is_filled_in() {
+    let texts = paragraph_texts(FIELDS);
+    assert_eq!(texts[0], "Introduction 1");
+    assert_ne!(texts[1], "1999");
+    assert!(texts[1].len() == 4 && texts[1].chars().all(|ch| ch.is_ascii_digit()));
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use common::body;
+use docx::{read_docx_from, FontFamily, FontMap, PdfOptions};
+use std::io::Cursor;
+
+const CORPORATE_FONT: &str = r#"<w:p><w:r><w:rPr><w:rFonts w:ascii="Foundry Sans" w:hAnsi="Foundry Sans"/></w:rPr><w:t>Quarterly report</w:t></w:r></w:p>"#;
+
+fn run_font_family(options: &PdfOptions) -> Option<FontFamily> {
+    let document = read_docx_from(Cursor::new(body(CORPORATE_FONT)), options).unwrap();
+    document.content[0].runs()[0].font_family
+}
+
+#[test]
+fn unknown_fonts_have_no_family() {
+    assert_eq!(run_font_family(&PdfOptions::default()), None);
+}
+
+#[test]
+fn mapped_fonts_use_their_family() {
+    let mut font_map = FontMap::new();
+    font_map.add_mapping("foundry sans", FontFamily::Courier);
+    let options = PdfOptions {
+        font_map,
+        ..Default::default()
+    };
+    assert_eq!(run_font_family(&options), Some(FontFamily::Courier));
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{docx, docx_with_parts, render_to_recorder};
+use docx::PdfOptions;
+use std::path::PathBuf;
+
+// Renders the fixtures of tests/fixtures and compares what is drawn with the
+// expectations checked in next to them, in <fixture>.expected. After an
+// intended change in the output, run the tests with UPDATE_EXPECTED=1 to
+// rewrite the expectations, and review their diff.
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(fixture_path(name)).unwrap()
+}
+
+fn document(name: &str) -> String {
+    String::from_utf8(fixture(&format!("{}.xml", name))).unwrap()
+}
+
+fn check(name: &str, package: Vec<u8>) {
+    let trace = render_to_recorder(package, &PdfOptions::default())
+        .expect("the fixture renders")
+        .trace;
+
+    let expected_path = fixture_path(&format!("{}.expected", name));
+    if std::env::var_os("UPDATE_EXPECTED").is_some() {
+        std::fs::write(&expected_path, &trace).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&expected_path).unwrap();
+    assert_eq!(trace, expected, "output of {} changed", name);
+}
+
+#[test]
+fn text() {
+    check("text", docx(&document("text")));
+}
+
+#[test]
+fn table() {
+    check("table", docx(&document("table")));
+}
+
+#[test]
+fn list() {
+    let numbering = fixture("list-numbering.xml");
+    check(
+        "list",
+        docx_with_parts(&document("list"), &[("numbering.xml", &numbering)]),
+    );
+}
+
+#[test]
+fn image() {
+    let image = fixture("pixel.png");
+    check(
+        "image",
+        docx_with_parts(&document("image"), &[("media/pixel.png", &image)]),
+    );
+}
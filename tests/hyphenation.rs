@@ -0,0 +1,62 @@
+#![cfg(feature = "hyphenation")]
+
+mod common;
+
+use common::{body, render_to_recorder};
+use docx::PdfOptions;
+
+// Long words of varied lengths, so that some of them overflow a line end
+// with room for their start.
+const WORDS: [&str; 8] = [
+    "international",
+    "organizations",
+    "coordinated",
+    "representatives",
+    "responsible",
+    "telecommunications",
+    "infrastructure",
+    "administration",
+];
+
+fn paragraph(alignment: &str) -> Vec<u8> {
+    let text = vec![WORDS.join(" "); 4].join(" ");
+    body(&format!(
+        r#"<w:p><w:pPr><w:jc w:val="{}"/></w:pPr><w:r><w:t>{}</w:t></w:r></w:p>"#,
+        alignment, text
+    ))
+}
+
+#[test]
+fn justified_lines_end_in_hyphenated_words() {
+    let recorder = render_to_recorder(paragraph("both"), &PdfOptions::default())
+        .expect("the document renders");
+
+    let mut hyphenated = 0;
+    for (index, text) in recorder.texts.iter().enumerate() {
+        let Some(head) = text.strip_suffix('-') else {
+            continue;
+        };
+        // The rest of the word starts the next line.
+        let tail = recorder.texts[index + 1].split(' ').next().unwrap();
+        let word = format!("{}{}", head, tail);
+        assert!(WORDS.contains(&word.as_str()), "{:?} is not a word", word);
+        hyphenated += 1;
+    }
+    assert!(
+        hyphenated > 0,
+        "no word was hyphenated: {:?}",
+        recorder.texts
+    );
+}
+
+#[test]
+fn other_alignments_are_not_hyphenated() {
+    let recorder = render_to_recorder(paragraph("left"), &PdfOptions::default())
+        .expect("the document renders");
+
+    assert!(
+        !recorder.texts.iter().any(|text| text.ends_with('-')),
+        "{:?}",
+        recorder.texts
+    );
+}
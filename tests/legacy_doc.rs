@@ -0,0 +1,11 @@
+use docx::{convert_bytes, ConversionError, PdfOptions, TypographyConfig};
+
+#[test]
+fn legacy_doc_files_are_reported_as_such() {
+    // The OLE compound file signature, followed by the rest of a header.
+    let mut doc = vec![0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+    doc.resize(512, 0);
+
+    let result = convert_bytes(&doc, &TypographyConfig::default(), &PdfOptions::default());
+    assert!(matches!(result, Err(ConversionError::LegacyDocUnsupported)));
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::body;
+use docx::{convert_bytes, ConversionError, ConversionLimits, PdfOptions, TypographyConfig};
+
+const TWO_PAGES: &str = r#"<w:p><w:r><w:t>First page</w:t></w:r></w:p><w:p><w:r><w:br w:type="page"/></w:r></w:p><w:p><w:r><w:t>Second page</w:t></w:r></w:p>"#;
+
+fn convert_with(limits: ConversionLimits) -> docx::Result<Vec<u8>> {
+    let options = PdfOptions {
+        limits,
+        ..Default::default()
+    };
+    convert_bytes(&body(TWO_PAGES), &TypographyConfig::default(), &options)
+}
+
+#[test]
+fn documents_within_the_limits_convert() {
+    let limits = ConversionLimits {
+        max_pages: Some(2),
+        max_content_items: Some(10),
+        max_image_bytes: Some(0),
+    };
+    assert!(convert_with(limits).is_ok());
+}
+
+#[test]
+fn conversion_stops_past_the_page_limit() {
+    let limits = ConversionLimits {
+        max_pages: Some(1),
+        ..Default::default()
+    };
+    assert!(matches!(
+        convert_with(limits),
+        Err(ConversionError::LimitExceeded {
+            limit: "pages",
+            max: 1
+        })
+    ));
+}
+
+#[test]
+fn a_single_long_paragraph_stops_at_the_page_limit() {
+    let paragraph = format!(
+        "<w:p><w:r><w:t>{}</w:t></w:r></w:p>",
+        "lorem ipsum dolor sit amet ".repeat(5000)
+    );
+    let options = PdfOptions {
+        limits: ConversionLimits {
+            max_pages: Some(2),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let result = convert_bytes(&body(&paragraph), &TypographyConfig::default(), &options);
+    assert!(matches!(
+        result,
+        Err(ConversionError::LimitExceeded {
+            limit: "pages",
+            max: 2
+        })
+    ));
+}
+
+#[test]
+fn conversion_stops_past_the_content_item_limit() {
+    let limits = ConversionLimits {
+        max_content_items: Some(1),
+        ..Default::default()
+    };
+    assert!(matches!(
+        convert_with(limits),
+        Err(ConversionError::LimitExceeded {
+            limit: "content items",
+            ..
+        })
+    ));
+}
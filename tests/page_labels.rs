@@ -0,0 +1,47 @@
+mod common;
+
+use common::body;
+use docx::{convert_bytes, ConversionError, PdfOptions, TypographyConfig};
+use printpdf::lopdf::{Document, Object};
+
+const THREE_PAGES: &str = r#"<w:p><w:r><w:t>Preface</w:t></w:r></w:p><w:p><w:r><w:br w:type="page"/></w:r></w:p><w:p><w:r><w:t>Contents</w:t></w:r></w:p><w:p><w:r><w:br w:type="page"/></w:r></w:p><w:p><w:r><w:t>Chapter</w:t></w:r></w:p>"#;
+
+fn convert_with_labels(ranges: &str) -> docx::Result<Vec<u8>> {
+    let options = PdfOptions {
+        page_labels: ranges
+            .split(',')
+            .map(str::parse)
+            .collect::<docx::Result<_>>()?,
+        ..Default::default()
+    };
+    convert_bytes(&body(THREE_PAGES), &TypographyConfig::default(), &options)
+}
+
+#[test]
+fn page_labels_are_written_to_the_catalog() {
+    let pdf = Document::load_mem(&convert_with_labels("1:roman,3:arabic").unwrap()).unwrap();
+    let labels = pdf
+        .catalog()
+        .unwrap()
+        .get(b"PageLabels")
+        .and_then(Object::as_dict)
+        .unwrap();
+    let nums = labels.get(b"Nums").and_then(Object::as_array).unwrap();
+    let ranges: Vec<(i64, Vec<u8>)> = nums
+        .chunks(2)
+        .map(|range| {
+            let style = range[1].as_dict().unwrap().get(b"S").unwrap();
+            let style = style.as_name().unwrap().to_vec();
+            (range[0].as_i64().unwrap(), style)
+        })
+        .collect();
+    assert_eq!(ranges, [(0, b"r".to_vec()), (2, b"D".to_vec())]);
+}
+
+#[test]
+fn page_label_ranges_out_of_page_order_are_refused() {
+    assert!(matches!(
+        convert_with_labels("3:arabic,1:roman"),
+        Err(ConversionError::InvalidOption(_))
+    ));
+}
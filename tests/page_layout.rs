@@ -0,0 +1,105 @@
+mod common;
+
+use common::{body, body_with_parts};
+use docx::{
+    convert_bytes, read_docx_from, ConversionError, DocContent, PageSize, PdfOptions,
+    TypographyConfig,
+};
+use std::io::Cursor;
+
+fn convert_body(xml: &str, typography: &TypographyConfig) -> docx::Result<Vec<u8>> {
+    convert_bytes(&body(xml), typography, &PdfOptions::default())
+}
+
+#[test]
+fn line_height_taller_than_the_page_is_refused() {
+    let typography = TypographyConfig {
+        line_height: 400.0,
+        ..Default::default()
+    };
+    let result = convert_body("<w:p><w:r><w:t>Text</w:t></w:r></w:p>", &typography);
+    assert!(matches!(result, Err(ConversionError::InvalidPageConfig(_))));
+}
+
+#[test]
+fn pages_narrower_than_the_margins_are_refused() {
+    let result = convert_body(
+        r#"<w:p><w:r><w:t>Text</w:t></w:r></w:p><w:sectPr><w:pgSz w:w="1000" w:h="16838"/></w:sectPr>"#,
+        &TypographyConfig::default(),
+    );
+    assert!(matches!(result, Err(ConversionError::InvalidPageConfig(_))));
+}
+
+#[test]
+fn columns_wider_than_the_page_are_refused() {
+    let result = convert_body(
+        r#"<w:p><w:r><w:t>Text</w:t></w:r></w:p><w:sectPr><w:cols w:num="20" w:space="720"/></w:sectPr>"#,
+        &TypographyConfig::default(),
+    );
+    assert!(matches!(result, Err(ConversionError::InvalidPageConfig(_))));
+}
+
+#[test]
+fn each_section_has_its_own_columns() {
+    // The w:sectPr of a revision (w:sectPrChange) is not the section's own.
+    let package = body(
+        r#"<w:p><w:pPr><w:sectPr><w:cols w:num="2" w:space="720"/></w:sectPr></w:pPr><w:r><w:t>Two</w:t></w:r></w:p><w:p><w:r><w:t>One</w:t></w:r></w:p><w:sectPr><w:sectPrChange w:id="1"><w:sectPr><w:cols w:num="3"/></w:sectPr></w:sectPrChange></w:sectPr>"#,
+    );
+    let document =
+        read_docx_from(Cursor::new(package), &PdfOptions::default()).expect("the document is read");
+
+    assert_eq!(document.columns.count, 2);
+    let sections: Vec<_> = document
+        .content
+        .iter()
+        .filter_map(|item| match item {
+            DocContent::SectionBreak(section) => Some(*section),
+            _ => None,
+        })
+        .collect();
+    let [section] = sections[..] else {
+        panic!("expected one section break, got {:?}", sections);
+    };
+    assert_eq!(section.columns.count, 1);
+}
+
+#[test]
+fn images_anchored_to_the_margin_are_placed_from_the_section_margins() {
+    let pixel = std::fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/pixel.png"
+    ))
+    .unwrap();
+    let package = body_with_parts(
+        r#"<w:p><w:r><w:drawing><wp:anchor distT="0" distB="0" distL="0" distR="0" simplePos="0" relativeHeight="1" behindDoc="0" locked="0" layoutInCell="1" allowOverlap="1"><wp:simplePos x="0" y="0"/><wp:positionH relativeFrom="margin"><wp:posOffset>360000</wp:posOffset></wp:positionH><wp:positionV relativeFrom="margin"><wp:posOffset>0</wp:posOffset></wp:positionV><wp:extent cx="914400" cy="914400"/><wp:wrapNone/><wp:docPr id="1" name="Picture 1"/><a:graphic><a:graphicData uri="http://schemas.openxmlformats.org/drawingml/2006/picture"><pic:pic><pic:nvPicPr><pic:cNvPr id="1" name="pixel.png"/><pic:cNvPicPr/></pic:nvPicPr><pic:blipFill><a:blip r:embed="rId2"/></pic:blipFill><pic:spPr/></pic:pic></a:graphicData></a:graphic></wp:anchor></w:drawing></w:r></w:p><w:sectPr><w:pgMar w:top="1440" w:right="1440" w:bottom="1440" w:left="720"/></w:sectPr>"#,
+        &[("media/pixel.png", &pixel)],
+    );
+    let document =
+        read_docx_from(Cursor::new(package), &PdfOptions::default()).expect("the document is read");
+
+    let positions: Vec<_> = document
+        .content
+        .iter()
+        .filter_map(|item| match item {
+            DocContent::Image { image, .. } => image.position,
+            _ => None,
+        })
+        .collect();
+    let [(x, y)] = positions[..] else {
+        panic!("expected one placed image, got {:?}", positions);
+    };
+    // 12.7mm of left margin and 10mm of offset, 25.4mm of top margin.
+    assert!(
+        (x - 22.7).abs() < 0.01 && (y - 25.4).abs() < 0.01,
+        "{:?}",
+        (x, y)
+    );
+}
+
+#[test]
+fn page_sizes_are_parsed_by_name_or_in_mm() {
+    assert_eq!("letter".parse::<PageSize>().unwrap(), PageSize::LETTER);
+    let size: PageSize = "200x250".parse().unwrap();
+    assert_eq!((size.width, size.height), (200.0, 250.0));
+    assert!("0x250".parse::<PageSize>().is_err());
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::body_with_parts;
+use docx::{convert_bytes, PdfOptions, TypographyConfig};
+use printpdf::lopdf::{Document, Object};
+
+const CORE: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"><dc:title>Relatório anual</dc:title><dc:creator>Ana</dc:creator><dcterms:modified xsi:type="dcterms:W3CDTF">2024-01-31T12:00:00Z</dcterms:modified></cp:coreProperties>"#;
+
+// PDF/A does not allow the built-in fonts.
+const FONT: &[u8] = include_bytes!("fixtures/DejaVuSansMono-Oblique.ttf");
+
+fn pdfa() -> Document {
+    let package = body_with_parts(
+        "<w:p><w:r><w:t>Resultados</w:t></w:r></w:p>",
+        &[("docProps/core.xml", CORE.as_bytes())],
+    );
+    let options = PdfOptions {
+        pdfa: true,
+        font: Some(FONT.to_vec()),
+        ..Default::default()
+    };
+    let pdf = convert_bytes(&package, &TypographyConfig::default(), &options)
+        .expect("the document converts");
+    Document::load_mem(&pdf).expect("the output is a valid PDF")
+}
+
+#[test]
+fn info_keeps_the_metadata_as_text_strings() {
+    let pdf = pdfa();
+    let info = pdf
+        .trailer
+        .get(b"Info")
+        .and_then(|info| pdf.dereference(info))
+        .and_then(|(_, info)| info.as_dict())
+        .expect("the trailer has an Info dictionary");
+
+    // Non-ASCII text is written in UTF-16BE, with a byte order mark.
+    let mut title = vec![0xFE, 0xFF];
+    title.extend("Relatório anual".encode_utf16().flat_map(u16::to_be_bytes));
+    assert_eq!(info.get(b"Title").and_then(Object::as_str).unwrap(), title);
+    assert_eq!(
+        info.get(b"Author").and_then(Object::as_str).unwrap(),
+        b"Ana"
+    );
+    assert!(!info.has(b"GTS_PDFXVersion"));
+}
+
+#[test]
+fn xmp_metadata_matches_the_info_dictionary() {
+    let pdf = pdfa();
+    let metadata = pdf
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Metadata"))
+        .and_then(|metadata| pdf.dereference(metadata))
+        .and_then(|(_, metadata)| metadata.as_stream())
+        .expect("the catalog has a metadata stream");
+    // PDF/A does not allow the metadata stream to be filtered.
+    assert!(!metadata.dict.has(b"Filter"));
+
+    let xmp = String::from_utf8(metadata.content.clone()).expect("the metadata is UTF-8");
+    for property in [
+        "<pdfaid:part>2</pdfaid:part>",
+        "<pdfaid:conformance>B</pdfaid:conformance>",
+        r#"<dc:title><rdf:Alt><rdf:li xml:lang="x-default">Relatório anual</rdf:li></rdf:Alt></dc:title>"#,
+        "<dc:creator><rdf:Seq><rdf:li>Ana</rdf:li></rdf:Seq></dc:creator>",
+    ] {
+        assert!(
+            xmp.contains(property),
+            "{} is missing from\n{}",
+            property,
+            xmp
+        );
+    }
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use common::{body, body_with_parts, render_to_recorder};
+use docx::{ConversionError, PageSize, PdfOptions, UnsupportedImageMode};
+
+const ONE_PARAGRAPH: &str = r#"<w:p><w:r><w:t>Hello world</w:t></w:r></w:p>"#;
+
+const LANDSCAPE_SECTION: &str = r#"<w:p><w:pPr><w:sectPr><w:pgSz w:w="11906" w:h="16838"/></w:sectPr></w:pPr><w:r><w:t>Portrait</w:t></w:r></w:p><w:p><w:r><w:t>Landscape</w:t></w:r></w:p><w:sectPr><w:pgSz w:w="16838" w:h="11906" w:orient="landscape"/></w:sectPr>"#;
+
+const ONE_TABLE: &str =
+    r#"<w:tbl><w:tr><w:tc><w:p><w:r><w:t>Cell</w:t></w:r></w:p></w:tc></w:tr></w:tbl>"#;
+
+const FORMATTED_CELL: &str = r#"<w:tbl><w:tr><w:tc><w:p><w:r><w:t>Total</w:t></w:r></w:p><w:p><w:r><w:rPr><w:b/></w:rPr><w:t>42</w:t></w:r></w:p></w:tc></w:tr></w:tbl>"#;
+
+const SECTION_AFTER_PAGE_BREAK: &str = r#"<w:p><w:r><w:t>Portrait</w:t></w:r></w:p><w:p><w:pPr><w:sectPr><w:pgSz w:w="11906" w:h="16838"/></w:sectPr></w:pPr><w:r><w:br w:type="page"/></w:r></w:p><w:p><w:r><w:t>Landscape</w:t></w:r></w:p><w:sectPr><w:pgSz w:w="16838" w:h="11906" w:orient="landscape"/></w:sectPr>"#;
+
+const FLOATING_IMAGE: &str = r#"<w:p><w:r><w:t>Logo</w:t></w:r><w:r><w:drawing><wp:anchor distT="0" distB="0" distL="0" distR="0" simplePos="0" relativeHeight="1" behindDoc="0" locked="0" layoutInCell="1" allowOverlap="1"><wp:simplePos x="0" y="0"/><wp:positionH relativeFrom="page"><wp:posOffset>914400</wp:posOffset></wp:positionH><wp:positionV relativeFrom="page"><wp:posOffset>914400</wp:posOffset></wp:positionV><wp:extent cx="914400" cy="914400"/><wp:wrapNone/><wp:docPr id="1" name="Picture 1"/><a:graphic><a:graphicData uri="http://schemas.openxmlformats.org/drawingml/2006/picture"><pic:pic><pic:nvPicPr><pic:cNvPr id="1" name="pixel.png"/><pic:cNvPicPr/></pic:nvPicPr><pic:blipFill><a:blip r:embed="rId2"/><a:stretch><a:fillRect/></a:stretch></pic:blipFill><pic:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="914400" cy="914400"/></a:xfrm><a:prstGeom prst="rect"/></pic:spPr></pic:pic></a:graphicData></a:graphic></wp:anchor></w:drawing></w:r></w:p>"#;
+
+const INLINE_IMAGE: &str = r#"<w:p><w:r><w:t>Signed</w:t></w:r><w:r><w:drawing><wp:inline distT="0" distB="0" distL="0" distR="0"><wp:extent cx="228600" cy="228600"/><wp:docPr id="1" name="Picture 1"/><a:graphic><a:graphicData uri="http://schemas.openxmlformats.org/drawingml/2006/picture"><pic:pic><pic:nvPicPr><pic:cNvPr id="1" name="broken.png"/><pic:cNvPicPr/></pic:nvPicPr><pic:blipFill><a:blip r:embed="rId2"/><a:stretch><a:fillRect/></a:stretch></pic:blipFill><pic:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="228600" cy="228600"/></a:xfrm><a:prstGeom prst="rect"/></pic:spPr></pic:pic></a:graphicData></a:graphic></wp:inline></w:drawing></w:r></w:p>"#;
+
+#[test]
+fn text_is_drawn_on_a_custom_renderer() {
+    let recorder = render_to_recorder(body(ONE_PARAGRAPH), &PdfOptions::default())
+        .expect("the document renders");
+
+    assert_eq!(recorder.texts, ["Hello world"]);
+    assert!(recorder.new_pages.is_empty());
+}
+
+#[test]
+fn a_section_break_starts_a_page_of_the_next_section_size() {
+    let recorder = render_to_recorder(body(LANDSCAPE_SECTION), &PdfOptions::default())
+        .expect("the document renders");
+
+    assert_eq!(recorder.texts, ["Portrait", "Landscape"]);
+    let [landscape] = recorder.new_pages[..] else {
+        panic!("expected one new page, got {:?}", recorder.new_pages);
+    };
+    assert!((landscape.width - 297.0).abs() < 0.1 && (landscape.height - 210.0).abs() < 0.1);
+}
+
+#[test]
+fn auto_caption_numbers_an_uncaptioned_table() {
+    let options = PdfOptions {
+        auto_caption: true,
+        ..Default::default()
+    };
+    let recorder = render_to_recorder(body(ONE_TABLE), &options).expect("the document renders");
+
+    assert_eq!(recorder.texts, ["Cell", "Table 1"]);
+}
+
+#[test]
+fn each_cell_paragraph_starts_a_line_with_its_own_formatting() {
+    let recorder = render_to_recorder(body(FORMATTED_CELL), &PdfOptions::default())
+        .expect("the document renders");
+
+    assert_eq!(recorder.texts, ["Total", "42"]);
+    assert!(
+        recorder.trace.contains("text Total\ntext bold 42\n"),
+        "{}",
+        recorder.trace
+    );
+}
+
+#[test]
+fn a_section_after_a_page_break_resizes_the_blank_page() {
+    let recorder = render_to_recorder(body(SECTION_AFTER_PAGE_BREAK), &PdfOptions::default())
+        .expect("the document renders");
+
+    assert_eq!(recorder.texts, ["Portrait", "Landscape"]);
+    assert_eq!(recorder.new_pages.len(), 1);
+    let [landscape] = recorder.resized_pages[..] else {
+        panic!(
+            "expected one resized page, got {:?}",
+            recorder.resized_pages
+        );
+    };
+    assert!((landscape.width - 297.0).abs() < 0.1 && (landscape.height - 210.0).abs() < 0.1);
+}
+
+#[test]
+fn a_cover_page_with_nothing_to_show_is_left_out() {
+    let options = PdfOptions {
+        cover_page: true,
+        ..Default::default()
+    };
+    let recorder = render_to_recorder(body(ONE_PARAGRAPH), &options).expect("the document renders");
+
+    assert_eq!(recorder.texts, ["Hello world"]);
+    assert!(recorder.new_pages.is_empty());
+}
+
+#[test]
+fn auto_caption_leaves_out_floating_images() {
+    let pixel = std::fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/pixel.png"
+    ))
+    .unwrap();
+    let package = body_with_parts(FLOATING_IMAGE, &[("media/pixel.png", &pixel)]);
+    let options = PdfOptions {
+        auto_caption: true,
+        ..Default::default()
+    };
+    let recorder = render_to_recorder(package, &options).expect("the document renders");
+
+    assert!(
+        recorder.trace.contains("image 25.4x25.4"),
+        "{}",
+        recorder.trace
+    );
+    assert_eq!(recorder.texts, ["Logo"]);
+}
+
+#[test]
+fn a_page_size_override_keeps_each_section_orientation() {
+    let options = PdfOptions {
+        page_size: Some(PageSize::LETTER),
+        ..Default::default()
+    };
+    let recorder =
+        render_to_recorder(body(LANDSCAPE_SECTION), &options).expect("the document renders");
+
+    let [landscape] = recorder.new_pages[..] else {
+        panic!("expected one new page, got {:?}", recorder.new_pages);
+    };
+    assert_eq!(landscape, PageSize::LETTER.oriented(true));
+}
+
+#[test]
+fn unsupported_inline_images_follow_the_unsupported_image_mode() {
+    let render = |mode| {
+        let package = body_with_parts(INLINE_IMAGE, &[("media/broken.png", b"not an image")]);
+        let options = PdfOptions {
+            on_unsupported_image: mode,
+            ..Default::default()
+        };
+        render_to_recorder(package, &options)
+    };
+
+    let skipped = render(UnsupportedImageMode::Skip).expect("the document renders");
+    assert_eq!(skipped.trace, "text Signed\n");
+    let placeholder = render(UnsupportedImageMode::Placeholder).expect("the document renders");
+    assert_eq!(placeholder.trace, "text Signed\nrect\n");
+    assert!(matches!(
+        render(UnsupportedImageMode::Fail),
+        Err(ConversionError::UnsupportedImage { .. })
+    ));
+}
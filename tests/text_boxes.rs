@@ -0,0 +1,41 @@
+mod common;
+
+use common::{body, render_to_recorder};
+use docx::{read_docx_from, DocContent, PdfOptions};
+use std::io::Cursor;
+
+// A text box anchored in a paragraph, as Word writes it: the shape in
+// mc:Choice and a VML copy of it in mc:Fallback.
+const TEXT_BOX: &str = r#"<w:p><w:r><w:t>Before</w:t></w:r><w:r><mc:AlternateContent><mc:Choice Requires="wps"><w:drawing><wp:anchor distT="0" distB="0" distL="0" distR="0" simplePos="0" relativeHeight="1" behindDoc="0" locked="0" layoutInCell="1" allowOverlap="1"><wp:simplePos x="0" y="0"/><wp:positionH relativeFrom="column"><wp:posOffset>0</wp:posOffset></wp:positionH><wp:positionV relativeFrom="paragraph"><wp:posOffset>0</wp:posOffset></wp:positionV><wp:extent cx="1828800" cy="457200"/><wp:wrapSquare wrapText="bothSides"/><wp:docPr id="1" name="Text Box 1"/><a:graphic><a:graphicData uri="http://schemas.microsoft.com/office/word/2010/wordprocessingShape"><wps:wsp><wps:txbx><w:txbxContent><w:p><w:r><w:t>Line one</w:t></w:r></w:p><w:p><w:r><w:t>Line two</w:t></w:r></w:p></w:txbxContent></wps:txbx><wps:bodyPr/></wps:wsp></a:graphicData></a:graphic></wp:anchor></w:drawing></mc:Choice><mc:Fallback><w:pict><v:shape><v:textbox><w:txbxContent><w:p><w:r><w:t>Fallback copy</w:t></w:r></w:p></w:txbxContent></v:textbox></v:shape></w:pict></mc:Fallback></mc:AlternateContent></w:r></w:p><w:p><w:r><w:t>After</w:t></w:r></w:p>"#;
+
+#[test]
+fn a_text_box_follows_its_paragraph_with_the_text_of_its_own_paragraphs() {
+    let document = read_docx_from(Cursor::new(body(TEXT_BOX)), &PdfOptions::default())
+        .expect("the document reads");
+
+    let text_boxes: Vec<String> = document
+        .content
+        .iter()
+        .filter_map(|item| match item {
+            DocContent::TextBox(runs) => Some(runs.iter().map(|run| run.text.as_str()).collect()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(text_boxes, ["Line one\nLine two"]);
+    assert!(matches!(document.content[1], DocContent::TextBox(_)));
+}
+
+#[test]
+fn a_text_box_is_drawn_as_a_bordered_block() {
+    let recorder =
+        render_to_recorder(body(TEXT_BOX), &PdfOptions::default()).expect("the document renders");
+
+    assert_eq!(recorder.texts, ["Before", "Line one", "Line two", "After"]);
+    assert!(
+        recorder
+            .trace
+            .contains("text Line one\ntext Line two\nrect\n"),
+        "{}",
+        recorder.trace
+    );
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::body;
+use docx::{
+    read_docx_from, DocContent, DocxDocument, PdfOptions, Strike, TextRun, TrackChangesMode,
+};
+use std::io::Cursor;
+
+const CHANGES: &str = r#"<w:p><w:r><w:t xml:space="preserve">The </w:t></w:r><w:del w:id="1" w:author="A" w:date="2024-01-01T00:00:00Z"><w:r><w:delText>old</w:delText></w:r></w:del><w:ins w:id="2" w:author="A" w:date="2024-01-01T00:00:00Z"><w:r><w:t>new</w:t></w:r></w:ins><w:r><w:t xml:space="preserve"> text</w:t></w:r></w:p><w:tbl><w:tr><w:tc><w:p><w:del w:id="3" w:author="A" w:date="2024-01-01T00:00:00Z"><w:r><w:delText>old</w:delText></w:r></w:del><w:ins w:id="4" w:author="A" w:date="2024-01-01T00:00:00Z"><w:r><w:t>new</w:t></w:r></w:ins></w:p></w:tc></w:tr></w:tbl>"#;
+
+fn read(mode: TrackChangesMode) -> DocxDocument {
+    let options = PdfOptions {
+        track_changes: mode,
+        ..Default::default()
+    };
+    read_docx_from(Cursor::new(body(CHANGES)), &options).expect("the document reads")
+}
+
+fn paragraph_text(document: &DocxDocument) -> String {
+    document.content[0]
+        .runs()
+        .iter()
+        .map(|run| run.text.as_str())
+        .collect()
+}
+
+fn cell_text(document: &DocxDocument) -> String {
+    let DocContent::Table { table, .. } = &document.content[1] else {
+        panic!("expected a table, got {:?}", document.content[1]);
+    };
+    table.rows[0].cells[0].text()
+}
+
+#[test]
+fn accept_keeps_insertions() {
+    let document = read(TrackChangesMode::Accept);
+    assert_eq!(paragraph_text(&document), "The new text");
+    assert_eq!(cell_text(&document), "new");
+}
+
+#[test]
+fn reject_keeps_deletions() {
+    let document = read(TrackChangesMode::Reject);
+    assert_eq!(paragraph_text(&document), "The old text");
+    assert_eq!(cell_text(&document), "old");
+}
+
+fn assert_marked(runs: &[TextRun]) {
+    let deleted = runs
+        .iter()
+        .find(|run| run.text == "old")
+        .expect("the deletion");
+    assert_eq!(deleted.strike, Strike::Single);
+    assert!(!deleted.underline);
+    let inserted = runs
+        .iter()
+        .find(|run| run.text == "new")
+        .expect("the insertion");
+    assert!(inserted.underline);
+    assert_eq!(inserted.strike, Strike::None);
+}
+
+#[test]
+fn show_marks_both_in_paragraphs_and_cells() {
+    let document = read(TrackChangesMode::Show);
+    assert_eq!(paragraph_text(&document), "The oldnew text");
+    assert_marked(document.content[0].runs());
+    assert_eq!(cell_text(&document), "oldnew");
+    let DocContent::Table { table, .. } = &document.content[1] else {
+        panic!("expected a table, got {:?}", document.content[1]);
+    };
+    assert_marked(&table.rows[0].cells[0].paragraphs[0]);
+}